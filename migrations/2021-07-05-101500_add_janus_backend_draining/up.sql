@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE janus_backend ADD COLUMN draining BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE janus_rtc_stream
+    DROP COLUMN slow_link_count,
+    DROP COLUMN media_loss_count;
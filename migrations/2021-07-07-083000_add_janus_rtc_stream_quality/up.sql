@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE janus_rtc_stream
+    ADD COLUMN slow_link_count INT NOT NULL DEFAULT 0,
+    ADD COLUMN media_loss_count INT NOT NULL DEFAULT 0;
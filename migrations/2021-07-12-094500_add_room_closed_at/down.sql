@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE room DROP COLUMN closed_at;
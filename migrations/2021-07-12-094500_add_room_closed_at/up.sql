@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE room ADD COLUMN closed_at TIMESTAMPTZ;
+
+-- Rooms closed before the migration must not be closed by the service once again.
+UPDATE room SET closed_at = upper(time) WHERE upper(time) <= now();
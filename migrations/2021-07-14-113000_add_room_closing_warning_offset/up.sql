@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE room ADD COLUMN closing_warning_offset INT;
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE room_ban (
+    room_id UUID NOT NULL,
+    account_id ACCOUNT_ID NOT NULL,
+    banned_by AGENT_ID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    PRIMARY KEY (room_id, account_id)
+);
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE room ADD COLUMN locked BOOLEAN NOT NULL DEFAULT FALSE;
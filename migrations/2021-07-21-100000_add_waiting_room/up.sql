@@ -0,0 +1,10 @@
+-- Your SQL goes here
+ALTER TABLE room ADD COLUMN requires_admission BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- `ALTER TYPE ... ADD VALUE` can't run inside a transaction so the type gets recreated.
+ALTER TYPE agent_status RENAME TO agent_status_old;
+CREATE TYPE agent_status AS ENUM ('in_progress', 'ready', 'waiting');
+ALTER TABLE agent ALTER COLUMN status DROP DEFAULT;
+ALTER TABLE agent ALTER COLUMN status TYPE agent_status USING status::text::agent_status;
+ALTER TABLE agent ALTER COLUMN status SET DEFAULT 'in_progress';
+DROP TYPE agent_status_old;
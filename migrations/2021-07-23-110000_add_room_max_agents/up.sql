@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE room ADD COLUMN max_agents INT CHECK (max_agents > 0);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX room_parent_id_idx;
+ALTER TABLE room DROP COLUMN parent_id;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE agent_session;
+DROP TYPE agent_session_leave_reason;
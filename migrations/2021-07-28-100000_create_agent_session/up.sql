@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TYPE agent_session_leave_reason AS ENUM ('left', 'kicked', 'banned', 'moved', 'closed');
+
+CREATE TABLE agent_session (
+    id UUID DEFAULT gen_random_uuid(),
+    agent_id AGENT_ID NOT NULL,
+    room_id UUID NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ,
+    leave_reason agent_session_leave_reason,
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX agent_session_room_id_agent_id_idx ON agent_session (room_id, agent_id);
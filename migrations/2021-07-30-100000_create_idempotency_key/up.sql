@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE idempotency_key (
+    audience TEXT NOT NULL,
+    key TEXT NOT NULL,
+    room_id UUID,
+    rtc_id UUID,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    FOREIGN KEY (rtc_id) REFERENCES rtc (id) ON DELETE CASCADE,
+    CHECK ((room_id IS NULL) <> (rtc_id IS NULL)),
+    PRIMARY KEY (audience, key)
+);
+
+CREATE INDEX idempotency_key_created_at_idx ON idempotency_key (created_at);
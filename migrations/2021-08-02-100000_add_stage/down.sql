@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE stage_member;
+DROP TYPE stage_member_status;
+
+ALTER TABLE room DROP COLUMN stage_mode;
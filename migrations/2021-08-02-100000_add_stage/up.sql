@@ -0,0 +1,15 @@
+-- Your SQL goes here
+ALTER TABLE room ADD COLUMN stage_mode BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TYPE stage_member_status AS ENUM ('invited', 'accepted');
+
+CREATE TABLE stage_member (
+    room_id UUID NOT NULL,
+    agent_id AGENT_ID NOT NULL,
+    status stage_member_status NOT NULL DEFAULT 'invited',
+    invited_by AGENT_ID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    PRIMARY KEY (room_id, agent_id)
+);
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE raised_hand (
+    room_id UUID NOT NULL,
+    agent_id AGENT_ID NOT NULL,
+    raised_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    PRIMARY KEY (room_id, agent_id)
+);
+
+CREATE INDEX raised_hand_room_id_raised_at_idx ON raised_hand (room_id, raised_at);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE room DROP COLUMN video_remb;
+ALTER TABLE room DROP COLUMN quality_preset;
+
+DROP TYPE quality_preset;
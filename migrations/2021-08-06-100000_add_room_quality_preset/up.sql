@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TYPE quality_preset AS ENUM ('low', 'medium', 'high', 'audio_only');
+
+ALTER TABLE room ADD COLUMN quality_preset quality_preset NULL;
+ALTER TABLE room ADD COLUMN video_remb BIGINT NULL;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX janus_rtc_stream_time_upper_idx;
+DROP INDEX janus_rtc_stream_time_lower_idx;
+DROP INDEX agent_session_finished_at_idx;
+DROP INDEX agent_session_started_at_idx;
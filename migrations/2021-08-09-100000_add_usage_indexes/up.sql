@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE INDEX agent_session_started_at_idx ON agent_session (started_at);
+CREATE INDEX agent_session_finished_at_idx ON agent_session (finished_at);
+CREATE INDEX janus_rtc_stream_time_lower_idx ON janus_rtc_stream (LOWER(time));
+CREATE INDEX janus_rtc_stream_time_upper_idx ON janus_rtc_stream (UPPER(time));
@@ -0,0 +1,26 @@
+-- Room and rtc keys can't share the same key anymore.
+DELETE FROM idempotency_key AS ik
+WHERE ik.kind = 'rtc'
+AND   EXISTS (
+    SELECT 1
+    FROM idempotency_key
+    WHERE audience = ik.audience
+    AND   key = ik.key
+    AND   kind = 'room'
+);
+
+ALTER TABLE idempotency_key
+    DROP CONSTRAINT idempotency_key_pkey,
+    DROP CONSTRAINT idempotency_key_kind_check,
+    ALTER COLUMN room_id DROP NOT NULL;
+
+UPDATE idempotency_key
+SET room_id = NULL
+WHERE kind = 'rtc';
+
+ALTER TABLE idempotency_key
+    ADD CONSTRAINT idempotency_key_check CHECK ((room_id IS NULL) <> (rtc_id IS NULL)),
+    ADD PRIMARY KEY (audience, key),
+    DROP COLUMN kind;
+
+DROP TYPE idempotency_key_kind;
@@ -0,0 +1,24 @@
+CREATE TYPE idempotency_key_kind AS ENUM ('room', 'rtc');
+
+ALTER TABLE idempotency_key ADD COLUMN kind idempotency_key_kind;
+
+UPDATE idempotency_key
+SET kind = 'room'
+WHERE rtc_id IS NULL;
+
+-- Keys of rtcs also store the room the rtc has been created in.
+UPDATE idempotency_key AS ik
+SET kind = 'rtc',
+    room_id = rtc.room_id
+FROM rtc
+WHERE rtc.id = ik.rtc_id;
+
+ALTER TABLE idempotency_key
+    ALTER COLUMN kind SET NOT NULL,
+    ALTER COLUMN room_id SET NOT NULL,
+    DROP CONSTRAINT idempotency_key_check,
+    DROP CONSTRAINT idempotency_key_pkey;
+
+ALTER TABLE idempotency_key
+    ADD CONSTRAINT idempotency_key_kind_check CHECK ((kind = 'rtc') = (rtc_id IS NOT NULL)),
+    ADD PRIMARY KEY (audience, kind, key);
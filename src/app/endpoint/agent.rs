@@ -1,10 +1,26 @@
+use std::result::Result as StdResult;
+
+use anyhow::anyhow;
 use async_std::{stream, task};
 use async_trait::async_trait;
+use chrono::Duration;
+use diesel::{pg::PgConnection, Connection};
 use serde::Deserialize;
-use svc_agent::mqtt::{IncomingRequestProperties, ResponseStatus};
+use serde_json::json;
+use svc_agent::{
+    mqtt::{IncomingRequestProperties, ResponseStatus},
+    Addressable, AgentId, Authenticable,
+};
 
 use crate::{
-    app::{context::Context, endpoint::prelude::*, metrics::HistogramExt},
+    app::{
+        context::Context,
+        endpoint::{
+            prelude::*,
+            subscription::{self, LeaveReason, RoomEnterLeaveEvent},
+        },
+        metrics::HistogramExt,
+    },
     db,
 };
 
@@ -78,6 +94,353 @@ impl RequestHandler for ListHandler {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Deserialize)]
+pub struct KickRequest {
+    room_id: db::room::Id,
+    agent_id: AgentId,
+}
+
+pub struct KickHandler;
+
+#[async_trait]
+impl RequestHandler for KickHandler {
+    type Payload = KickRequest;
+    const ERROR_TITLE: &'static str = "Failed to kick agent";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let messages = remove_agent(context, payload, reqp, LeaveReason::Kicked).await?;
+
+        context
+            .metrics()
+            .request_duration
+            .agent_kick
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(messages)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+pub type BanRequest = KickRequest;
+pub struct BanHandler;
+
+#[async_trait]
+impl RequestHandler for BanHandler {
+    type Payload = BanRequest;
+    const ERROR_TITLE: &'static str = "Failed to ban agent";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let messages = remove_agent(context, payload, reqp, LeaveReason::Banned).await?;
+
+        context
+            .metrics()
+            .request_duration
+            .agent_ban
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(messages)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+pub type AdmitRequest = KickRequest;
+pub struct AdmitHandler;
+
+#[async_trait]
+impl RequestHandler for AdmitHandler {
+    type Payload = AdmitRequest;
+    const ERROR_TITLE: &'static str = "Failed to admit agent";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let (room, authz_time) = find_room_and_authorize(context, &payload, reqp).await?;
+
+        // Move the agent out of the waiting room if the room is not full.
+        let conn = context.get_conn().await?;
+        let result = task::spawn_blocking({
+            let agent_id = payload.agent_id.clone();
+            let room_id = room.id();
+            let maybe_max_agents = room.max_agents();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    find_waiting_agent(&agent_id, room_id, &conn)?;
+
+                    if let Some(max_agents) = maybe_max_agents {
+                        helpers::check_room_capacity(room_id, max_agents, &agent_id, &conn)?;
+                    }
+
+                    db::agent::UpdateQuery::new(&agent_id, room_id)
+                        .status(db::agent::Status::InProgress)
+                        .execute(&conn)?;
+
+                    Ok(())
+                })
+            }
+        })
+        .await;
+
+        if let Err(ref err) = result {
+            if err.error_kind() == AppErrorKind::RoomFull {
+                context.metrics().observe_room_full(room.audience());
+            }
+        }
+
+        result?;
+
+        // Subscribe the agent to the room's events. The response to the admission request
+        // and `room.enter` notification are being sent on the broker's response.
+        let request = helpers::build_subscription_request(
+            context,
+            reqp,
+            "subscription.create",
+            payload.agent_id,
+            room.id(),
+            CorrelationData::SubscriptionCreate,
+            Some(authz_time),
+        )?;
+
+        context
+            .metrics()
+            .request_duration
+            .agent_admit
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::once(request)))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+pub type RejectRequest = KickRequest;
+pub struct RejectHandler;
+
+#[async_trait]
+impl RequestHandler for RejectHandler {
+    type Payload = RejectRequest;
+    const ERROR_TITLE: &'static str = "Failed to reject agent";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let (room, authz_time) = find_room_and_authorize(context, &payload, reqp).await?;
+
+        // Remove the agent from the waiting room.
+        let conn = context.get_conn().await?;
+        task::spawn_blocking({
+            let agent_id = payload.agent_id.clone();
+            let room_id = room.id();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    find_waiting_agent(&agent_id, room_id, &conn)?;
+
+                    db::agent::DeleteQuery::new()
+                        .agent_id(&agent_id)
+                        .room_id(room_id)
+                        .execute(&conn)?;
+
+                    Ok(())
+                })
+            }
+        })
+        .await?;
+
+        let response = helpers::build_response(
+            ResponseStatus::OK,
+            json!({}),
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        let notification = helpers::build_notification(
+            "agent.reject",
+            &format!("rooms/{}/events", room.id()),
+            RoomEnterLeaveEvent::new(room.id(), payload.agent_id),
+            reqp,
+            context.start_timestamp(),
+        );
+
+        context
+            .metrics()
+            .request_duration
+            .agent_reject
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::from_iter(vec![response, notification])))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// Finds the room and authorizes managing the agent in it.
+async fn find_room_and_authorize<C: Context>(
+    context: &mut C,
+    payload: &KickRequest,
+    reqp: &IncomingRequestProperties,
+) -> StdResult<(db::room::Object, Duration), AppError> {
+    let conn = context.get_conn().await?;
+    let room = task::spawn_blocking({
+        let room_id = payload.room_id;
+        move || helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::NotClosed, &conn)
+    })
+    .await?;
+    helpers::add_room_logger_tags(context, &room);
+
+    let room_id = room.id().to_string();
+    let agent_id = payload.agent_id.to_string();
+    let object = vec!["rooms", &room_id, "agents", &agent_id];
+
+    let authz_time = context
+        .authz()
+        .authorize(room.audience(), reqp, object, "update")
+        .await?;
+    context.metrics().observe_auth(authz_time);
+
+    Ok((room, authz_time))
+}
+
+fn find_waiting_agent(
+    agent_id: &AgentId,
+    room_id: db::room::Id,
+    conn: &PgConnection,
+) -> StdResult<db::agent::Object, AppError> {
+    match db::agent::FindQuery::new(agent_id, room_id).execute(conn)? {
+        Some(agent) if agent.status() == db::agent::Status::Waiting => Ok(agent),
+        _ => Err(anyhow!("Agent is not waiting in the room"))
+            .error(AppErrorKind::AgentNotEnteredTheRoom),
+    }
+}
+
+async fn remove_agent<C: Context>(
+    context: &mut C,
+    payload: KickRequest,
+    reqp: &IncomingRequestProperties,
+    reason: LeaveReason,
+) -> Result {
+    let (room, authz_time) = find_room_and_authorize(context, &payload, reqp).await?;
+
+    // Forbid entering the room to all agents of the banned account.
+    if reason == LeaveReason::Banned {
+        let conn = context.get_conn().await?;
+        task::spawn_blocking({
+            let room_id = room.id();
+            let account_id = payload.agent_id.as_account_id().to_owned();
+            let banned_by = reqp.as_agent_id().to_owned();
+
+            move || db::room_ban::InsertQuery::new(room_id, &account_id, &banned_by).execute(&conn)
+        })
+        .await?;
+    }
+
+    // Kicking removes the agent only while the ban applies to all the account's agents.
+    let conn = context.get_conn().await?;
+    let agents = task::spawn_blocking({
+        let room_id = room.id();
+        let agent_id = payload.agent_id.clone();
+
+        move || match reason {
+            LeaveReason::Kicked => db::agent::FindQuery::new(&agent_id, room_id)
+                .execute(&conn)
+                .map(|maybe_agent| maybe_agent.into_iter().collect::<Vec<_>>()),
+            LeaveReason::Banned => {
+                db::agent::ListByAccountQuery::new(agent_id.as_account_id(), room_id).execute(&conn)
+            }
+        }
+    })
+    .await?;
+
+    let mut messages = vec![helpers::build_response(
+        ResponseStatus::OK,
+        json!({}),
+        reqp,
+        context.start_timestamp(),
+        Some(authz_time),
+    )];
+
+    for agent in agents {
+        let agent_id = agent.agent_id().to_owned();
+
+        // The agent waiting for admission has neither entered the room nor been subscribed
+        // to its events so just reject it.
+        if agent.status() == db::agent::Status::Waiting {
+            let conn = context.get_conn().await?;
+            task::spawn_blocking({
+                let agent_id = agent_id.clone();
+                let room_id = room.id();
+
+                move || {
+                    db::agent::DeleteQuery::new()
+                        .agent_id(&agent_id)
+                        .room_id(room_id)
+                        .execute(&conn)
+                }
+            })
+            .await?;
+
+            messages.push(helpers::build_notification(
+                "agent.reject",
+                &format!("rooms/{}/events", room.id()),
+                RoomEnterLeaveEvent::new(room.id(), agent_id),
+                reqp,
+                context.start_timestamp(),
+            ));
+
+            continue;
+        }
+
+        // Delete the agent with its connections and send `agent.leave` to Janus.
+        if !subscription::leave_room(context, &agent_id, room.id(), reason.into()).await? {
+            continue;
+        }
+
+        messages.push(helpers::build_notification(
+            "room.leave",
+            &format!("rooms/{}/events", room.id()),
+            RoomEnterLeaveEvent::new(room.id(), agent_id.clone()).reason(reason),
+            reqp,
+            context.start_timestamp(),
+        ));
+
+        // Unsubscribe the agent from the room's events.
+        messages.push(helpers::build_subscription_request(
+            context,
+            reqp,
+            "subscription.delete",
+            agent_id,
+            room.id(),
+            CorrelationData::AgentKick,
+            None,
+        )?);
+    }
+
+    // It's fine to ban an agent in advance.
+    if messages.len() == 1 && reason == LeaveReason::Kicked {
+        return Err(anyhow!("Agent is not online in the room"))
+            .error(AppErrorKind::AgentNotEnteredTheRoom);
+    }
+
+    Ok(Box::new(stream::from_iter(messages)))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[cfg(test)]
 mod tests {
     mod list {
@@ -236,4 +599,719 @@ mod tests {
             assert_eq!(err.kind(), "room_not_found");
         }
     }
+
+    mod kick {
+        use serde::Deserialize;
+        use serde_json::Value as JsonValue;
+        use svc_agent::AgentId;
+
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+        use super::super::*;
+
+        ///////////////////////////////////////////////////////////////////////////
+
+        #[derive(Deserialize)]
+        struct DynSubRequest {
+            subject: AgentId,
+            object: Vec<String>,
+        }
+
+        #[async_std::test]
+        async fn kick_agent() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                let rtc = shared_helpers::insert_rtc_with_room(&conn, &room);
+                shared_helpers::insert_connected_agent(
+                    &conn,
+                    student.agent_id(),
+                    room.id(),
+                    rtc.id(),
+                );
+                room
+            };
+
+            // Allow the moderator to kick the student.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            // Make agent.kick request.
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = KickRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<KickHandler>(&mut context, &moderator, payload)
+                .await
+                .expect("Agent kick failed");
+
+            // Assert response.
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            // Assert notification.
+            let (event, evp, topic) = find_event::<JsonValue>(messages.as_slice());
+            assert_eq!(evp.label(), "room.leave");
+            assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+            assert_eq!(event["agent_id"], student_id);
+            assert_eq!(event["reason"], "kicked");
+
+            // Assert dynamic subscription deletion request.
+            let (payload, reqp, _) = find_request::<DynSubRequest>(messages.as_slice());
+            assert_eq!(reqp.method(), "subscription.delete");
+            assert_eq!(&payload.subject, student.agent_id());
+            assert_eq!(payload.object, vec!["rooms", &room_id, "events"]);
+
+            // Assert the agent and its connections are deleted.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let agents = db::agent::ListQuery::new()
+                .room_id(room.id())
+                .execute(&conn)
+                .expect("Failed to list agents");
+
+            assert!(agents.is_empty());
+
+            let connections = db::agent_connection::ListByRoomQuery::new(room.id())
+                .execute(&conn)
+                .expect("Failed to list agent connections");
+
+            assert!(connections.is_empty());
+        }
+
+        #[async_std::test]
+        async fn kick_agent_not_entered() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_room(&conn)
+            };
+
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            let mut context = TestContext::new(db, authz);
+
+            let payload = KickRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let err = handle_request::<KickHandler>(&mut context, &moderator, payload)
+                .await
+                .expect_err("Unexpected success on agent kick");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "agent_not_entered_the_room");
+        }
+
+        #[async_std::test]
+        async fn kick_agent_not_authorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+            let other_student = TestAgent::new("web", "other_student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), room.id());
+                room
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let payload = KickRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let err = handle_request::<KickHandler>(&mut context, &other_student, payload)
+                .await
+                .expect_err("Unexpected success on agent kick");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+
+    mod ban {
+        use serde_json::Value as JsonValue;
+
+        use crate::test_helpers::{
+            outgoing_envelope::OutgoingEnvelopeProperties, prelude::*, test_deps::LocalDeps,
+        };
+
+        use super::super::*;
+
+        ///////////////////////////////////////////////////////////////////////////
+
+        #[async_std::test]
+        async fn ban_agent() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), room.id());
+                room
+            };
+
+            // Allow the moderator to ban the student.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            // Make agent.ban request.
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = BanRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<BanHandler>(&mut context, &moderator, payload)
+                .await
+                .expect("Agent ban failed");
+
+            let (event, evp, _) = find_event::<JsonValue>(messages.as_slice());
+            assert_eq!(evp.label(), "room.leave");
+            assert_eq!(event["reason"], "banned");
+
+            // Assert the student's account is banned.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let maybe_ban = db::room_ban::FindQuery::new(room.id(), student.account_id())
+                .execute(&conn)
+                .expect("Failed to find room ban");
+
+            assert!(maybe_ban.is_some());
+        }
+
+        #[async_std::test]
+        async fn ban_all_account_agents() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+            let student_mobile = TestAgent::new("mobile", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), room.id());
+                shared_helpers::insert_agent(&conn, student_mobile.agent_id(), room.id());
+                room
+            };
+
+            // Allow the moderator to ban the student.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            // Make agent.ban request.
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = BanRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<BanHandler>(&mut context, &moderator, payload)
+                .await
+                .expect("Agent ban failed");
+
+            // Both of the student's agents must leave the room.
+            let leave_events_count = messages
+                .iter()
+                .filter(|message| match message.properties() {
+                    OutgoingEnvelopeProperties::Event(evp) => evp.label() == "room.leave",
+                    _ => false,
+                })
+                .count();
+
+            assert_eq!(leave_events_count, 2);
+
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let agents = db::agent::ListByAccountQuery::new(student.account_id(), room.id())
+                .execute(&conn)
+                .expect("Failed to list agents");
+
+            assert!(agents.is_empty());
+        }
+
+        #[async_std::test]
+        async fn ban_agent_in_advance() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_room(&conn)
+            };
+
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            let mut context = TestContext::new(db, authz);
+
+            let payload = BanRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<BanHandler>(&mut context, &moderator, payload)
+                .await
+                .expect("Agent ban failed");
+
+            // Nobody to notify since the agent is not in the room.
+            assert_eq!(messages.len(), 1);
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+        }
+
+        #[async_std::test]
+        async fn ban_waiting_agent() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                factory::Agent::new()
+                    .agent_id(student.agent_id())
+                    .room_id(room.id())
+                    .status(db::agent::Status::Waiting)
+                    .insert(&conn);
+
+                room
+            };
+
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = BanRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<BanHandler>(&mut context, &moderator, payload)
+                .await
+                .expect("Agent ban failed");
+
+            // The waiting agent gets rejected without leaving the room or unsubscribing.
+            assert_eq!(messages.len(), 2);
+
+            let (event, evp, _) = find_event::<JsonValue>(messages.as_slice());
+            assert_eq!(evp.label(), "agent.reject");
+            assert_eq!(event["agent_id"], student_id);
+
+            assert!(messages.iter().all(|message| !matches!(
+                message.properties(),
+                OutgoingEnvelopeProperties::Request(_)
+            )));
+
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let maybe_agent = db::agent::FindQuery::new(student.agent_id(), room.id())
+                .execute(&conn)
+                .expect("Failed to find agent");
+
+            assert!(maybe_agent.is_none());
+        }
+    }
+
+    mod admit {
+        use serde::Deserialize;
+        use svc_agent::AgentId;
+
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+        use super::super::*;
+
+        ///////////////////////////////////////////////////////////////////////////
+
+        #[derive(Deserialize)]
+        struct DynSubRequest {
+            subject: AgentId,
+            object: Vec<String>,
+        }
+
+        #[async_std::test]
+        async fn admit_agent() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                factory::Agent::new()
+                    .agent_id(student.agent_id())
+                    .room_id(room.id())
+                    .status(db::agent::Status::Waiting)
+                    .insert(&conn);
+
+                room
+            };
+
+            // Allow the moderator to admit the student.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            // Make agent.admit request.
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = AdmitRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<AdmitHandler>(&mut context, &moderator, payload)
+                .await
+                .expect("Agent admission failed");
+
+            // Assert dynamic subscription request for the student.
+            let (payload, reqp, _) = find_request::<DynSubRequest>(messages.as_slice());
+            assert_eq!(reqp.method(), "subscription.create");
+            assert_eq!(&payload.subject, student.agent_id());
+            assert_eq!(payload.object, vec!["rooms", &room_id, "events"]);
+
+            // Assert the student is not waiting anymore.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let agent = db::agent::FindQuery::new(student.agent_id(), room.id())
+                .execute(&conn)
+                .expect("Failed to find agent")
+                .expect("Agent not found");
+
+            assert_eq!(agent.status(), db::agent::Status::InProgress);
+        }
+
+        #[async_std::test]
+        async fn admit_agent_to_full_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+            let other_student = TestAgent::new("web", "other_student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                let room = db::room::UpdateQuery::new(room.id())
+                    .max_agents(Some(Some(1)))
+                    .execute(&conn)
+                    .expect("Failed to update room");
+
+                factory::Agent::new()
+                    .agent_id(student.agent_id())
+                    .room_id(room.id())
+                    .status(db::agent::Status::Waiting)
+                    .insert(&conn);
+
+                // The only slot is taken by another agent which is still entering.
+                factory::Agent::new()
+                    .agent_id(other_student.agent_id())
+                    .room_id(room.id())
+                    .status(db::agent::Status::InProgress)
+                    .insert(&conn);
+
+                room
+            };
+
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = AdmitRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let err = handle_request::<AdmitHandler>(&mut context, &moderator, payload)
+                .await
+                .expect_err("Unexpected success on agent admission");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "room_full");
+
+            // Assert the student is still waiting.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let agent = db::agent::FindQuery::new(student.agent_id(), room.id())
+                .execute(&conn)
+                .expect("Failed to find agent")
+                .expect("Agent not found");
+
+            assert_eq!(agent.status(), db::agent::Status::Waiting);
+        }
+
+        #[async_std::test]
+        async fn admit_agent_not_waiting() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), room.id());
+                room
+            };
+
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            let mut context = TestContext::new(db, authz);
+
+            let payload = AdmitRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let err = handle_request::<AdmitHandler>(&mut context, &moderator, payload)
+                .await
+                .expect_err("Unexpected success on agent admission");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "agent_not_entered_the_room");
+        }
+    }
+
+    mod reject {
+        use serde_json::Value as JsonValue;
+
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+        use super::super::*;
+
+        ///////////////////////////////////////////////////////////////////////////
+
+        #[async_std::test]
+        async fn reject_agent() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                factory::Agent::new()
+                    .agent_id(student.agent_id())
+                    .room_id(room.id())
+                    .status(db::agent::Status::Waiting)
+                    .insert(&conn);
+
+                room
+            };
+
+            // Allow the moderator to reject the student.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                moderator.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            // Make agent.reject request.
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = RejectRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<RejectHandler>(&mut context, &moderator, payload)
+                .await
+                .expect("Agent rejection failed");
+
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            let (event, evp, topic) = find_event::<JsonValue>(messages.as_slice());
+            assert_eq!(evp.label(), "agent.reject");
+            assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+            assert_eq!(event["agent_id"], student_id);
+
+            // Assert the student is removed from the waiting room.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let maybe_agent = db::agent::FindQuery::new(student.agent_id(), room.id())
+                .execute(&conn)
+                .expect("Failed to find agent");
+
+            assert!(maybe_agent.is_none());
+        }
+    }
 }
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use crate::{
     app::{context::Context, endpoint::prelude::*, metrics::HistogramExt},
     backend::janus::client::update_agent_reader_config::{
@@ -34,7 +32,7 @@ impl State {
         let configs = rtc_reader_configs
             .iter()
             .map(|(rtc_reader_config, rtc)| {
-                StateConfigItem::new(rtc.created_by().to_owned())
+                StateConfigItem::new(rtc)
                     .receive_video(rtc_reader_config.receive_video())
                     .receive_audio(rtc_reader_config.receive_audio())
             })
@@ -46,15 +44,19 @@ impl State {
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StateConfigItem {
-    agent_id: AgentId,
+    #[serde(default)]
+    agent_id: Option<AgentId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rtc_id: Option<db::rtc::Id>,
     receive_video: Option<bool>,
     receive_audio: Option<bool>,
 }
 
 impl StateConfigItem {
-    fn new(agent_id: AgentId) -> Self {
+    fn new(rtc: &Rtc) -> Self {
         Self {
-            agent_id,
+            agent_id: Some(rtc.created_by().to_owned()),
+            rtc_id: Some(rtc.id()),
             receive_video: None,
             receive_audio: None,
         }
@@ -104,43 +106,26 @@ impl RequestHandler for UpdateHandler {
                     &conn,
                 )?;
 
-                if room.rtc_sharing_policy() != db::rtc::SharingPolicy::Owned {
-                    return Err(anyhow!(
-                    "Agent reader config is available only for rooms with owned RTC sharing policy"
-                ))
-                    .error(AppErrorKind::InvalidPayload)?;
-                }
+                helpers::check_rtc_configs_availability(&room)?;
 
                 helpers::check_room_presence(&room, &agent_id, &conn)?;
 
                 let rtc_reader_configs_with_rtcs = conn.transaction::<_, AppError, _>(|| {
-                    // Find RTCs owned by agents.
-                    let agent_ids = payload
-                        .configs
-                        .iter()
-                        .map(|c| &c.agent_id)
-                        .collect::<Vec<_>>();
-
                     let rtcs = db::rtc::ListQuery::new()
                         .room_id(room.id())
-                        .created_by(agent_ids.as_slice())
                         .execute(&conn)?;
 
-                    let agents_to_rtcs = rtcs
-                        .iter()
-                        .map(|rtc| (rtc.created_by(), rtc.id()))
-                        .collect::<HashMap<_, _>>();
-
                     // Create or update the config.
                     for state_config_item in payload.configs {
-                        let rtc_id = agents_to_rtcs
-                            .get(&state_config_item.agent_id)
-                            .ok_or_else(|| {
-                                anyhow!("{} has no owned RTC", state_config_item.agent_id)
-                            })
-                            .error(AppErrorKind::InvalidPayload)?;
+                        let rtc_id = helpers::find_config_rtc(
+                            &room,
+                            &rtcs,
+                            state_config_item.rtc_id,
+                            state_config_item.agent_id.as_ref(),
+                        )?
+                        .id();
 
-                        let mut q = db::rtc_reader_config::UpsertQuery::new(*rtc_id, &agent_id);
+                        let mut q = db::rtc_reader_config::UpsertQuery::new(rtc_id, &agent_id);
 
                         if let Some(receive_video) = state_config_item.receive_video {
                             q = q.receive_video(receive_video);
@@ -247,12 +232,7 @@ impl RequestHandler for ReadHandler {
                     &conn,
                 )?;
 
-                if room.rtc_sharing_policy() != db::rtc::SharingPolicy::Owned {
-                    return Err(anyhow!(
-                    "Agent reader config is available only for rooms with owned RTC sharing policy"
-                ))
-                    .error(AppErrorKind::InvalidPayload)?;
-                }
+                helpers::check_rtc_configs_availability(&room)?;
 
                 helpers::check_room_presence(&room, &agent_id, &conn)?;
 
@@ -348,12 +328,14 @@ mod tests {
                 room_id: room.id(),
                 configs: vec![
                     StateConfigItem {
-                        agent_id: agent2.agent_id().to_owned(),
+                        agent_id: Some(agent2.agent_id().to_owned()),
+                        rtc_id: None,
                         receive_video: Some(true),
                         receive_audio: Some(false),
                     },
                     StateConfigItem {
-                        agent_id: agent3.agent_id().to_owned(),
+                        agent_id: Some(agent3.agent_id().to_owned()),
+                        rtc_id: None,
                         receive_video: Some(false),
                         receive_audio: Some(false),
                     },
@@ -373,7 +355,7 @@ mod tests {
             let agent2_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent2.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent2.agent_id()))
                 .expect("Config for agent2 not found");
 
             assert_eq!(agent2_config.receive_video, Some(true));
@@ -382,7 +364,7 @@ mod tests {
             let agent3_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent3.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent3.agent_id()))
                 .expect("Config for agent3 not found");
 
             assert_eq!(agent3_config.receive_video, Some(false));
@@ -393,12 +375,14 @@ mod tests {
                 room_id: room.id(),
                 configs: vec![
                     StateConfigItem {
-                        agent_id: agent4.agent_id().to_owned(),
+                        agent_id: Some(agent4.agent_id().to_owned()),
+                        rtc_id: None,
                         receive_video: Some(true),
                         receive_audio: Some(true),
                     },
                     StateConfigItem {
-                        agent_id: agent3.agent_id().to_owned(),
+                        agent_id: Some(agent3.agent_id().to_owned()),
+                        rtc_id: None,
                         receive_video: None,
                         receive_audio: Some(true),
                     },
@@ -418,7 +402,7 @@ mod tests {
             let agent2_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent2.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent2.agent_id()))
                 .expect("Config for agent2 not found");
 
             assert_eq!(agent2_config.receive_video, Some(true));
@@ -427,7 +411,7 @@ mod tests {
             let agent3_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent3.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent3.agent_id()))
                 .expect("Config for agent3 not found");
 
             assert_eq!(agent3_config.receive_video, Some(false));
@@ -436,7 +420,7 @@ mod tests {
             let agent4_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent4.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent4.agent_id()))
                 .expect("Config for agent4 not found");
 
             assert_eq!(agent4_config.receive_video, Some(true));
@@ -446,6 +430,62 @@ mod tests {
             Ok(())
         }
 
+        #[async_std::test]
+        async fn update_agent_reader_config_in_shared_room() -> std::io::Result<()> {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user1", USR_AUDIENCE);
+            let presenter = TestAgent::new("web", "presenter", USR_AUDIENCE);
+
+            // Insert a shared RTC room with a single RTC created by the presenter.
+            let (room, rtc) = db
+                .connection_pool()
+                .get()
+                .map(|conn| {
+                    let room = factory::Room::new()
+                        .audience(USR_AUDIENCE)
+                        .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                        .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                        .insert(&conn);
+
+                    shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
+
+                    let rtc = factory::Rtc::new(room.id())
+                        .created_by(presenter.agent_id().to_owned())
+                        .insert(&conn);
+
+                    (room, rtc)
+                })
+                .unwrap();
+
+            // Make agent_reader_config.update request referring to the RTC by its id.
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let payload = State {
+                room_id: room.id(),
+                configs: vec![StateConfigItem {
+                    agent_id: None,
+                    rtc_id: Some(rtc.id()),
+                    receive_video: None,
+                    receive_audio: Some(false),
+                }],
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Agent reader config update failed");
+
+            // Assert response.
+            let (state, respp, _) = find_response::<State>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(state.configs.len(), 1);
+            assert_eq!(state.configs[0].rtc_id, Some(rtc.id()));
+            assert_eq!(state.configs[0].receive_video, Some(true));
+            assert_eq!(state.configs[0].receive_audio, Some(false));
+            Ok(())
+        }
+
         #[async_std::test]
         async fn too_many_config_items() -> std::io::Result<()> {
             // Make agent_reader_config.update request.
@@ -460,7 +500,8 @@ mod tests {
                     let agent = TestAgent::new("web", &format!("user{}", i), USR_AUDIENCE);
 
                     StateConfigItem {
-                        agent_id: agent.agent_id().to_owned(),
+                        agent_id: Some(agent.agent_id().to_owned()),
+                        rtc_id: None,
                         receive_video: Some(false),
                         receive_audio: Some(true),
                     }
@@ -514,7 +555,8 @@ mod tests {
             let payload = State {
                 room_id: room.id(),
                 configs: vec![StateConfigItem {
-                    agent_id: agent2.agent_id().to_owned(),
+                    agent_id: Some(agent2.agent_id().to_owned()),
+                    rtc_id: None,
                     receive_video: Some(false),
                     receive_audio: Some(true),
                 }],
@@ -622,7 +664,7 @@ mod tests {
                     let room = factory::Room::new()
                         .audience(USR_AUDIENCE)
                         .time((Bound::Included(Utc::now()), Bound::Unbounded))
-                        .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                        .rtc_sharing_policy(RtcSharingPolicy::None)
                         .insert(&conn);
 
                     shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
@@ -748,7 +790,7 @@ mod tests {
             let agent2_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent2.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent2.agent_id()))
                 .expect("Config for agent2 not found");
 
             assert_eq!(agent2_config.receive_video, Some(true));
@@ -757,7 +799,7 @@ mod tests {
             let agent3_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent3.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent3.agent_id()))
                 .expect("Config for agent3 not found");
 
             assert_eq!(agent3_config.receive_video, Some(false));
@@ -852,7 +894,7 @@ mod tests {
                     let room = factory::Room::new()
                         .audience(USR_AUDIENCE)
                         .time((Bound::Included(Utc::now()), Bound::Unbounded))
-                        .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                        .rtc_sharing_policy(RtcSharingPolicy::None)
                         .insert(&conn);
 
                     shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::result::Result as StdResult;
 
 use crate::{
     app::{context::Context, endpoint::prelude::*, metrics::HistogramExt},
@@ -7,7 +7,7 @@ use crate::{
         UpdateWriterConfigRequestBodyConfigItem,
     },
     db,
-    db::{rtc::Object as Rtc, rtc_writer_config::Object as RtcWriterConfig},
+    db::{room::QualityPreset, rtc::Object as Rtc, rtc_writer_config::Object as RtcWriterConfig},
 };
 use anyhow::anyhow;
 use async_std::{stream, task};
@@ -30,14 +30,14 @@ pub struct State {
 }
 
 impl State {
-    fn new(
+    pub(crate) fn new(
         room_id: db::room::Id,
         rtc_writer_configs_with_rtcs: &[(RtcWriterConfig, Rtc)],
     ) -> State {
         let configs = rtc_writer_configs_with_rtcs
             .iter()
             .map(|(rtc_writer_config, rtc)| {
-                let mut config_item = StateConfigItem::new(rtc.created_by().to_owned())
+                let mut config_item = StateConfigItem::new(rtc)
                     .send_video(rtc_writer_config.send_video())
                     .send_audio(rtc_writer_config.send_audio());
 
@@ -60,21 +60,29 @@ impl State {
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StateConfigItem {
-    agent_id: AgentId,
+    #[serde(default)]
+    agent_id: Option<AgentId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rtc_id: Option<db::rtc::Id>,
     send_video: Option<bool>,
     send_audio: Option<bool>,
     video_remb: Option<u32>,
+    /// Translated to `send_video` and `video_remb` unless they're set explicitly.
+    #[serde(default, skip_serializing)]
+    quality_preset: Option<QualityPreset>,
     #[cfg_attr(not(test), serde(skip_deserializing))]
     send_audio_updated_by: Option<AgentId>,
 }
 
 impl StateConfigItem {
-    fn new(agent_id: AgentId) -> Self {
+    fn new(rtc: &Rtc) -> Self {
         Self {
-            agent_id,
+            agent_id: Some(rtc.created_by().to_owned()),
+            rtc_id: Some(rtc.id()),
             send_video: None,
             send_audio: None,
             video_remb: None,
+            quality_preset: None,
             send_audio_updated_by: None,
         }
     }
@@ -134,12 +142,7 @@ impl RequestHandler for UpdateHandler {
                 let room =
                     helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::Open, &conn)?;
 
-                if room.rtc_sharing_policy() != db::rtc::SharingPolicy::Owned {
-                    return Err(anyhow!(
-                    "Agent writer config is available only for rooms with owned RTC sharing policy"
-                ))
-                    .error(AppErrorKind::InvalidPayload)?;
-                }
+                helpers::check_rtc_configs_availability(&room)?;
 
                 helpers::check_room_presence(&room, &agent_id, &conn)?;
                 Ok::<_, AppError>(room)
@@ -148,8 +151,10 @@ impl RequestHandler for UpdateHandler {
         .await?;
         helpers::add_room_logger_tags(context, &room);
         // Authorize agent writer config updating on the tenant.
-        let is_only_owned_config =
-            payload.configs.len() == 1 && &payload.configs[0].agent_id == reqp.as_agent_id();
+        let is_only_owned_config = room.rtc_sharing_policy() == db::rtc::SharingPolicy::Owned
+            && payload.configs.len() == 1
+            && payload.configs[0].rtc_id.is_none()
+            && payload.configs[0].agent_id.as_ref() == Some(reqp.as_agent_id());
 
         let maybe_authz_time = if is_only_owned_config {
             None
@@ -165,41 +170,46 @@ impl RequestHandler for UpdateHandler {
             Some(authz_time)
         };
 
+        // Translate quality presets to the audience's writer settings.
+        let mut configs = Vec::with_capacity(payload.configs.len());
+
+        for mut state_config_item in payload.configs {
+            if let Some(preset) = state_config_item.quality_preset {
+                let preset_config = helpers::find_quality_preset(context, room.audience(), preset)?;
+
+                state_config_item.send_video =
+                    state_config_item.send_video.or(Some(preset.send_video()));
+
+                state_config_item.video_remb = state_config_item
+                    .video_remb
+                    .or(Some(preset_config.video_remb));
+            }
+
+            configs.push(state_config_item);
+        }
+
         let conn = context.get_conn().await?;
 
         let (rtc_writer_configs_with_rtcs, maybe_backend) = task::spawn_blocking({
+            let room = room.clone();
             let room_id = room.id();
             let backend_id = room.backend_id().cloned();
             let agent_id = reqp.as_agent_id().clone();
             move || {
                 conn.transaction::<_, AppError, _>(|| {
-                    // Find RTCs owned by agents.
-                    let agent_ids = payload
-                        .configs
-                        .iter()
-                        .map(|c| &c.agent_id)
-                        .collect::<Vec<_>>();
-
-                    let rtcs = db::rtc::ListQuery::new()
-                        .room_id(room_id)
-                        .created_by(agent_ids.as_slice())
-                        .execute(&conn)?;
-
-                    let agents_to_rtcs = rtcs
-                        .iter()
-                        .map(|rtc| (rtc.created_by(), rtc.id()))
-                        .collect::<HashMap<_, _>>();
+                    let rtcs = db::rtc::ListQuery::new().room_id(room_id).execute(&conn)?;
 
                     // Create or update the config.
-                    for state_config_item in payload.configs {
-                        let rtc_id = agents_to_rtcs
-                            .get(&state_config_item.agent_id)
-                            .ok_or_else(|| {
-                                anyhow!("{} has no owned RTC", state_config_item.agent_id)
-                            })
-                            .error(AppErrorKind::InvalidPayload)?;
+                    for state_config_item in configs {
+                        let rtc_id = helpers::find_config_rtc(
+                            &room,
+                            &rtcs,
+                            state_config_item.rtc_id,
+                            state_config_item.agent_id.as_ref(),
+                        )?
+                        .id();
 
-                        let mut q = db::rtc_writer_config::UpsertQuery::new(*rtc_id);
+                        let mut q = db::rtc_writer_config::UpsertQuery::new(rtc_id);
 
                         if let Some(send_video) = state_config_item.send_video {
                             q = q.send_video(send_video);
@@ -216,7 +226,7 @@ impl RequestHandler for UpdateHandler {
                         q.execute(&conn)?;
 
                         let snapshot_q = db::rtc_writer_config_snapshot::InsertQuery::new(
-                            *rtc_id,
+                            rtc_id,
                             state_config_item.send_video,
                             state_config_item.send_audio,
                         );
@@ -241,30 +251,7 @@ impl RequestHandler for UpdateHandler {
         .await?;
 
         if let Some(backend) = maybe_backend {
-            let items = rtc_writer_configs_with_rtcs
-                .iter()
-                .map(
-                    |(rtc_writer_config, rtc)| UpdateWriterConfigRequestBodyConfigItem {
-                        stream_id: rtc.id(),
-                        send_video: rtc_writer_config.send_video(),
-                        send_audio: rtc_writer_config.send_audio(),
-                        video_remb: rtc_writer_config.video_remb().map(|x| x as u32),
-                    },
-                )
-                .collect::<Vec<UpdateWriterConfigRequestBodyConfigItem>>();
-
-            let request = UpdateWriterConfigRequest {
-                session_id: backend.session_id(),
-                handle_id: backend.handle_id(),
-                body: UpdateWriterConfigRequestBody::new(items),
-            };
-            context
-                .janus_clients()
-                .get_or_insert(&backend)
-                .error(AppErrorKind::BackendClientCreationFailed)?
-                .writer_update(request)
-                .await
-                .error(AppErrorKind::BackendRequestFailed)?;
+            update_backend(context, &backend, &rtc_writer_configs_with_rtcs).await?;
         }
 
         // Respond to the agent and broadcast notification.
@@ -299,6 +286,41 @@ impl RequestHandler for UpdateHandler {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Sends the whole writer config of the room to the backend in a single request.
+pub(crate) async fn update_backend<C: Context>(
+    context: &mut C,
+    backend: &db::janus_backend::Object,
+    rtc_writer_configs_with_rtcs: &[(RtcWriterConfig, Rtc)],
+) -> StdResult<(), AppError> {
+    let items = rtc_writer_configs_with_rtcs
+        .iter()
+        .map(
+            |(rtc_writer_config, rtc)| UpdateWriterConfigRequestBodyConfigItem {
+                stream_id: rtc.id(),
+                send_video: rtc_writer_config.send_video(),
+                send_audio: rtc_writer_config.send_audio(),
+                video_remb: rtc_writer_config.video_remb().map(|x| x as u32),
+            },
+        )
+        .collect::<Vec<UpdateWriterConfigRequestBodyConfigItem>>();
+
+    let request = UpdateWriterConfigRequest {
+        session_id: backend.session_id(),
+        handle_id: backend.handle_id(),
+        body: UpdateWriterConfigRequestBody::new(items),
+    };
+
+    context
+        .janus_clients()
+        .get_or_insert(backend)
+        .error(AppErrorKind::BackendClientCreationFailed)?
+        .writer_update(request)
+        .await
+        .error(AppErrorKind::BackendRequestFailed)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 #[derive(Debug, Deserialize)]
 pub struct ReadRequest {
     room_id: db::room::Id,
@@ -326,12 +348,7 @@ impl RequestHandler for ReadHandler {
                     &conn,
                 )?;
 
-                if room.rtc_sharing_policy() != db::rtc::SharingPolicy::Owned {
-                    return Err(anyhow!(
-                    "Agent writer config is available only for rooms with owned RTC sharing policy"
-                ))
-                    .error(AppErrorKind::InvalidPayload)?;
-                }
+                helpers::check_rtc_configs_availability(&room)?;
 
                 helpers::check_room_presence(&room, &agent_id, &conn)?;
 
@@ -432,17 +449,21 @@ mod tests {
                 room_id: room.id(),
                 configs: vec![
                     StateConfigItem {
-                        agent_id: agent2.agent_id().to_owned(),
+                        agent_id: Some(agent2.agent_id().to_owned()),
+                        rtc_id: None,
                         send_video: Some(true),
                         send_audio: Some(false),
                         video_remb: Some(300_000),
+                        quality_preset: None,
                         send_audio_updated_by: None,
                     },
                     StateConfigItem {
-                        agent_id: agent3.agent_id().to_owned(),
+                        agent_id: Some(agent3.agent_id().to_owned()),
+                        rtc_id: None,
                         send_video: Some(false),
                         send_audio: Some(false),
                         video_remb: None,
+                        quality_preset: None,
                         send_audio_updated_by: None,
                     },
                 ],
@@ -460,7 +481,7 @@ mod tests {
             let agent2_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent2.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent2.agent_id()))
                 .expect("Config for agent2 not found");
 
             assert_eq!(agent2_config.send_video, Some(true));
@@ -475,7 +496,7 @@ mod tests {
             let agent3_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent3.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent3.agent_id()))
                 .expect("Config for agent3 not found");
 
             assert_eq!(agent3_config.send_video, Some(false));
@@ -496,7 +517,7 @@ mod tests {
             let agent2_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent2.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent2.agent_id()))
                 .expect("Config for agent2 not found");
 
             assert_eq!(agent2_config.send_video, Some(true));
@@ -511,7 +532,7 @@ mod tests {
             let agent3_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent3.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent3.agent_id()))
                 .expect("Config for agent3 not found");
 
             assert_eq!(agent3_config.send_video, Some(false));
@@ -523,17 +544,21 @@ mod tests {
                 room_id: room.id(),
                 configs: vec![
                     StateConfigItem {
-                        agent_id: agent4.agent_id().to_owned(),
+                        agent_id: Some(agent4.agent_id().to_owned()),
+                        rtc_id: None,
                         send_video: Some(true),
                         send_audio: Some(true),
                         video_remb: Some(1_000_000),
+                        quality_preset: None,
                         send_audio_updated_by: None,
                     },
                     StateConfigItem {
-                        agent_id: agent3.agent_id().to_owned(),
+                        agent_id: Some(agent3.agent_id().to_owned()),
+                        rtc_id: None,
                         send_video: None,
                         send_audio: Some(true),
                         video_remb: None,
+                        quality_preset: None,
                         send_audio_updated_by: None,
                     },
                 ],
@@ -552,7 +577,7 @@ mod tests {
             let agent2_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent2.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent2.agent_id()))
                 .expect("Config for agent2 not found");
 
             assert_eq!(agent2_config.send_video, Some(true));
@@ -562,7 +587,7 @@ mod tests {
             let agent3_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent3.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent3.agent_id()))
                 .expect("Config for agent3 not found");
 
             assert_eq!(agent3_config.send_video, Some(false));
@@ -572,7 +597,7 @@ mod tests {
             let agent4_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent4.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent4.agent_id()))
                 .expect("Config for agent4 not found");
 
             assert_eq!(agent4_config.send_video, Some(true));
@@ -588,7 +613,7 @@ mod tests {
             let agent2_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent2.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent2.agent_id()))
                 .expect("Config for agent2 not found");
 
             assert_eq!(agent2_config.send_video, Some(true));
@@ -598,7 +623,7 @@ mod tests {
             let agent3_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent3.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent3.agent_id()))
                 .expect("Config for agent3 not found");
 
             assert_eq!(agent3_config.send_video, Some(false));
@@ -608,7 +633,7 @@ mod tests {
             let agent4_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent4.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent4.agent_id()))
                 .expect("Config for agent4 not found");
 
             assert_eq!(agent4_config.send_video, Some(true));
@@ -658,6 +683,234 @@ mod tests {
             Ok(())
         }
 
+        #[async_std::test]
+        async fn update_agent_writer_config_in_shared_room() -> std::io::Result<()> {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let presenter = TestAgent::new("web", "presenter", USR_AUDIENCE);
+
+            // Insert a shared RTC room with a single RTC created by the presenter.
+            let (room, rtc) = db
+                .connection_pool()
+                .get()
+                .map(|conn| {
+                    let room = factory::Room::new()
+                        .audience(USR_AUDIENCE)
+                        .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                        .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                        .insert(&conn);
+
+                    shared_helpers::insert_agent(&conn, host.agent_id(), room.id());
+
+                    let rtc = factory::Rtc::new(room.id())
+                        .created_by(presenter.agent_id().to_owned())
+                        .insert(&conn);
+
+                    (room, rtc)
+                })
+                .unwrap();
+
+            // Allow the host to update agent_writer_config.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(host.account_id(), vec!["rooms", &room_id], "update");
+
+            // Make agent_writer_config.update request referring to the RTC by its id.
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = State {
+                room_id: room.id(),
+                configs: vec![StateConfigItem {
+                    agent_id: None,
+                    rtc_id: Some(rtc.id()),
+                    send_video: None,
+                    send_audio: Some(false),
+                    video_remb: Some(300_000),
+                    quality_preset: None,
+                    send_audio_updated_by: None,
+                }],
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &host, payload)
+                .await
+                .expect("Agent writer config update failed");
+
+            // Assert response.
+            let (state, respp, _) = find_response::<State>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(state.configs.len(), 1);
+            assert_eq!(state.configs[0].rtc_id, Some(rtc.id()));
+            assert_eq!(state.configs[0].send_video, Some(true));
+            assert_eq!(state.configs[0].send_audio, Some(false));
+            assert_eq!(state.configs[0].video_remb, Some(300_000));
+
+            // Assert snapshot history.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let snapshots = db::rtc_writer_config_snapshot::ListWithRtcQuery::new(room.id())
+                .execute(&conn)
+                .expect("Failed to list writer config snapshots");
+
+            assert_eq!(snapshots.len(), 1);
+            Ok(())
+        }
+
+        #[async_std::test]
+        async fn update_agent_writer_config_in_shared_room_by_agent_id() -> std::io::Result<()> {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let presenter = TestAgent::new("web", "presenter", USR_AUDIENCE);
+
+            let room = db
+                .connection_pool()
+                .get()
+                .map(|conn| {
+                    let room = factory::Room::new()
+                        .audience(USR_AUDIENCE)
+                        .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                        .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                        .insert(&conn);
+
+                    shared_helpers::insert_agent(&conn, host.agent_id(), room.id());
+
+                    factory::Rtc::new(room.id())
+                        .created_by(presenter.agent_id().to_owned())
+                        .insert(&conn);
+
+                    room
+                })
+                .unwrap();
+
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(host.account_id(), vec!["rooms", &room_id], "update");
+
+            // RTCs are not owned in shared rooms so referring by the agent is ambiguous.
+            let mut context = TestContext::new(db, authz);
+
+            let payload = State {
+                room_id: room.id(),
+                configs: vec![StateConfigItem {
+                    agent_id: Some(presenter.agent_id().to_owned()),
+                    rtc_id: None,
+                    send_video: None,
+                    send_audio: Some(false),
+                    video_remb: None,
+                    quality_preset: None,
+                    send_audio_updated_by: None,
+                }],
+            };
+
+            let err = handle_request::<UpdateHandler>(&mut context, &host, payload)
+                .await
+                .expect_err("Unexpected agent writer config update success");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+            Ok(())
+        }
+
+        #[async_std::test]
+        async fn update_agent_writer_config_with_quality_preset() -> std::io::Result<()> {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let presenter = TestAgent::new("web", "presenter", USR_AUDIENCE);
+            let listener = TestAgent::new("web", "listener", USR_AUDIENCE);
+
+            let (room, presenter_rtc, listener_rtc) = db
+                .connection_pool()
+                .get()
+                .map(|conn| {
+                    let room = factory::Room::new()
+                        .audience(USR_AUDIENCE)
+                        .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                        .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                        .insert(&conn);
+
+                    shared_helpers::insert_agent(&conn, host.agent_id(), room.id());
+
+                    let rtcs = vec![&presenter, &listener]
+                        .into_iter()
+                        .map(|agent| {
+                            factory::Rtc::new(room.id())
+                                .created_by(agent.agent_id().to_owned())
+                                .insert(&conn)
+                        })
+                        .collect::<Vec<_>>();
+
+                    (room, rtcs[0].clone(), rtcs[1].clone())
+                })
+                .unwrap();
+
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(host.account_id(), vec!["rooms", &room_id], "update");
+
+            // Make agent_writer_config.update request with presets instead of raw values.
+            let mut context = TestContext::new(db, authz);
+
+            let payload = State {
+                room_id: room.id(),
+                configs: vec![
+                    StateConfigItem {
+                        agent_id: None,
+                        rtc_id: Some(presenter_rtc.id()),
+                        send_video: None,
+                        send_audio: None,
+                        video_remb: None,
+                        quality_preset: Some(QualityPreset::Low),
+                        send_audio_updated_by: None,
+                    },
+                    StateConfigItem {
+                        agent_id: None,
+                        rtc_id: Some(listener_rtc.id()),
+                        send_video: None,
+                        send_audio: None,
+                        video_remb: None,
+                        quality_preset: Some(QualityPreset::AudioOnly),
+                        send_audio_updated_by: None,
+                    },
+                ],
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &host, payload)
+                .await
+                .expect("Agent writer config update failed");
+
+            // Assert presets are translated to the default bitrates.
+            let (state, respp, _) = find_response::<State>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(state.configs.len(), 2);
+
+            let presenter_config = state
+                .configs
+                .iter()
+                .find(|c| c.rtc_id == Some(presenter_rtc.id()))
+                .expect("Presenter config not found");
+
+            assert_eq!(presenter_config.send_video, Some(true));
+            assert_eq!(presenter_config.video_remb, Some(300_000));
+
+            let listener_config = state
+                .configs
+                .iter()
+                .find(|c| c.rtc_id == Some(listener_rtc.id()))
+                .expect("Listener config not found");
+
+            assert_eq!(listener_config.send_video, Some(false));
+            assert_eq!(listener_config.video_remb, Some(64_000));
+            Ok(())
+        }
+
         #[async_std::test]
         async fn too_many_config_items() -> std::io::Result<()> {
             // Make agent_writer_config.update request.
@@ -672,10 +925,12 @@ mod tests {
                     let agent = TestAgent::new("web", &format!("user{}", i), USR_AUDIENCE);
 
                     StateConfigItem {
-                        agent_id: agent.agent_id().to_owned(),
+                        agent_id: Some(agent.agent_id().to_owned()),
+                        rtc_id: None,
                         send_video: Some(false),
                         send_audio: Some(true),
                         video_remb: Some(300_000),
+                        quality_preset: None,
                         send_audio_updated_by: None,
                     }
                 })
@@ -787,7 +1042,7 @@ mod tests {
                     let room = factory::Room::new()
                         .audience(USR_AUDIENCE)
                         .time((Bound::Included(Utc::now()), Bound::Unbounded))
-                        .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                        .rtc_sharing_policy(RtcSharingPolicy::None)
                         .insert(&conn);
 
                     shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
@@ -916,7 +1171,7 @@ mod tests {
             let agent2_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent2.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent2.agent_id()))
                 .expect("Config for agent2 not found");
 
             assert_eq!(agent2_config.send_video, Some(true));
@@ -927,7 +1182,7 @@ mod tests {
             let agent3_config = state
                 .configs
                 .iter()
-                .find(|c| &c.agent_id == agent3.agent_id())
+                .find(|c| c.agent_id.as_ref() == Some(agent3.agent_id()))
                 .expect("Config for agent3 not found");
 
             assert_eq!(agent3_config.send_video, Some(false));
@@ -1028,7 +1283,7 @@ mod tests {
                     let room = factory::Room::new()
                         .audience(USR_AUDIENCE)
                         .time((Bound::Included(Utc::now()), Bound::Unbounded))
-                        .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                        .rtc_sharing_policy(RtcSharingPolicy::None)
                         .insert(&conn);
 
                     shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
@@ -0,0 +1,269 @@
+use async_std::{stream, task};
+use async_trait::async_trait;
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use svc_agent::{
+    mqtt::{IncomingRequestProperties, ResponseStatus},
+    AgentId,
+};
+
+use crate::{
+    app::{context::Context, endpoint::prelude::*, metrics::HistogramExt},
+    db::{self, agent_session::LeaveReason},
+};
+
+const MAX_LIMIT: usize = 25;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AgentAttendance {
+    agent_id: AgentId,
+    /// Total presence in seconds.
+    total: i64,
+    intervals: Vec<AttendanceInterval>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct AttendanceInterval {
+    #[serde(with = "ts_seconds")]
+    started_at: DateTime<Utc>,
+    #[serde(
+        with = "crate::serde::ts_seconds_option",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    leave_reason: Option<LeaveReason>,
+}
+
+/// Groups sessions by agent keeping the order of their first appearance.
+///
+/// Sessions that are still open are counted in `total` up to `until`.
+pub(crate) fn build_attendance(
+    sessions: &[db::agent_session::Object],
+    until: DateTime<Utc>,
+) -> Vec<AgentAttendance> {
+    let mut attendance: Vec<AgentAttendance> = Vec::new();
+
+    for session in sessions {
+        let finished_at = session.finished_at().unwrap_or(until);
+        let duration = (finished_at - session.started_at()).num_seconds().max(0);
+
+        let interval = AttendanceInterval {
+            started_at: session.started_at(),
+            finished_at: session.finished_at(),
+            leave_reason: session.leave_reason(),
+        };
+
+        match attendance
+            .iter_mut()
+            .find(|entry| &entry.agent_id == session.agent_id())
+        {
+            Some(entry) => {
+                entry.total += duration;
+                entry.intervals.push(interval);
+            }
+            None => attendance.push(AgentAttendance {
+                agent_id: session.agent_id().to_owned(),
+                total: duration,
+                intervals: vec![interval],
+            }),
+        }
+    }
+
+    attendance
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    room_id: db::room::Id,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+    const ERROR_TITLE: &'static str = "Failed to list attendance";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let conn = context.get_conn().await?;
+        let room = task::spawn_blocking({
+            let room_id = payload.room_id;
+            move || helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::Any, &conn)
+        })
+        .await?;
+        helpers::add_room_logger_tags(context, &room);
+
+        // Authorize attendance reading in the room.
+        let room_id = room.id().to_string();
+        let object = vec!["rooms", &room_id, "attendance"];
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience(), reqp, object, "read")
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Get the room's sessions and group them by agent.
+        let conn = context.get_conn().await?;
+        let sessions = task::spawn_blocking(move || {
+            db::agent_session::ListQuery::new(payload.room_id).execute(&conn)
+        })
+        .await?;
+
+        let attendance = build_attendance(&sessions, Utc::now())
+            .into_iter()
+            .skip(payload.offset.unwrap_or(0))
+            .take(std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT))
+            .collect::<Vec<_>>();
+
+        context
+            .metrics()
+            .request_duration
+            .attendance_list
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::once(helpers::build_response(
+            ResponseStatus::OK,
+            attendance,
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    mod list {
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+        use super::super::*;
+
+        #[async_std::test]
+        async fn list_attendance() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let first_participant = TestAgent::new("web", "user1", USR_AUDIENCE);
+            let second_participant = TestAgent::new("web", "user2", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                // The first participant has entered twice and the second one is still online.
+                for reason in &[LeaveReason::Left, LeaveReason::Kicked] {
+                    db::agent_session::StartQuery::new(first_participant.agent_id(), room.id())
+                        .execute(&conn)
+                        .expect("Failed to start session");
+
+                    db::agent_session::FinishQuery::new(room.id(), *reason)
+                        .agent_id(first_participant.agent_id())
+                        .execute(&conn)
+                        .expect("Failed to finish session");
+                }
+
+                db::agent_session::StartQuery::new(second_participant.agent_id(), room.id())
+                    .execute(&conn)
+                    .expect("Failed to start session");
+
+                // Starting an already open session is a no-op.
+                db::agent_session::StartQuery::new(second_participant.agent_id(), room.id())
+                    .execute(&conn)
+                    .expect("Failed to start session");
+
+                room
+            };
+
+            // Allow agent to read attendance in the room.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["rooms", &room_id, "attendance"],
+                "read",
+            );
+
+            // Make attendance.list request.
+            let mut context = TestContext::new(db, authz);
+
+            let payload = ListRequest {
+                room_id: room.id(),
+                offset: None,
+                limit: None,
+            };
+
+            let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Attendance listing failed");
+
+            // Assert response.
+            let (attendance, respp, _) = find_response::<Vec<AgentAttendance>>(messages.as_slice());
+
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(attendance.len(), 2);
+
+            assert_eq!(&attendance[0].agent_id, first_participant.agent_id());
+            assert_eq!(attendance[0].intervals.len(), 2);
+            assert_eq!(
+                attendance[0].intervals[1].leave_reason,
+                Some(LeaveReason::Kicked)
+            );
+
+            assert_eq!(&attendance[1].agent_id, second_participant.agent_id());
+            assert_eq!(attendance[1].intervals.len(), 1);
+            assert_eq!(attendance[1].intervals[0].finished_at, None);
+        }
+
+        #[async_std::test]
+        async fn list_attendance_not_authorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_room(&conn)
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let payload = ListRequest {
+                room_id: room.id(),
+                offset: None,
+                limit: None,
+            };
+
+            let err = handle_request::<ListHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on attendance listing");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+}
@@ -0,0 +1,1065 @@
+use std::{ops::Bound, result::Result as StdResult};
+
+use anyhow::anyhow;
+use async_std::{stream, task};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use diesel::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use slog::error;
+use svc_agent::{
+    mqtt::{IncomingRequestProperties, IntoPublishableMessage, ResponseStatus},
+    AgentId,
+};
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::{prelude::*, subscription, subscription::RoomEnterLeaveEvent},
+        metrics::HistogramExt,
+        room_closing,
+    },
+    db::{self, room::Object as Room},
+};
+
+///////////////////////////////////////////////////////////////////////////////
+
+const MAX_BREAKOUT_ROOMS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRequest {
+    id: db::room::Id,
+    count: usize,
+    tags: Option<JsonValue>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateNotification {
+    id: db::room::Id,
+    rooms: Vec<Room>,
+}
+
+pub struct CreateHandler;
+
+#[async_trait]
+impl RequestHandler for CreateHandler {
+    type Payload = CreateRequest;
+    const ERROR_TITLE: &'static str = "Failed to create breakout rooms";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        if payload.count == 0 || payload.count > MAX_BREAKOUT_ROOMS {
+            return Err(anyhow!(
+                "Breakout rooms count must be between 1 and {}",
+                MAX_BREAKOUT_ROOMS
+            ))
+            .error(AppErrorKind::InvalidPayload);
+        }
+
+        let (parent, authz_time) = find_parent_and_authorize(
+            context,
+            payload.id,
+            helpers::RoomTimeRequirement::NotClosed,
+            reqp,
+        )
+        .await?;
+
+        helpers::check_quota_for(
+            context,
+            parent.audience(),
+            helpers::Quota::OpenRooms,
+            payload.count as u32,
+        )
+        .await?;
+
+        // Create breakout rooms inheriting the parent's properties so that the same
+        // access restrictions and quality apply to the agents moved there.
+        let conn = context.get_conn().await?;
+        let rooms = task::spawn_blocking({
+            let parent = parent.clone();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    let mut rooms = Vec::with_capacity(payload.count);
+
+                    for _ in 0..payload.count {
+                        let mut q = db::room::InsertQuery::new(
+                            *parent.time(),
+                            parent.audience(),
+                            parent.rtc_sharing_policy(),
+                        )
+                        .parent_id(parent.id());
+
+                        if let Some(ref tags) = payload.tags {
+                            q = q.tags(tags);
+                        }
+
+                        if let Some(classroom_id) = parent.classroom_id() {
+                            q = q.classroom_id(classroom_id);
+                        }
+
+                        if let Some(max_agents) = parent.max_agents() {
+                            q = q.max_agents(max_agents);
+                        }
+
+                        if let (Some(preset), Some(video_remb)) =
+                            (parent.quality_preset(), parent.video_remb())
+                        {
+                            q = q.quality_preset(preset, video_remb as u32);
+                        }
+
+                        q = q
+                            .locked(parent.is_locked())
+                            .requires_admission(parent.requires_admission())
+                            .stage_mode(parent.is_stage_mode());
+
+                        rooms.push(q.execute(&conn)?);
+                    }
+
+                    Ok(rooms)
+                })
+            }
+        })
+        .await?;
+
+        let mut messages = Vec::with_capacity(rooms.len() + 2);
+
+        messages.push(helpers::build_response(
+            ResponseStatus::OK,
+            rooms.clone(),
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        ));
+
+        for room in rooms.iter() {
+            messages.push(helpers::build_notification(
+                "room.create",
+                &format!("audiences/{}/events", room.audience()),
+                room.clone(),
+                reqp,
+                context.start_timestamp(),
+            ));
+        }
+
+        messages.push(helpers::build_notification(
+            "room.breakout.create",
+            &format!("rooms/{}/events", parent.id()),
+            CreateNotification {
+                id: parent.id(),
+                rooms,
+            },
+            reqp,
+            context.start_timestamp(),
+        ));
+
+        context
+            .metrics()
+            .request_duration
+            .room_breakout_create
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::from_iter(messages)))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AssignRequest {
+    id: db::room::Id,
+    agent_id: AgentId,
+    room_id: db::room::Id,
+}
+
+pub struct AssignHandler;
+
+#[async_trait]
+impl RequestHandler for AssignHandler {
+    type Payload = AssignRequest;
+    const ERROR_TITLE: &'static str = "Failed to assign agent to breakout room";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let (parent, authz_time) = find_parent_and_authorize(
+            context,
+            payload.id,
+            helpers::RoomTimeRequirement::Open,
+            reqp,
+        )
+        .await?;
+
+        // Find the room the agent is currently in among the parent and its breakout rooms.
+        let conn = context.get_conn().await?;
+        let current_room_id = task::spawn_blocking({
+            let parent_id = parent.id();
+            let agent_id = payload.agent_id.clone();
+            let target_room_id = payload.room_id;
+
+            move || {
+                let children = db::room::ListQuery::new()
+                    .parent_id(parent_id)
+                    .closed(false)
+                    .execute(&conn)?;
+
+                let room_ids = std::iter::once(parent_id)
+                    .chain(children.iter().map(|room| room.id()))
+                    .collect::<Vec<_>>();
+
+                if !room_ids.contains(&target_room_id) {
+                    return Err(anyhow!("Breakout room not found"))
+                        .error(AppErrorKind::RoomNotFound);
+                }
+
+                db::agent::ListQuery::new()
+                    .agent_id(&agent_id)
+                    .execute(&conn)?
+                    .iter()
+                    .map(|agent| agent.room_id())
+                    .find(|room_id| room_ids.contains(room_id))
+                    .ok_or_else(|| anyhow!("Agent is not online in the room"))
+                    .error(AppErrorKind::AgentNotEnteredTheRoom)
+            }
+        })
+        .await?;
+
+        let mut messages = vec![helpers::build_response(
+            ResponseStatus::OK,
+            json!({}),
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        )];
+
+        if current_room_id != payload.room_id {
+            let move_messages = move_agent(
+                context,
+                reqp,
+                &payload.agent_id,
+                current_room_id,
+                payload.room_id,
+            )
+            .await?;
+
+            messages.extend(move_messages);
+
+            messages.push(helpers::build_notification(
+                "room.breakout.assign",
+                &format!("rooms/{}/events", parent.id()),
+                payload,
+                reqp,
+                context.start_timestamp(),
+            ));
+        }
+
+        context
+            .metrics()
+            .request_duration
+            .room_breakout_assign
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::from_iter(messages)))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CloseRequest {
+    id: db::room::Id,
+}
+
+#[derive(Debug, Serialize)]
+struct CloseNotification {
+    id: db::room::Id,
+    rooms: Vec<db::room::Id>,
+}
+
+pub struct CloseHandler;
+
+#[async_trait]
+impl RequestHandler for CloseHandler {
+    type Payload = CloseRequest;
+    const ERROR_TITLE: &'static str = "Failed to close breakout rooms";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let (parent, authz_time) = find_parent_and_authorize(
+            context,
+            payload.id,
+            helpers::RoomTimeRequirement::Open,
+            reqp,
+        )
+        .await?;
+
+        // End breakout rooms and collect the agents to return to the parent.
+        // The rooms get ended before moving the agents so nobody can enter them meanwhile.
+        // Moving failures are reported but don't stop returning the rest of the agents.
+        let conn = context.get_conn().await?;
+        let rooms_with_agents = task::spawn_blocking({
+            let parent_id = parent.id();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    let children = db::room::ListQuery::new()
+                        .parent_id(parent_id)
+                        .closed(false)
+                        .execute(&conn)?;
+
+                    let mut rooms_with_agents = Vec::with_capacity(children.len());
+
+                    for room in children {
+                        let time = (room.time().0, Bound::Excluded(Utc::now()));
+
+                        let room = db::room::UpdateQuery::new(room.id())
+                            .time(Some(time))
+                            .execute(&conn)?;
+
+                        let agents = db::agent::ListQuery::new()
+                            .room_id(room.id())
+                            .execute(&conn)?;
+
+                        rooms_with_agents.push((room, agents));
+                    }
+
+                    Ok(rooms_with_agents)
+                })
+            }
+        })
+        .await?;
+
+        let mut messages = vec![helpers::build_response(
+            ResponseStatus::OK,
+            json!({}),
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        )];
+
+        let mut room_ids = Vec::with_capacity(rooms_with_agents.len());
+
+        for (room, agents) in rooms_with_agents {
+            for agent in agents {
+                match move_agent(context, reqp, agent.agent_id(), room.id(), parent.id()).await {
+                    Ok(move_messages) => messages.extend(move_messages),
+                    Err(err) => {
+                        error!(
+                            context.logger(),
+                            "Failed to move agent {} back to the parent room: {:?}",
+                            agent.agent_id(),
+                            err
+                        );
+
+                        err.notify_sentry(context.logger());
+                    }
+                }
+            }
+
+            // The room has been ended anyway so closing failure doesn't fail the request.
+            if let Err(err) = room_closing::close_room(context, room.clone()).await {
+                error!(context.logger(), "Failed to close room: {:?}", err);
+                err.notify_sentry(context.logger());
+            }
+
+            messages.push(helpers::build_notification(
+                "room.close",
+                &format!("rooms/{}/events", room.id()),
+                room.clone(),
+                reqp,
+                context.start_timestamp(),
+            ));
+
+            messages.push(helpers::build_notification(
+                "room.close",
+                &format!("audiences/{}/events", room.audience()),
+                room.clone(),
+                reqp,
+                context.start_timestamp(),
+            ));
+
+            room_ids.push(room.id());
+        }
+
+        messages.push(helpers::build_notification(
+            "room.breakout.close",
+            &format!("rooms/{}/events", parent.id()),
+            CloseNotification {
+                id: parent.id(),
+                rooms: room_ids,
+            },
+            reqp,
+            context.start_timestamp(),
+        ));
+
+        context
+            .metrics()
+            .request_duration
+            .room_breakout_close
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::from_iter(messages)))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// Finds the parent room and authorizes managing its breakout rooms.
+async fn find_parent_and_authorize<C: Context>(
+    context: &mut C,
+    id: db::room::Id,
+    time_requirement: helpers::RoomTimeRequirement,
+    reqp: &IncomingRequestProperties,
+) -> StdResult<(Room, Duration), AppError> {
+    let conn = context.get_conn().await?;
+    let room =
+        task::spawn_blocking(move || helpers::find_room_by_id(id, time_requirement, &conn)).await?;
+    helpers::add_room_logger_tags(context, &room);
+
+    if room.parent_id().is_some() {
+        return Err(anyhow!("Breakout rooms can't be nested")).error(AppErrorKind::InvalidPayload);
+    }
+
+    let room_id = room.id().to_string();
+    let object = vec!["rooms", &room_id];
+
+    let authz_time = context
+        .authz()
+        .authorize(room.audience(), reqp, object, "update")
+        .await?;
+    context.metrics().observe_auth(authz_time);
+
+    Ok((room, authz_time))
+}
+
+// Moves the agent from one room to another on its behalf: removes it from the former one
+// with `room.leave` notification and subscribes to the latter one.
+// `room.enter` notification is being sent on the broker's response.
+async fn move_agent<C: Context>(
+    context: &mut C,
+    reqp: &IncomingRequestProperties,
+    agent_id: &AgentId,
+    from_room_id: db::room::Id,
+    to_room_id: db::room::Id,
+) -> StdResult<Vec<Box<dyn IntoPublishableMessage + Send>>, AppError> {
+    let mut messages = Vec::with_capacity(3);
+
+    if subscription::leave_room(
+        context,
+        agent_id,
+        from_room_id,
+        db::agent_session::LeaveReason::Moved,
+    )
+    .await?
+    {
+        messages.push(helpers::build_notification(
+            "room.leave",
+            &format!("rooms/{}/events", from_room_id),
+            RoomEnterLeaveEvent::new(from_room_id, agent_id.to_owned()),
+            reqp,
+            context.start_timestamp(),
+        ));
+
+        messages.push(helpers::build_subscription_request(
+            context,
+            reqp,
+            "subscription.delete",
+            agent_id.to_owned(),
+            from_room_id,
+            CorrelationData::BreakoutLeave,
+            None,
+        )?);
+    }
+
+    // Register agent in `in_progress` state until the broker subscribes it.
+    let conn = context.get_conn().await?;
+    task::spawn_blocking({
+        let agent_id = agent_id.to_owned();
+        move || db::agent::InsertQuery::new(&agent_id, to_room_id).execute(&conn)
+    })
+    .await?;
+
+    messages.push(helpers::build_subscription_request(
+        context,
+        reqp,
+        "subscription.create",
+        agent_id.to_owned(),
+        to_room_id,
+        CorrelationData::BreakoutEnter,
+        None,
+    )?);
+
+    Ok(messages)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use diesel::pg::PgConnection;
+    use serde::Deserialize;
+
+    use crate::test_helpers::{
+        find_event_by_predicate,
+        outgoing_envelope::{OutgoingEnvelope, OutgoingEnvelopeProperties},
+        prelude::*,
+        test_deps::LocalDeps,
+    };
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct DynSubRequest {
+        subject: AgentId,
+        object: Vec<String>,
+    }
+
+    fn insert_breakout_room(conn: &PgConnection, parent: &Room) -> Room {
+        db::room::InsertQuery::new(
+            *parent.time(),
+            parent.audience(),
+            parent.rtc_sharing_policy(),
+        )
+        .parent_id(parent.id())
+        .execute(conn)
+        .expect("Failed to insert breakout room")
+    }
+
+    // Dynamic subscription requests as (method, subject, object) tuples.
+    fn subscription_requests(messages: &[OutgoingEnvelope]) -> Vec<(String, AgentId, Vec<String>)> {
+        messages
+            .iter()
+            .filter_map(|message| match message.properties() {
+                OutgoingEnvelopeProperties::Request(reqp) => {
+                    let payload = message.payload::<DynSubRequest>();
+                    Some((reqp.method().to_owned(), payload.subject, payload.object))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    mod create {
+        use super::*;
+
+        #[async_std::test]
+        async fn create_breakout_rooms() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let parent = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_room(&conn)
+            };
+
+            // Allow agent to update the parent room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let parent_id = parent.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &parent_id], "update");
+
+            // Make room.breakout.create request.
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = CreateRequest {
+                id: parent.id(),
+                count: 3,
+                tags: None,
+            };
+
+            let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Breakout rooms creation failed");
+
+            // Assert response.
+            let (rooms, respp, _) = find_response::<Vec<Room>>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(rooms.len(), 3);
+
+            for room in rooms.iter() {
+                assert_eq!(room.parent_id(), Some(parent.id()));
+                assert_eq!(room.audience(), parent.audience());
+                assert_eq!(room.time(), parent.time());
+                assert_eq!(room.rtc_sharing_policy(), parent.rtc_sharing_policy());
+            }
+
+            // Assert notification to the parent room.
+            let (event, _, _) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                    evp.label() == "room.breakout.create" && topic.contains("rooms")
+                })
+                .expect("Failed to find room.breakout.create event");
+
+            assert_eq!(event["id"], parent_id);
+            assert_eq!(event["rooms"].as_array().map(|rooms| rooms.len()), Some(3));
+
+            // Assert breakout rooms in the DB.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let db_rooms = db::room::ListQuery::new()
+                .parent_id(parent.id())
+                .execute(&conn)
+                .expect("Failed to list rooms");
+
+            assert_eq!(db_rooms.len(), 3);
+        }
+
+        #[async_std::test]
+        async fn create_breakout_rooms_inheriting_parent_settings() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let parent = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                db::room::InsertQuery::new(*room.time(), room.audience(), room.rtc_sharing_policy())
+                    .locked(true)
+                    .requires_admission(true)
+                    .max_agents(10)
+                    .stage_mode(true)
+                    .quality_preset(db::room::QualityPreset::Low, 500_000)
+                    .execute(&conn)
+                    .expect("Failed to insert parent room")
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let parent_id = parent.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &parent_id], "update");
+
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = CreateRequest {
+                id: parent.id(),
+                count: 2,
+                tags: None,
+            };
+
+            let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Breakout rooms creation failed");
+
+            let (rooms, respp, _) = find_response::<Vec<Room>>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(rooms.len(), 2);
+
+            for room in rooms.iter() {
+                assert!(room.is_locked());
+                assert!(room.requires_admission());
+                assert_eq!(room.max_agents(), Some(10));
+                assert!(room.is_stage_mode());
+                assert_eq!(room.quality_preset(), Some(db::room::QualityPreset::Low));
+            }
+
+            // The bitrate isn't exposed in the API so assert it in the DB.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let db_rooms = db::room::ListQuery::new()
+                .parent_id(parent.id())
+                .execute(&conn)
+                .expect("Failed to list rooms");
+
+            for room in db_rooms {
+                assert_eq!(room.video_remb(), Some(500_000));
+            }
+        }
+
+        #[async_std::test]
+        async fn create_nested_breakout_rooms() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let breakout_room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let parent = shared_helpers::insert_room(&conn);
+                insert_breakout_room(&conn, &parent)
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let room_id = breakout_room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "update");
+
+            let mut context = TestContext::new(db, authz);
+
+            let payload = CreateRequest {
+                id: breakout_room.id(),
+                count: 1,
+                tags: None,
+            };
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on breakout rooms creation");
+
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+
+        #[async_std::test]
+        async fn create_breakout_rooms_quota_exceeded() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let parent = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_room(&conn)
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let parent_id = parent.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &parent_id], "update");
+
+            let mut context = TestContext::new(db.clone(), authz);
+
+            // The parent room takes one of the three allowed open rooms.
+            context.config_mut().quotas.insert(
+                USR_AUDIENCE.to_owned(),
+                crate::config::QuotaConfig {
+                    max_open_rooms: Some(3),
+                    ..Default::default()
+                },
+            );
+
+            let payload = CreateRequest {
+                id: parent.id(),
+                count: 3,
+                tags: None,
+            };
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on breakout rooms creation");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "quota_exceeded");
+
+            // Assert no breakout rooms have been created.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let db_rooms = db::room::ListQuery::new()
+                .parent_id(parent.id())
+                .execute(&conn)
+                .expect("Failed to list rooms");
+
+            assert!(db_rooms.is_empty());
+        }
+
+        #[async_std::test]
+        async fn create_breakout_rooms_not_authorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let parent = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_room(&conn)
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let payload = CreateRequest {
+                id: parent.id(),
+                count: 1,
+                tags: None,
+            };
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on breakout rooms creation");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+
+    mod assign {
+        use super::*;
+
+        #[async_std::test]
+        async fn assign_agent() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let (parent, breakout_room) = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let parent = shared_helpers::insert_room(&conn);
+                let breakout_room = insert_breakout_room(&conn, &parent);
+                shared_helpers::insert_agent(&conn, student.agent_id(), parent.id());
+                (parent, breakout_room)
+            };
+
+            // Allow the moderator to update the parent room.
+            let mut authz = TestAuthz::new();
+            let parent_id = parent.id().to_string();
+            let breakout_room_id = breakout_room.id().to_string();
+            authz.allow(moderator.account_id(), vec!["rooms", &parent_id], "update");
+
+            // Make room.breakout.assign request.
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = AssignRequest {
+                id: parent.id(),
+                agent_id: student.agent_id().to_owned(),
+                room_id: breakout_room.id(),
+            };
+
+            let messages = handle_request::<AssignHandler>(&mut context, &moderator, payload)
+                .await
+                .expect("Breakout room assignment failed");
+
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            // Assert the student is moved from the parent room to the breakout one.
+            let (event, _, _) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                    evp.label() == "room.leave"
+                        && topic.ends_with(&format!("/rooms/{}/events", parent_id))
+                })
+                .expect("Failed to find room.leave event");
+
+            assert_eq!(event["agent_id"], student.agent_id().to_string());
+
+            assert_eq!(
+                subscription_requests(messages.as_slice()),
+                vec![
+                    (
+                        String::from("subscription.delete"),
+                        student.agent_id().to_owned(),
+                        vec![
+                            String::from("rooms"),
+                            parent_id.clone(),
+                            String::from("events")
+                        ],
+                    ),
+                    (
+                        String::from("subscription.create"),
+                        student.agent_id().to_owned(),
+                        vec![
+                            String::from("rooms"),
+                            breakout_room_id.clone(),
+                            String::from("events")
+                        ],
+                    ),
+                ]
+            );
+
+            let (event, _, _) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, _| {
+                    evp.label() == "room.breakout.assign"
+                })
+                .expect("Failed to find room.breakout.assign event");
+
+            assert_eq!(event["room_id"], breakout_room_id);
+
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let maybe_agent = db::agent::FindQuery::new(student.agent_id(), parent.id())
+                .execute(&conn)
+                .expect("Failed to find agent");
+
+            assert!(maybe_agent.is_none());
+
+            let agent = db::agent::FindQuery::new(student.agent_id(), breakout_room.id())
+                .execute(&conn)
+                .expect("Failed to find agent")
+                .expect("Agent not found");
+
+            assert_eq!(agent.status(), db::agent::Status::InProgress);
+        }
+
+        #[async_std::test]
+        async fn assign_agent_to_foreign_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let (parent, other_room) = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let parent = shared_helpers::insert_room(&conn);
+                let other_room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), parent.id());
+                (parent, other_room)
+            };
+
+            let mut authz = TestAuthz::new();
+            let parent_id = parent.id().to_string();
+            authz.allow(moderator.account_id(), vec!["rooms", &parent_id], "update");
+
+            let mut context = TestContext::new(db, authz);
+
+            let payload = AssignRequest {
+                id: parent.id(),
+                agent_id: student.agent_id().to_owned(),
+                room_id: other_room.id(),
+            };
+
+            let err = handle_request::<AssignHandler>(&mut context, &moderator, payload)
+                .await
+                .expect_err("Unexpected success on breakout room assignment");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "room_not_found");
+        }
+    }
+
+    mod close {
+        use crate::db::room::FindQueryable;
+
+        use super::*;
+
+        #[async_std::test]
+        async fn close_breakout_rooms() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let (parent, breakout_room) = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let parent = shared_helpers::insert_room(&conn);
+                let breakout_room = insert_breakout_room(&conn, &parent);
+                shared_helpers::insert_agent(&conn, student.agent_id(), breakout_room.id());
+                (parent, breakout_room)
+            };
+
+            // Allow the moderator to update the parent room.
+            let mut authz = TestAuthz::new();
+            let parent_id = parent.id().to_string();
+            let breakout_room_id = breakout_room.id().to_string();
+            authz.allow(moderator.account_id(), vec!["rooms", &parent_id], "update");
+
+            // Make room.breakout.close request.
+            let mut context = TestContext::new(db.clone(), authz);
+            let payload = CloseRequest { id: parent.id() };
+
+            let messages = handle_request::<CloseHandler>(&mut context, &moderator, payload)
+                .await
+                .expect("Breakout rooms closing failed");
+
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            // Assert the student is returned to the parent room.
+            assert_eq!(
+                subscription_requests(messages.as_slice()),
+                vec![
+                    (
+                        String::from("subscription.delete"),
+                        student.agent_id().to_owned(),
+                        vec![
+                            String::from("rooms"),
+                            breakout_room_id.clone(),
+                            String::from("events")
+                        ],
+                    ),
+                    (
+                        String::from("subscription.create"),
+                        student.agent_id().to_owned(),
+                        vec![
+                            String::from("rooms"),
+                            parent_id.clone(),
+                            String::from("events")
+                        ],
+                    ),
+                ]
+            );
+
+            // Assert notifications.
+            find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                evp.label() == "room.close"
+                    && topic.ends_with(&format!("/rooms/{}/events", breakout_room_id))
+            })
+            .expect("Failed to find room.close event");
+
+            let (event, _, _) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                    evp.label() == "room.breakout.close"
+                        && topic.ends_with(&format!("/rooms/{}/events", parent_id))
+                })
+                .expect("Failed to find room.breakout.close event");
+
+            assert_eq!(event["rooms"], json!([breakout_room_id]));
+
+            // Assert the breakout room is closed.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let room = db::room::FindQuery::new(breakout_room.id())
+                .execute(&conn)
+                .expect("Failed to find room")
+                .expect("Room not found");
+
+            assert!(room.is_closed());
+            assert!(room.closed_at().is_some());
+        }
+    }
+}
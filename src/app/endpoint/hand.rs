@@ -0,0 +1,553 @@
+use std::result::Result as StdResult;
+
+use async_std::{stream, task};
+use async_trait::async_trait;
+use chrono::{serde::ts_seconds, DateTime, Duration, Utc};
+use diesel::{pg::PgConnection, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use svc_agent::{
+    mqtt::{IncomingRequestProperties, ResponseStatus},
+    Addressable, AgentId,
+};
+
+use crate::{
+    app::{
+        context::Context, endpoint::prelude::*, message_handler::MessageStream,
+        metrics::HistogramExt,
+    },
+    db,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RaisedHand {
+    agent_id: AgentId,
+    #[serde(with = "ts_seconds")]
+    raised_at: DateTime<Utc>,
+}
+
+impl From<db::raised_hand::Object> for RaisedHand {
+    fn from(object: db::raised_hand::Object) -> Self {
+        Self {
+            agent_id: object.agent_id().to_owned(),
+            raised_at: object.raised_at(),
+        }
+    }
+}
+
+/// The whole hand queue of the room sent on each change so late joiners don't miss anything.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HandUpdateEvent {
+    room_id: db::room::Id,
+    hands: Vec<RaisedHand>,
+}
+
+impl HandUpdateEvent {
+    fn load(room_id: db::room::Id, conn: &PgConnection) -> StdResult<Self, AppError> {
+        let hands = db::raised_hand::ListQuery::new(room_id)
+            .execute(conn)?
+            .into_iter()
+            .map(RaisedHand::from)
+            .collect();
+
+        Ok(Self { room_id, hands })
+    }
+}
+
+/// Lowers the agent's hand in the room.
+///
+/// Returns the updated queue or `None` if the hand hasn't been raised.
+pub(crate) fn lower_hand(
+    room_id: db::room::Id,
+    agent_id: &AgentId,
+    conn: &PgConnection,
+) -> StdResult<Option<HandUpdateEvent>, AppError> {
+    conn.transaction::<_, AppError, _>(|| {
+        let row_count = db::raised_hand::DeleteQuery::new(room_id, agent_id).execute(conn)?;
+
+        if row_count == 0 {
+            Ok(None)
+        } else {
+            HandUpdateEvent::load(room_id, conn).map(Some)
+        }
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct RaiseRequest {
+    room_id: db::room::Id,
+}
+
+pub struct RaiseHandler;
+
+#[async_trait]
+impl RequestHandler for RaiseHandler {
+    type Payload = RaiseRequest;
+    const ERROR_TITLE: &'static str = "Failed to raise hand";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let room = find_room(context, payload.room_id, helpers::RoomTimeRequirement::Open).await?;
+
+        // Put the hand at the end of the queue unless it's already there.
+        let conn = context.get_conn().await?;
+        let maybe_event = task::spawn_blocking({
+            let room = room.clone();
+            let agent_id = reqp.as_agent_id().to_owned();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    helpers::check_room_presence(&room, &agent_id, &conn)?;
+
+                    let row_count =
+                        db::raised_hand::InsertQuery::new(room.id(), &agent_id).execute(&conn)?;
+
+                    if row_count == 0 {
+                        Ok(None)
+                    } else {
+                        HandUpdateEvent::load(room.id(), &conn).map(Some)
+                    }
+                })
+            }
+        })
+        .await?;
+
+        let messages = build_messages(context, &room, maybe_event, reqp, None);
+
+        context
+            .metrics()
+            .request_duration
+            .hand_raise
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(messages)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub type LowerRequest = RaiseRequest;
+pub struct LowerHandler;
+
+#[async_trait]
+impl RequestHandler for LowerHandler {
+    type Payload = LowerRequest;
+    const ERROR_TITLE: &'static str = "Failed to lower hand";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let room = find_room(context, payload.room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        let conn = context.get_conn().await?;
+        let maybe_event = task::spawn_blocking({
+            let room_id = room.id();
+            let agent_id = reqp.as_agent_id().to_owned();
+            move || lower_hand(room_id, &agent_id, &conn)
+        })
+        .await?;
+
+        let messages = build_messages(context, &room, maybe_event, reqp, None);
+
+        context
+            .metrics()
+            .request_duration
+            .hand_lower
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(messages)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    room_id: db::room::Id,
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+    const ERROR_TITLE: &'static str = "Failed to list raised hands";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let room = find_room(context, payload.room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Authorize room reading.
+        let room_id = room.id().to_string();
+        let object = vec!["rooms", &room_id];
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience(), reqp, object, "read")
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let conn = context.get_conn().await?;
+        let event = task::spawn_blocking({
+            let room_id = room.id();
+            move || HandUpdateEvent::load(room_id, &conn)
+        })
+        .await?;
+
+        context
+            .metrics()
+            .request_duration
+            .hand_list
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::once(helpers::build_response(
+            ResponseStatus::OK,
+            event.hands,
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct DismissRequest {
+    room_id: db::room::Id,
+    agent_id: AgentId,
+}
+
+pub struct DismissHandler;
+
+#[async_trait]
+impl RequestHandler for DismissHandler {
+    type Payload = DismissRequest;
+    const ERROR_TITLE: &'static str = "Failed to dismiss hand";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let room = find_room(context, payload.room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Authorize managing the agent in the room like for `agent.admit`.
+        let room_id = room.id().to_string();
+        let agent_id = payload.agent_id.to_string();
+        let object = vec!["rooms", &room_id, "agents", &agent_id];
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience(), reqp, object, "update")
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let conn = context.get_conn().await?;
+        let maybe_event = task::spawn_blocking({
+            let room_id = room.id();
+            let agent_id = payload.agent_id;
+            move || lower_hand(room_id, &agent_id, &conn)
+        })
+        .await?;
+
+        let messages = build_messages(context, &room, maybe_event, reqp, Some(authz_time));
+
+        context
+            .metrics()
+            .request_duration
+            .hand_dismiss
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(messages)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+async fn find_room<C: Context>(
+    context: &mut C,
+    room_id: db::room::Id,
+    opening_requirement: helpers::RoomTimeRequirement,
+) -> StdResult<db::room::Object, AppError> {
+    let conn = context.get_conn().await?;
+    let room =
+        task::spawn_blocking(move || helpers::find_room_by_id(room_id, opening_requirement, &conn))
+            .await?;
+
+    helpers::add_room_logger_tags(context, &room);
+    Ok(room)
+}
+
+// Sends `hand.update` notification only if the queue has actually changed.
+fn build_messages<C: Context>(
+    context: &C,
+    room: &db::room::Object,
+    maybe_event: Option<HandUpdateEvent>,
+    reqp: &IncomingRequestProperties,
+    maybe_authz_time: Option<Duration>,
+) -> MessageStream {
+    let mut messages = vec![helpers::build_response(
+        ResponseStatus::OK,
+        json!({}),
+        reqp,
+        context.start_timestamp(),
+        maybe_authz_time,
+    )];
+
+    if let Some(event) = maybe_event {
+        messages.push(helpers::build_notification(
+            "hand.update",
+            &format!("rooms/{}/events", room.id()),
+            event,
+            reqp,
+            context.start_timestamp(),
+        ));
+    }
+
+    Box::new(stream::from_iter(messages))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value as JsonValue;
+
+    use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+    use super::*;
+
+    mod raise {
+        use super::*;
+
+        #[async_std::test]
+        async fn raise_hand() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let first = TestAgent::new("web", "first", USR_AUDIENCE);
+            let second = TestAgent::new("web", "second", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_agent(&conn, first.agent_id(), room.id());
+                shared_helpers::insert_agent(&conn, second.agent_id(), room.id());
+                room
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            // Raise hands one by one.
+            for agent in &[&first, &second] {
+                let payload = RaiseRequest { room_id: room.id() };
+
+                handle_request::<RaiseHandler>(&mut context, agent, payload)
+                    .await
+                    .expect("Hand raising failed");
+            }
+
+            // Raising the hand again keeps its place in the queue and doesn't notify.
+            let payload = RaiseRequest { room_id: room.id() };
+
+            let messages = handle_request::<RaiseHandler>(&mut context, &first, payload)
+                .await
+                .expect("Hand raising failed");
+
+            assert_eq!(messages.len(), 1);
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            // Lowering the first hand moves the second one to the top.
+            let payload = LowerRequest { room_id: room.id() };
+
+            let messages = handle_request::<LowerHandler>(&mut context, &first, payload)
+                .await
+                .expect("Hand lowering failed");
+
+            let (event, evp, topic) = find_event::<HandUpdateEvent>(messages.as_slice());
+            assert_eq!(evp.label(), "hand.update");
+            assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+            assert_eq!(event.room_id, room.id());
+            assert_eq!(event.hands.len(), 1);
+            assert_eq!(&event.hands[0].agent_id, second.agent_id());
+        }
+
+        #[async_std::test]
+        async fn raise_hand_not_entered() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_room(&conn)
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+            let payload = RaiseRequest { room_id: room.id() };
+
+            let err = handle_request::<RaiseHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on hand raising");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "agent_not_entered_the_room");
+        }
+    }
+
+    mod list {
+        use super::*;
+
+        #[async_std::test]
+        async fn list_hands() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let late_joiner = TestAgent::new("web", "late_joiner", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                db::raised_hand::InsertQuery::new(room.id(), agent.agent_id())
+                    .execute(&conn)
+                    .expect("Failed to raise hand");
+
+                room
+            };
+
+            // Allow the late joiner to read the room.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(late_joiner.account_id(), vec!["rooms", &room_id], "read");
+
+            // Make hand.list request.
+            let mut context = TestContext::new(db, authz);
+            let payload = ListRequest { room_id: room.id() };
+
+            let messages = handle_request::<ListHandler>(&mut context, &late_joiner, payload)
+                .await
+                .expect("Hands listing failed");
+
+            let (hands, respp, _) = find_response::<Vec<RaisedHand>>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(hands.len(), 1);
+            assert_eq!(&hands[0].agent_id, agent.agent_id());
+        }
+    }
+
+    mod dismiss {
+        use super::*;
+
+        #[async_std::test]
+        async fn dismiss_hand() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                db::raised_hand::InsertQuery::new(room.id(), student.agent_id())
+                    .execute(&conn)
+                    .expect("Failed to raise hand");
+
+                room
+            };
+
+            // Allow the host to manage the student.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                host.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            // Make hand.dismiss request.
+            let mut context = TestContext::new(db, authz);
+
+            let payload = DismissRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<DismissHandler>(&mut context, &host, payload)
+                .await
+                .expect("Hand dismissing failed");
+
+            let (event, evp, _) = find_event::<HandUpdateEvent>(messages.as_slice());
+            assert_eq!(evp.label(), "hand.update");
+            assert!(event.hands.is_empty());
+        }
+
+        #[async_std::test]
+        async fn dismiss_hand_not_authorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+            let other_student = TestAgent::new("web", "other_student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_room(&conn)
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let payload = DismissRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let err = handle_request::<DismissHandler>(&mut context, &other_student, payload)
+                .await
+                .expect_err("Unexpected success on hand dismissing");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+}
@@ -3,23 +3,29 @@ use std::ops::Bound;
 use crate::{
     app::{
         context::Context,
+        endpoint::{
+            room::SubscriptionRequest, subscription::CorrelationDataPayload, CorrelationData,
+        },
         error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
         API_VERSION,
     },
+    config::QualityPresetConfig,
     db,
-    db::room::Object as Room,
+    db::room::{Object as Room, QualityPreset},
 };
-use anyhow::anyhow;
-use chrono::{DateTime, Duration, Utc};
+use anyhow::{anyhow, Context as AnyhowContext};
+use async_std::task;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
 use diesel::pg::PgConnection;
 use serde::Serialize;
 use slog::o;
 use svc_agent::{
     mqtt::{
         IncomingRequestProperties, IntoPublishableMessage, OutgoingEvent, OutgoingEventProperties,
-        OutgoingResponse, ResponseStatus, ShortTermTimingProperties,
+        OutgoingRequest, OutgoingResponse, ResponseStatus, ShortTermTimingProperties,
+        SubscriptionTopic,
     },
-    AgentId,
+    Addressable, AgentId, Subscription,
 };
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -54,6 +60,49 @@ pub fn build_notification(
     Box::new(OutgoingEvent::broadcast(payload, props, path))
 }
 
+// Builds a dynamic subscription request to the broker on the `rooms/:room_id/events` topic.
+pub fn build_subscription_request<C: Context>(
+    context: &C,
+    reqp: &IncomingRequestProperties,
+    method: &str,
+    subject: AgentId,
+    room_id: db::room::Id,
+    corr_data: fn(CorrelationDataPayload) -> CorrelationData,
+    maybe_authz_time: Option<Duration>,
+) -> Result<Box<dyn IntoPublishableMessage + Send>, AppError> {
+    let object = vec![
+        String::from("rooms"),
+        room_id.to_string(),
+        String::from("events"),
+    ];
+
+    let payload = SubscriptionRequest::new(subject.clone(), object.clone());
+    let broker_id = AgentId::new("nevermind", context.config().broker_id.to_owned());
+
+    let response_topic = Subscription::unicast_responses_from(&broker_id)
+        .subscription_topic(context.agent_id(), API_VERSION)
+        .context("Failed to build response topic")
+        .error(AppErrorKind::BrokerRequestFailed)?;
+
+    let corr_data_payload = CorrelationDataPayload::new(reqp.to_owned(), subject, object);
+
+    let corr_data = corr_data(corr_data_payload)
+        .dump()
+        .context("Failed to dump correlation data")
+        .error(AppErrorKind::BrokerRequestFailed)?;
+
+    let mut timing = ShortTermTimingProperties::until_now(context.start_timestamp());
+
+    if let Some(authz_time) = maybe_authz_time {
+        timing.set_authorization_time(authz_time);
+    }
+
+    let props = reqp.to_request(method, &response_topic, &corr_data, timing);
+    let to = &context.config().broker_id;
+    let outgoing_request = OutgoingRequest::multicast(payload, props, to, API_VERSION);
+    Ok(Box::new(outgoing_request))
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 pub enum RoomTimeRequirement {
@@ -158,15 +207,153 @@ pub fn check_room_presence(
     agent_id: &AgentId,
     conn: &PgConnection,
 ) -> Result<(), AppError> {
-    let results = db::agent::ListQuery::new()
-        .room_id(room.id())
-        .agent_id(agent_id)
-        .execute(conn)?;
+    let maybe_agent = db::agent::FindQuery::new(agent_id, room.id()).execute(conn)?;
 
-    if results.is_empty() {
-        Err(anyhow!("Agent is not online in the room")).error(AppErrorKind::AgentNotEnteredTheRoom)
-    } else {
+    match maybe_agent {
+        // Agents waiting for admission are not considered present.
+        Some(agent) if agent.status() != db::agent::Status::Waiting => Ok(()),
+        _ => Err(anyhow!("Agent is not online in the room"))
+            .error(AppErrorKind::AgentNotEnteredTheRoom),
+    }
+}
+
+/// Fails with `RoomFull` if the room has no place for one more agent.
+/// Must be called in a transaction since it locks the room not to let concurrent
+/// entrances exceed the limit.
+pub fn check_room_capacity(
+    room_id: db::room::Id,
+    max_agents: i32,
+    agent_id: &AgentId,
+    conn: &PgConnection,
+) -> Result<(), AppError> {
+    db::room::LockQuery::new(room_id).execute(conn)?;
+
+    // Both entering and entered agents take places. The agent may be re-entering the room
+    // or being admitted from the waiting room so don't count it.
+    let agents_count = db::agent::CountQuery::new(
+        room_id,
+        &[db::agent::Status::InProgress, db::agent::Status::Ready],
+    )
+    .except_agent_id(agent_id)
+    .execute(conn)?;
+
+    if agents_count >= i64::from(max_agents) {
+        return Err(anyhow!("The room is full")).error(AppErrorKind::RoomFull);
+    }
+
+    Ok(())
+}
+
+/// Fails unless agent reader and writer configs are available in the room
+/// that is its RTCs are either owned by agents or shared.
+pub fn check_rtc_configs_availability(room: &db::room::Object) -> Result<(), AppError> {
+    match room.rtc_sharing_policy() {
+        db::rtc::SharingPolicy::Owned | db::rtc::SharingPolicy::Shared => Ok(()),
+        db::rtc::SharingPolicy::None => Err(anyhow!(
+            "Agent configs are available only for rooms with owned or shared RTC sharing policy"
+        ))
+        .error(AppErrorKind::InvalidPayload),
+    }
+}
+
+/// Finds the room's RTC an agent config item refers to either by the RTC id
+/// or by the RTC owner which is possible only in rooms with owned RTC sharing policy.
+pub fn find_config_rtc<'a>(
+    room: &db::room::Object,
+    rtcs: &'a [db::rtc::Object],
+    maybe_rtc_id: Option<db::rtc::Id>,
+    maybe_agent_id: Option<&AgentId>,
+) -> Result<&'a db::rtc::Object, AppError> {
+    match (maybe_rtc_id, maybe_agent_id) {
+        (Some(rtc_id), _) => rtcs
+            .iter()
+            .find(|rtc| rtc.id() == rtc_id)
+            .ok_or_else(|| anyhow!("RTC {} not found in the room", rtc_id)),
+        (None, Some(agent_id)) if room.rtc_sharing_policy() == db::rtc::SharingPolicy::Owned => {
+            rtcs.iter()
+                .find(|rtc| rtc.created_by() == agent_id)
+                .ok_or_else(|| anyhow!("{} has no owned RTC", agent_id))
+        }
+        _ => Err(anyhow!(
+            "Config item must refer to an RTC by `rtc_id` or by `agent_id` in owned RTC rooms"
+        )),
+    }
+    .error(AppErrorKind::InvalidPayload)
+}
+
+/// Finds the writer settings the quality preset stands for in the audience.
+pub fn find_quality_preset<C: Context>(
+    context: &C,
+    audience: &str,
+    preset: QualityPreset,
+) -> Result<QualityPresetConfig, AppError> {
+    context
+        .config()
+        .quality_presets
+        .get(audience, preset)
+        .ok_or_else(|| anyhow!("Quality preset is not configured for the audience"))
+        .error(AppErrorKind::InvalidPayload)
+}
+
+/// Checks whether the agent is allowed to moderate the room,
+/// e.g. to enter it when it's locked or bypassing admission.
+pub async fn is_moderator<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    reqp: &IncomingRequestProperties,
+) -> Result<bool, AppError> {
+    let room_id = room.id().to_string();
+    let object = vec!["rooms", &room_id];
+
+    match context
+        .authz()
+        .authorize(room.audience(), reqp, object, "moderate")
+        .await
+    {
+        Ok(authz_time) => {
+            context.metrics().observe_auth(authz_time);
+            Ok(true)
+        }
+        Err(err) => {
+            let err = AppError::from(err);
+
+            if err.error_kind() == AppErrorKind::AccessDenied {
+                Ok(false)
+            } else {
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Fails with `NotOnStage` if the room is in the stage mode and the agent is neither
+/// on the stage nor allowed to moderate the room.
+pub async fn check_stage<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    reqp: &IncomingRequestProperties,
+) -> Result<(), AppError> {
+    if !room.is_stage_mode() {
+        return Ok(());
+    }
+
+    let conn = context.get_conn().await?;
+
+    let maybe_member = task::spawn_blocking({
+        let room_id = room.id();
+        let agent_id = reqp.as_agent_id().to_owned();
+        move || db::stage_member::FindQuery::new(room_id, &agent_id).execute(&conn)
+    })
+    .await?;
+
+    let is_on_stage = maybe_member
+        .map(|member| member.status() == db::stage_member::Status::Accepted)
+        .unwrap_or(false);
+
+    if is_on_stage || is_moderator(context, room, reqp).await? {
         Ok(())
+    } else {
+        Err(anyhow!("Agent is not on the stage")).error(AppErrorKind::NotOnStage)
     }
 }
 
@@ -177,3 +364,102 @@ pub fn add_room_logger_tags<C: Context>(context: &mut C, room: &db::room::Object
         context.add_logger_tags(o!("scope" => scope.to_string()));
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug)]
+pub enum Quota {
+    OpenRooms,
+    ActiveStreams,
+    MonthlyRecordedMinutes,
+}
+
+impl Quota {
+    fn label(self) -> &'static str {
+        match self {
+            Self::OpenRooms => "open_rooms",
+            Self::ActiveStreams => "active_streams",
+            Self::MonthlyRecordedMinutes => "monthly_recorded_minutes",
+        }
+    }
+}
+
+/// Fails with `QuotaExceeded` if the audience has already used up the quota.
+///
+/// Audiences without the quota configured are not checked.
+/// The usage is exported as a gauge on each check.
+pub async fn check_quota<C: Context>(
+    context: &mut C,
+    audience: &str,
+    quota: Quota,
+) -> Result<(), AppError> {
+    check_quota_for(context, audience, quota, 1).await
+}
+
+/// Same as `check_quota` but fails unless there's room for `count` units at once.
+pub async fn check_quota_for<C: Context>(
+    context: &mut C,
+    audience: &str,
+    quota: Quota,
+    count: u32,
+) -> Result<(), AppError> {
+    let maybe_limit = context
+        .config()
+        .quotas
+        .get(audience)
+        .and_then(|config| match quota {
+            Quota::OpenRooms => config.max_open_rooms.map(|limit| limit as f64),
+            Quota::ActiveStreams => config.max_active_streams.map(|limit| limit as f64),
+            Quota::MonthlyRecordedMinutes => config.max_monthly_recorded_minutes,
+        });
+
+    let limit = match maybe_limit {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+
+    let conn = context.get_conn().await?;
+
+    let usage = task::spawn_blocking({
+        let audience = audience.to_owned();
+
+        move || match quota {
+            Quota::OpenRooms => db::room::count_open(&audience, &conn).map(|count| count as f64),
+            Quota::ActiveStreams => {
+                db::janus_rtc_stream::count_active(&audience, &conn).map(|count| count as f64)
+            }
+            Quota::MonthlyRecordedMinutes => {
+                let now = Utc::now();
+                let month_start = Utc.ymd(now.year(), now.month(), 1).and_hms(0, 0, 0);
+                db::usage::RecordedMinutesQuery::new(&audience, month_start, now).execute(&conn)
+            }
+        }
+    })
+    .await?;
+
+    context
+        .metrics()
+        .observe_quota_usage(quota.label(), audience, usage);
+
+    if usage + f64::from(count.saturating_sub(1)) >= limit {
+        return Err(anyhow!(
+            "Audience '{}' has exceeded '{}' quota",
+            audience,
+            quota.label()
+        ))
+        .error(AppErrorKind::QuotaExceeded);
+    }
+
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Idempotency keys created before the returned moment are expired according to the config.
+pub fn idempotency_keys_expired_before<C: Context>(context: &C) -> Result<DateTime<Utc>, AppError> {
+    let ttl = Duration::from_std(context.config().idempotency.key_ttl)
+        .context("Invalid idempotency key TTL")
+        .error(AppErrorKind::MessageHandlingFailed)?;
+
+    Ok(Utc::now() - ttl)
+}
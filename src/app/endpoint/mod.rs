@@ -52,24 +52,45 @@ macro_rules! request_routes {
 
 // Request routes configuration: method => RequestHandler
 request_routes!(
+    "agent.admit" => agent::AdmitHandler,
+    "agent.ban" => agent::BanHandler,
+    "agent.kick" => agent::KickHandler,
     "agent.list" => agent::ListHandler,
+    "agent.reject" => agent::RejectHandler,
     "agent_reader_config.read" => agent_reader_config::ReadHandler,
     "agent_reader_config.update" => agent_reader_config::UpdateHandler,
     "agent_writer_config.read" => agent_writer_config::ReadHandler,
     "agent_writer_config.update" => agent_writer_config::UpdateHandler,
+    "attendance.list" => attendance::ListHandler,
+    "hand.dismiss" => hand::DismissHandler,
+    "hand.list" => hand::ListHandler,
+    "hand.lower" => hand::LowerHandler,
+    "hand.raise" => hand::RaiseHandler,
     "message.broadcast" => message::BroadcastHandler,
     "message.unicast" => message::UnicastHandler,
+    "room.breakout.assign" => breakout::AssignHandler,
+    "room.breakout.close" => breakout::CloseHandler,
+    "room.breakout.create" => breakout::CreateHandler,
     "room.create" => room::CreateHandler,
     "room.enter" => room::EnterHandler,
     "room.leave" => room::LeaveHandler,
+    "room.list" => room::ListHandler,
+    "room.mute_all" => room::MuteAllHandler,
     "room.read" => room::ReadHandler,
     "room.update" => room::UpdateHandler,
     "rtc.connect" => rtc::ConnectHandler,
     "rtc.create" => rtc::CreateHandler,
     "rtc.list" => rtc::ListHandler,
     "rtc.read" => rtc::ReadHandler,
+    "rtc.unpublish" => rtc::UnpublishHandler,
     "rtc_signal.create" => rtc_signal::CreateHandler,
     "rtc_stream.list" => rtc_stream::ListHandler,
+    "stage.accept" => stage::AcceptHandler,
+    "stage.invite" => stage::InviteHandler,
+    "stage.remove" => stage::RemoveHandler,
+    "system.backend.update" => system::BackendUpdateHandler,
+    "system.balancer.explain" => system::BalancerExplainHandler,
+    "system.usage.read" => system::UsageReadHandler,
     "system.vacuum" => system::VacuumHandler,
     "writer_config_snapshot.read" => writer_config_snapshot::ReadHandler
 );
@@ -83,6 +104,9 @@ pub enum CorrelationData {
     SubscriptionCreate(subscription::CorrelationDataPayload),
     SubscriptionDelete(subscription::CorrelationDataPayload),
     MessageUnicast(message::CorrelationDataPayload),
+    AgentKick(subscription::CorrelationDataPayload),
+    BreakoutEnter(subscription::CorrelationDataPayload),
+    BreakoutLeave(subscription::CorrelationDataPayload),
 }
 
 #[async_trait]
@@ -129,7 +153,10 @@ macro_rules! response_routes {
 response_routes!(
     SubscriptionCreate => subscription::CreateResponseHandler,
     SubscriptionDelete => subscription::DeleteResponseHandler,
-    MessageUnicast => message::UnicastResponseHandler
+    MessageUnicast => message::UnicastResponseHandler,
+    AgentKick => subscription::KickResponseHandler,
+    BreakoutEnter => subscription::BreakoutEnterResponseHandler,
+    BreakoutLeave => subscription::KickResponseHandler
 );
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -197,12 +224,16 @@ event_routes!(
 mod agent;
 mod agent_reader_config;
 mod agent_writer_config;
+mod attendance;
+mod breakout;
+mod hand;
 pub mod helpers;
 mod message;
 mod room;
 pub mod rtc;
 pub mod rtc_signal;
 pub mod rtc_stream;
+mod stage;
 mod subscription;
 pub mod system;
 mod writer_config_snapshot;
@@ -2,39 +2,49 @@ use anyhow::{anyhow, Context as AnyhowContext};
 use async_std::{stream, task};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use diesel::{pg::PgConnection, Connection};
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
-use std::ops::Bound;
+use serde_json::{json, Value as JsonValue};
+use slog::error;
+use std::{ops::Bound, result::Result as StdResult};
 use svc_agent::{
     mqtt::{
         IncomingRequestProperties, IntoPublishableMessage, OutgoingRequest, ResponseStatus,
         ShortTermTimingProperties, SubscriptionTopic,
     },
-    Addressable, AgentId, Subscription,
+    Addressable, AgentId, Authenticable, Subscription,
 };
 use uuid::Uuid;
 
 use crate::{
     app::{
         context::Context,
-        endpoint::{prelude::*, subscription::CorrelationDataPayload},
+        endpoint::{
+            agent_writer_config,
+            prelude::*,
+            subscription::{CorrelationDataPayload, RoomEnterLeaveEvent},
+        },
         metrics::HistogramExt,
-        API_VERSION,
+        room_closing, API_VERSION,
     },
     db,
-    db::{room::RoomBackend, rtc::SharingPolicy as RtcSharingPolicy},
+    db::{
+        idempotency_key::Kind as IdempotencyKeyKind,
+        room::{FindQueryable, QualityPreset, RoomBackend},
+        rtc::SharingPolicy as RtcSharingPolicy,
+    },
 };
 
 ///////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Serialize)]
-struct SubscriptionRequest {
+pub(crate) struct SubscriptionRequest {
     subject: AgentId,
     object: Vec<String>,
 }
 
 impl SubscriptionRequest {
-    fn new(subject: AgentId, object: Vec<String>) -> Self {
+    pub(crate) fn new(subject: AgentId, object: Vec<String>) -> Self {
         Self { subject, object }
     }
 }
@@ -54,6 +64,15 @@ pub struct CreateRequest {
     reserve: Option<i32>,
     tags: Option<JsonValue>,
     classroom_id: Option<Uuid>,
+    requires_admission: Option<bool>,
+    max_agents: Option<i32>,
+    stage_mode: Option<bool>,
+    /// The default quality preset for the room's writers.
+    #[serde(default)]
+    quality_preset: Option<QualityPreset>,
+    /// Retries with the same key return the room created by the first request.
+    #[serde(default)]
+    idempotency_key: Option<String>,
 }
 
 pub struct CreateHandler;
@@ -74,16 +93,65 @@ impl RequestHandler for CreateHandler {
             .or_else(|| payload.backend.map(|b| b.into()))
             .unwrap_or(RtcSharingPolicy::None);
 
+        if let Some(max_agents) = payload.max_agents {
+            validate_max_agents(max_agents)?;
+        }
+
+        // Quality presets are applied through agent writer configs.
+        let maybe_quality_preset = match payload.quality_preset {
+            None => None,
+            Some(_) if rtc_sharing_policy == RtcSharingPolicy::None => {
+                return Err(anyhow!(
+                    "Quality preset is available only for rooms with owned or shared RTC sharing policy"
+                ))
+                .error(AppErrorKind::InvalidPayload);
+            }
+            Some(preset) => {
+                let config = helpers::find_quality_preset(context, &payload.audience, preset)?;
+                Some((preset, config))
+            }
+        };
+
         // Authorize room creation on the tenant.
         let authz_time = context
             .authz()
             .authorize(&payload.audience, reqp, vec!["rooms"], "create")
             .await?;
         context.metrics().observe_auth(authz_time);
+
+        // Respond with the room created by the original request if it's a retry.
+        if let Some(ref key) = payload.idempotency_key {
+            let created_after = helpers::idempotency_keys_expired_before(context)?;
+            let conn = context.get_conn().await?;
+
+            let maybe_room = task::spawn_blocking({
+                let audience = payload.audience.clone();
+                let key = key.clone();
+
+                move || find_room_by_idempotency_key(&audience, &key, created_after, &conn)
+            })
+            .await?;
+
+            if let Some(room) = maybe_room {
+                helpers::add_room_logger_tags(context, &room);
+
+                return Ok(Box::new(stream::once(helpers::build_response(
+                    ResponseStatus::OK,
+                    room,
+                    reqp,
+                    context.start_timestamp(),
+                    Some(authz_time),
+                ))));
+            }
+        }
+
+        helpers::check_quota(context, &payload.audience, helpers::Quota::OpenRooms).await?;
+
         // Create a room.
+        let expired_before = helpers::idempotency_keys_expired_before(context)?;
         let conn = context.get_conn().await?;
         let audience = payload.audience.clone();
-        let room = task::spawn_blocking({
+        let (room, is_created) = task::spawn_blocking({
             move || {
                 let mut q =
                     db::room::InsertQuery::new(payload.time, &payload.audience, rtc_sharing_policy);
@@ -100,7 +168,56 @@ impl RequestHandler for CreateHandler {
                     q = q.classroom_id(classroom_id);
                 }
 
-                q.execute(&conn)
+                if let Some(requires_admission) = payload.requires_admission {
+                    q = q.requires_admission(requires_admission);
+                }
+
+                if let Some(max_agents) = payload.max_agents {
+                    q = q.max_agents(max_agents);
+                }
+
+                if let Some(stage_mode) = payload.stage_mode {
+                    q = q.stage_mode(stage_mode);
+                }
+
+                if let Some((preset, config)) = maybe_quality_preset {
+                    q = q.quality_preset(preset, config.video_remb);
+                }
+
+                let audience = &payload.audience;
+                let idempotency_key = payload.idempotency_key.as_deref();
+
+                let result = conn.transaction::<_, diesel::result::Error, _>(|| {
+                    let room = q.execute(&conn)?;
+
+                    if let Some(key) = idempotency_key {
+                        let maybe_key = db::idempotency_key::InsertQuery::new(
+                            audience,
+                            key,
+                            room.id(),
+                            expired_before,
+                        )
+                        .execute(&conn)?;
+
+                        if maybe_key.is_none() {
+                            return Err(diesel::result::Error::RollbackTransaction);
+                        }
+                    }
+
+                    Ok(room)
+                });
+
+                match (result, idempotency_key) {
+                    (Ok(room), _) => Ok((room, true)),
+                    // A concurrent request with the same key has stored it first.
+                    (Err(diesel::result::Error::RollbackTransaction), Some(key)) => {
+                        find_room_by_idempotency_key(audience, key, expired_before, &conn)?
+                            .map(|room| (room, false))
+                            .ok_or_else(|| anyhow!("Room for the idempotency key not found"))
+                            .error(AppErrorKind::RoomNotFound)
+                    }
+                    (Err(err), _) => Err(err.into()),
+                }
             }
         })
         .await?;
@@ -117,6 +234,11 @@ impl RequestHandler for CreateHandler {
             Some(authz_time),
         );
 
+        // The concurrent request has already broadcasted the room.
+        if !is_created {
+            return Ok(Box::new(stream::once(response)));
+        }
+
         let notification = helpers::build_notification(
             "room.create",
             &format!("audiences/{}/events", audience),
@@ -129,6 +251,22 @@ impl RequestHandler for CreateHandler {
     }
 }
 
+fn find_room_by_idempotency_key(
+    audience: &str,
+    key: &str,
+    created_after: DateTime<Utc>,
+    conn: &PgConnection,
+) -> StdResult<Option<db::room::Object>, AppError> {
+    let maybe_key =
+        db::idempotency_key::FindQuery::new(audience, IdempotencyKeyKind::Room, key, created_after)
+            .execute(conn)?;
+
+    match maybe_key {
+        None => Ok(None),
+        Some(key) => Ok(db::room::FindQuery::new(key.room_id()).execute(conn)?),
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Deserialize)]
@@ -181,6 +319,143 @@ impl RequestHandler for ReadHandler {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+const MAX_LIMIT: i64 = 25;
+
+// Position of the last room on the page. It's passed to clients as an opaque base64 string.
+#[derive(Debug, Deserialize, Serialize)]
+struct ListCursor {
+    created_at: DateTime<Utc>,
+    id: db::room::Id,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    audience: String,
+    classroom_id: Option<Uuid>,
+    #[serde(default)]
+    #[serde(with = "crate::serde::ts_seconds_option_bound_tuple")]
+    time: Option<db::room::Time>,
+    rtc_sharing_policy: Option<RtcSharingPolicy>,
+    tags: Option<JsonValue>,
+    closed: Option<bool>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListResponse {
+    rooms: Vec<db::room::Object>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+    const ERROR_TITLE: &'static str = "Failed to list rooms";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        // Authorize rooms listing on the tenant.
+        let authz_time = context
+            .authz()
+            .authorize(&payload.audience, reqp, vec!["rooms"], "list")
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let maybe_cursor = match payload.cursor {
+            Some(ref cursor) => Some(
+                crate::util::from_base64::<ListCursor>(cursor)
+                    .context("Invalid cursor")
+                    .error(AppErrorKind::InvalidPayload)?,
+            ),
+            None => None,
+        };
+
+        let limit = match payload.limit {
+            Some(limit) if limit < 1 => {
+                return Err(anyhow!("Limit must be positive"))
+                    .error(AppErrorKind::InvalidPayload)?;
+            }
+            Some(limit) => std::cmp::min(limit, MAX_LIMIT),
+            None => MAX_LIMIT,
+        };
+
+        let conn = context.get_conn().await?;
+
+        let mut rooms = task::spawn_blocking(move || {
+            let mut query = db::room::ListQuery::new().audience(&payload.audience);
+
+            if let Some(classroom_id) = payload.classroom_id {
+                query = query.classroom_id(classroom_id);
+            }
+
+            if let Some(time) = payload.time {
+                query = query.time(time);
+            }
+
+            if let Some(rtc_sharing_policy) = payload.rtc_sharing_policy {
+                query = query.rtc_sharing_policy(rtc_sharing_policy);
+            }
+
+            if let Some(ref tags) = payload.tags {
+                query = query.tags(tags);
+            }
+
+            if let Some(closed) = payload.closed {
+                query = query.closed(closed);
+            }
+
+            if let Some(cursor) = maybe_cursor {
+                query = query.after(cursor.created_at, cursor.id);
+            }
+
+            // Take one more room to find out whether there's the next page.
+            query.limit(limit + 1).execute(&conn)
+        })
+        .await?;
+
+        let next_cursor = if rooms.len() as i64 > limit {
+            rooms.truncate(limit as usize);
+
+            rooms
+                .last()
+                .map(|room| {
+                    crate::util::to_base64(&ListCursor {
+                        created_at: room.created_at(),
+                        id: room.id(),
+                    })
+                })
+                .transpose()
+                .context("Failed to encode cursor")
+                .error(AppErrorKind::MessageBuildingFailed)?
+        } else {
+            None
+        };
+
+        context
+            .metrics()
+            .request_duration
+            .room_list
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::once(helpers::build_response(
+            ResponseStatus::OK,
+            ListResponse { rooms, next_cursor },
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateRequest {
     id: db::room::Id,
@@ -190,6 +465,10 @@ pub struct UpdateRequest {
     reserve: Option<Option<i32>>,
     tags: Option<JsonValue>,
     classroom_id: Option<Uuid>,
+    locked: Option<bool>,
+    requires_admission: Option<bool>,
+    max_agents: Option<Option<i32>>,
+    stage_mode: Option<bool>,
 }
 pub struct UpdateHandler;
 
@@ -203,6 +482,10 @@ impl RequestHandler for UpdateHandler {
         payload: Self::Payload,
         reqp: &IncomingRequestProperties,
     ) -> Result {
+        if let Some(Some(max_agents)) = payload.max_agents {
+            validate_max_agents(max_agents)?;
+        }
+
         let time_requirement = if payload.time.is_some() {
             // Forbid changing time of a closed room.
             helpers::RoomTimeRequirement::NotClosedOrUnboundedOpen
@@ -275,6 +558,10 @@ impl RequestHandler for UpdateHandler {
                 .reserve(payload.reserve)
                 .tags(payload.tags)
                 .classroom_id(payload.classroom_id)
+                .locked(payload.locked)
+                .requires_admission(payload.requires_admission)
+                .max_agents(payload.max_agents)
+                .stage_mode(payload.stage_mode)
                 .execute(&conn)?)
         }).await?;
 
@@ -300,21 +587,32 @@ impl RequestHandler for UpdateHandler {
         // Publish room closed notification.
         if let (_, Bound::Excluded(closed_at)) = room.time() {
             if room_was_open && *closed_at <= Utc::now() {
-                responses.push(helpers::build_notification(
-                    "room.close",
-                    &format!("rooms/{}/events", room.id()),
-                    room.clone(),
-                    reqp,
-                    context.start_timestamp(),
-                ));
-
-                responses.push(helpers::build_notification(
-                    "room.close",
-                    &format!("audiences/{}/events", room.audience()),
-                    room.clone(),
-                    reqp,
-                    context.start_timestamp(),
-                ));
+                // The room has been updated anyway so closing failure doesn't fail the request.
+                match room_closing::close_room(context, room.clone()).await {
+                    Ok(Some(closed_room)) => {
+                        responses.push(helpers::build_notification(
+                            "room.close",
+                            &format!("rooms/{}/events", closed_room.id()),
+                            closed_room.clone(),
+                            reqp,
+                            context.start_timestamp(),
+                        ));
+
+                        responses.push(helpers::build_notification(
+                            "room.close",
+                            &format!("audiences/{}/events", closed_room.audience()),
+                            closed_room,
+                            reqp,
+                            context.start_timestamp(),
+                        ));
+                    }
+                    // The scheduler has closed it in the meantime and notified already.
+                    Ok(None) => (),
+                    Err(err) => {
+                        error!(context.logger(), "Failed to close room: {:?}", err);
+                        err.notify_sentry(context.logger());
+                    }
+                }
             }
         }
         context
@@ -343,8 +641,20 @@ impl RequestHandler for EnterHandler {
         reqp: &IncomingRequestProperties,
     ) -> Result {
         let conn = context.get_conn().await?;
-        let room = task::spawn_blocking(move || {
-            helpers::find_room_by_id(payload.id, helpers::RoomTimeRequirement::NotClosed, &conn)
+        let (room, maybe_ban) = task::spawn_blocking({
+            let account_id = reqp.as_account_id().to_owned();
+
+            move || {
+                let room = helpers::find_room_by_id(
+                    payload.id,
+                    helpers::RoomTimeRequirement::NotClosed,
+                    &conn,
+                )?;
+
+                let maybe_ban =
+                    db::room_ban::FindQuery::new(room.id(), &account_id).execute(&conn)?;
+                Ok::<_, AppError>((room, maybe_ban))
+            }
         })
         .await?;
         helpers::add_room_logger_tags(context, &room);
@@ -359,13 +669,97 @@ impl RequestHandler for EnterHandler {
             .await?;
         context.metrics().observe_auth(authz_time);
 
-        // Register agent in `in_progress` state.
+        if maybe_ban.is_some() {
+            return Err(anyhow!("The account is banned from the room"))
+                .error(AppErrorKind::AgentBanned);
+        }
+
+        // Moderators may enter the room even if it's locked or requires admission.
+        let is_moderator = if room.is_locked() || room.requires_admission() {
+            helpers::is_moderator(context, &room, reqp).await?
+        } else {
+            false
+        };
+
+        if room.is_locked() && !is_moderator {
+            return Err(anyhow!("The room is locked")).error(AppErrorKind::RoomLocked);
+        }
+
+        // Put the agent to the waiting room without subscribing to the room's events
+        // until a moderator admits it. An already admitted agent enters as usual.
+        let is_waiting = if room.requires_admission() && !is_moderator {
+            let conn = context.get_conn().await?;
+
+            let agent = task::spawn_blocking({
+                let agent_id = reqp.as_agent_id().clone();
+                let room_id = room.id();
+
+                move || {
+                    db::agent::InsertQuery::new(&agent_id, room_id)
+                        .status(db::agent::Status::Waiting)
+                        .execute(&conn)
+                }
+            })
+            .await?;
+
+            agent.status() == db::agent::Status::Waiting
+        } else {
+            false
+        };
+
+        if is_waiting {
+            let response = helpers::build_response(
+                ResponseStatus::ACCEPTED,
+                json!({}),
+                reqp,
+                context.start_timestamp(),
+                Some(authz_time),
+            );
+
+            let notification = helpers::build_notification(
+                "agent.waiting",
+                &format!("rooms/{}/events", room.id()),
+                RoomEnterLeaveEvent::new(room.id(), reqp.as_agent_id().to_owned()),
+                reqp,
+                context.start_timestamp(),
+            );
+
+            context
+                .metrics()
+                .request_duration
+                .room_enter
+                .observe_timestamp(context.start_timestamp());
+
+            return Ok(Box::new(stream::from_iter(vec![response, notification])));
+        }
+
+        // Register agent in `in_progress` state if the room is not full.
         let conn = context.get_conn().await?;
-        task::spawn_blocking({
+        let result = task::spawn_blocking({
             let agent_id = reqp.as_agent_id().clone();
-            move || db::agent::InsertQuery::new(&agent_id, room.id()).execute(&conn)
+            let room_id = room.id();
+            let maybe_max_agents = room.max_agents();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    if let Some(max_agents) = maybe_max_agents {
+                        helpers::check_room_capacity(room_id, max_agents, &agent_id, &conn)?;
+                    }
+
+                    db::agent::InsertQuery::new(&agent_id, room_id).execute(&conn)?;
+                    Ok(())
+                })
+            }
         })
-        .await?;
+        .await;
+
+        if let Err(ref err) = result {
+            if err.error_kind() == AppErrorKind::RoomFull {
+                context.metrics().observe_room_full(room.audience());
+            }
+        }
+
+        result?;
 
         // Send dynamic subscription creation request to the broker.
         let subject = reqp.as_agent_id().to_owned();
@@ -484,6 +878,139 @@ impl RequestHandler for LeaveHandler {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Deserialize)]
+pub struct MuteAllRequest {
+    id: db::room::Id,
+    #[serde(default)]
+    exclude: Vec<AgentId>,
+}
+
+pub struct MuteAllHandler;
+
+#[async_trait]
+impl RequestHandler for MuteAllHandler {
+    type Payload = MuteAllRequest;
+    const ERROR_TITLE: &'static str = "Failed to mute all agents in room";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let conn = context.get_conn().await?;
+
+        let room = task::spawn_blocking({
+            let id = payload.id;
+            move || helpers::find_room_by_id(id, helpers::RoomTimeRequirement::Open, &conn)
+        })
+        .await?;
+        helpers::add_room_logger_tags(context, &room);
+
+        helpers::check_rtc_configs_availability(&room)?;
+
+        // Authorize room updating on the tenant like for updating others' writer configs.
+        let room_id = room.id().to_string();
+        let object = vec!["rooms", &room_id];
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience(), reqp, object, "update")
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Turn off audio of every RTC in the room except the excluded agents' ones.
+        let conn = context.get_conn().await?;
+
+        let (rtc_writer_configs_with_rtcs, maybe_backend) = task::spawn_blocking({
+            let room_id = room.id();
+            let backend_id = room.backend_id().cloned();
+            let agent_id = reqp.as_agent_id().to_owned();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    let rtcs = db::rtc::ListQuery::new().room_id(room_id).execute(&conn)?;
+
+                    for rtc in rtcs {
+                        if payload.exclude.contains(rtc.created_by()) {
+                            continue;
+                        }
+
+                        db::rtc_writer_config::UpsertQuery::new(rtc.id())
+                            .send_audio(false)
+                            .send_audio_updated_by(&agent_id)
+                            .execute(&conn)?;
+
+                        db::rtc_writer_config_snapshot::InsertQuery::new(
+                            rtc.id(),
+                            None,
+                            Some(false),
+                        )
+                        .execute(&conn)?;
+                    }
+
+                    let rtc_writer_configs_with_rtcs =
+                        db::rtc_writer_config::ListWithRtcQuery::new(room_id).execute(&conn)?;
+
+                    let maybe_backend = match &backend_id {
+                        None => None,
+                        Some(backend_id) => db::janus_backend::FindQuery::new()
+                            .id(backend_id)
+                            .execute(&conn)?,
+                    };
+
+                    Ok((rtc_writer_configs_with_rtcs, maybe_backend))
+                })
+            }
+        })
+        .await?;
+
+        // Push the whole config to the backend at once.
+        if let Some(backend) = maybe_backend {
+            agent_writer_config::update_backend(context, &backend, &rtc_writer_configs_with_rtcs)
+                .await?;
+        }
+
+        // Respond and broadcast the same notification as `agent_writer_config.update` does.
+        let state = agent_writer_config::State::new(room.id(), &rtc_writer_configs_with_rtcs);
+
+        let response = helpers::build_response(
+            ResponseStatus::OK,
+            state.clone(),
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        let notification = helpers::build_notification(
+            "agent_writer_config.update",
+            &format!("rooms/{}/events", room.id()),
+            state,
+            reqp,
+            context.start_timestamp(),
+        );
+
+        context
+            .metrics()
+            .request_duration
+            .room_mute_all
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::from_iter(vec![response, notification])))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+fn validate_max_agents(max_agents: i32) -> StdResult<(), AppError> {
+    if max_agents > 0 {
+        Ok(())
+    } else {
+        Err(anyhow!("Max agents must be positive")).error(AppErrorKind::InvalidPayload)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[cfg(test)]
 mod test {
     use serde::Deserialize;
@@ -534,6 +1061,11 @@ mod test {
                     reserve: Some(123),
                     tags: Some(json!({ "foo": "bar" })),
                     classroom_id: Some(classroom_id),
+                    requires_admission: None,
+                    max_agents: None,
+                    stage_mode: None,
+                    quality_preset: None,
+                    idempotency_key: None,
                 };
 
                 let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
@@ -581,6 +1113,11 @@ mod test {
                 reserve: None,
                 tags: None,
                 classroom_id: None,
+                requires_admission: None,
+                max_agents: None,
+                stage_mode: None,
+                quality_preset: None,
+                idempotency_key: None,
             };
 
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
@@ -590,26 +1127,271 @@ mod test {
             assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
             assert_eq!(err.kind(), "access_denied");
         }
-    }
-
-    mod read {
-        use crate::{
-            db::room::Object as Room,
-            test_helpers::{prelude::*, test_deps::LocalDeps},
-        };
-
-        use super::super::*;
 
         #[async_std::test]
-        async fn read_room() {
+        async fn create_room_with_quality_preset() {
             let local_deps = LocalDeps::new();
             let postgres = local_deps.run_postgres();
             let db = TestDb::with_local_postgres(&postgres);
 
-            let room = {
-                let conn = db
-                    .connection_pool()
-                    .get()
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["rooms"], "create");
+
+            let mut context = TestContext::new(db.clone(), authz);
+
+            // The audience has its own bitrate for the `high` preset.
+            let presets = vec![(
+                QualityPreset::High,
+                crate::config::QualityPresetConfig {
+                    video_remb: 1_500_000,
+                },
+            )]
+            .into_iter()
+            .collect();
+
+            context
+                .config_mut()
+                .quality_presets
+                .audiences
+                .insert(USR_AUDIENCE.to_owned(), presets);
+
+            // Make room.create request.
+            let payload = CreateRequest {
+                time: (Bound::Included(Utc::now()), Bound::Unbounded),
+                audience: USR_AUDIENCE.to_owned(),
+                backend: None,
+                rtc_sharing_policy: Some(db::rtc::SharingPolicy::Shared),
+                reserve: Some(2),
+                tags: None,
+                classroom_id: None,
+                requires_admission: None,
+                max_agents: None,
+                stage_mode: None,
+                quality_preset: Some(QualityPreset::High),
+                idempotency_key: None,
+            };
+
+            let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room creation failed");
+
+            // Assert response.
+            let (room, respp, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(room.quality_preset(), Some(QualityPreset::High));
+
+            // Assert the reserve is weighed by the audience's preset bitrate.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let room = db::room::FindQuery::new(room.id())
+                .execute(&conn)
+                .expect("Failed to find room")
+                .expect("Room not found");
+
+            assert_eq!(room.balancing_reserve(), 3);
+        }
+
+        #[async_std::test]
+        async fn create_room_with_quality_preset_and_no_rtc_sharing() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["rooms"], "create");
+
+            let mut context = TestContext::new(db, authz);
+
+            // Make room.create request.
+            let payload = CreateRequest {
+                time: (Bound::Included(Utc::now()), Bound::Unbounded),
+                audience: USR_AUDIENCE.to_owned(),
+                backend: None,
+                rtc_sharing_policy: Some(db::rtc::SharingPolicy::None),
+                reserve: None,
+                tags: None,
+                classroom_id: None,
+                requires_admission: None,
+                max_agents: None,
+                stage_mode: None,
+                quality_preset: Some(QualityPreset::Low),
+                idempotency_key: None,
+            };
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room creation");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+
+        #[async_std::test]
+        async fn create_room_quota_exceeded() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                // The only allowed open room of the audience.
+                shared_helpers::insert_room(&conn);
+            }
+
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["rooms"], "create");
+
+            let mut context = TestContext::new(db, authz);
+
+            context.config_mut().quotas.insert(
+                USR_AUDIENCE.to_owned(),
+                crate::config::QuotaConfig {
+                    max_open_rooms: Some(1),
+                    ..Default::default()
+                },
+            );
+
+            // Make room.create request.
+            let payload = CreateRequest {
+                time: (Bound::Included(Utc::now()), Bound::Unbounded),
+                audience: USR_AUDIENCE.to_owned(),
+                backend: None,
+                rtc_sharing_policy: Some(db::rtc::SharingPolicy::Shared),
+                reserve: None,
+                tags: None,
+                classroom_id: None,
+                requires_admission: None,
+                max_agents: None,
+                stage_mode: None,
+                quality_preset: None,
+                idempotency_key: None,
+            };
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room creation");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "quota_exceeded");
+        }
+
+        fn build_idempotent_payload() -> CreateRequest {
+            CreateRequest {
+                time: (Bound::Included(Utc::now()), Bound::Unbounded),
+                audience: USR_AUDIENCE.to_owned(),
+                backend: None,
+                rtc_sharing_policy: Some(db::rtc::SharingPolicy::Shared),
+                reserve: None,
+                tags: None,
+                classroom_id: None,
+                requires_admission: None,
+                max_agents: None,
+                stage_mode: None,
+                quality_preset: None,
+                idempotency_key: Some("lms-retry-key".to_owned()),
+            }
+        }
+
+        #[async_std::test]
+        async fn create_room_retry_with_idempotency_key() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["rooms"], "create");
+
+            let mut context = TestContext::new(db.clone(), authz);
+
+            // Make room.create request twice with the same key.
+            let messages =
+                handle_request::<CreateHandler>(&mut context, &agent, build_idempotent_payload())
+                    .await
+                    .expect("Room creation failed");
+
+            let (room, _, _) = find_response::<Room>(messages.as_slice());
+
+            let messages =
+                handle_request::<CreateHandler>(&mut context, &agent, build_idempotent_payload())
+                    .await
+                    .expect("Room creation retry failed");
+
+            // Assert the retry responds with the same room without a notification.
+            assert_eq!(messages.len(), 1);
+            let (retried_room, respp, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(retried_room.id(), room.id());
+
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let count = db::room::count_open(USR_AUDIENCE, &conn).expect("Failed to count rooms");
+            assert_eq!(count, 1);
+        }
+
+        #[async_std::test]
+        async fn create_room_retry_with_expired_idempotency_key() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["rooms"], "create");
+
+            let mut context = TestContext::new(db, authz);
+            context.config_mut().idempotency.key_ttl = std::time::Duration::from_secs(0);
+
+            // Make room.create request twice with the same key.
+            let messages =
+                handle_request::<CreateHandler>(&mut context, &agent, build_idempotent_payload())
+                    .await
+                    .expect("Room creation failed");
+
+            let (room, _, _) = find_response::<Room>(messages.as_slice());
+
+            let messages =
+                handle_request::<CreateHandler>(&mut context, &agent, build_idempotent_payload())
+                    .await
+                    .expect("Room creation retry failed");
+
+            // Assert a new room has been created since the key has expired.
+            let (new_room, _, _) = find_response::<Room>(messages.as_slice());
+            assert_ne!(new_room.id(), room.id());
+        }
+    }
+
+    mod read {
+        use crate::{
+            db::room::Object as Room,
+            test_helpers::{prelude::*, test_deps::LocalDeps},
+        };
+
+        use super::super::*;
+
+        #[async_std::test]
+        async fn read_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
                     .expect("Failed to get DB connection");
 
                 // Create room.
@@ -686,144 +1468,357 @@ mod test {
         }
     }
 
-    mod update {
+    mod list {
         use std::ops::Bound;
 
-        use chrono::{Duration, SubsecRound, Utc};
+        use chrono::{Duration, Utc};
         use serde_json::json;
-        use uuid::Uuid;
 
-        use crate::{
-            db::room::Object as Room,
-            test_helpers::{find_event_by_predicate, prelude::*, test_deps::LocalDeps},
-        };
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
 
         use super::super::*;
 
+        fn allow_listing(authz: &mut TestAuthz, agent: &TestAgent) {
+            authz.allow(agent.account_id(), vec!["rooms"], "list");
+        }
+
+        fn build_request(limit: Option<i64>, cursor: Option<String>) -> ListRequest {
+            ListRequest {
+                audience: USR_AUDIENCE.to_owned(),
+                classroom_id: None,
+                time: None,
+                rtc_sharing_policy: None,
+                tags: None,
+                closed: None,
+                cursor,
+                limit,
+            }
+        }
+
         #[async_std::test]
-        async fn update_room() {
+        async fn list_rooms_filtered() {
             let local_deps = LocalDeps::new();
             let postgres = local_deps.run_postgres();
             let db = TestDb::with_local_postgres(&postgres);
-            let now = Utc::now().trunc_subsecs(0);
+            let tags = json!({ "webinar_id": "123", "scope": "math" });
 
-            let room = {
+            let tagged_room = {
                 let conn = db
                     .connection_pool()
                     .get()
                     .expect("Failed to get DB connection");
 
-                // Create room.
+                let now = Utc::now();
+
+                // Rooms that don't match the filter.
+                shared_helpers::insert_room(&conn);
+                shared_helpers::insert_closed_room(&conn);
+
+                factory::Room::new()
+                    .audience("another.example.org")
+                    .time((Bound::Included(now), Bound::Unbounded))
+                    .tags(&tags)
+                    .insert(&conn);
+
                 factory::Room::new()
                     .audience(USR_AUDIENCE)
-                    .time((Bound::Unbounded, Bound::Unbounded))
-                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .time((
+                        Bound::Included(now - Duration::hours(2)),
+                        Bound::Excluded(now - Duration::hours(1)),
+                    ))
+                    .tags(&tags)
+                    .insert(&conn);
+
+                // The room to find.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(now), Bound::Unbounded))
+                    .tags(&tags)
                     .insert(&conn)
             };
 
-            // Allow agent to update the room.
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
-            let room_id = room.id().to_string();
-            authz.allow(agent.account_id(), vec!["rooms", &room_id], "update");
+            allow_listing(&mut authz, &agent);
 
-            // Make room.update request.
+            // Make room.list request.
             let mut context = TestContext::new(db, authz);
-            let classroom_id = Uuid::new_v4();
-
-            let time = (
-                Bound::Included(now + Duration::minutes(50)),
-                Bound::Unbounded,
-            );
 
-            let payload = UpdateRequest {
-                id: room.id(),
-                time: Some(time),
-                reserve: Some(Some(123)),
-                tags: Some(json!({"foo": "bar"})),
-                classroom_id: Some(classroom_id),
+            let payload = ListRequest {
+                tags: Some(json!({ "webinar_id": "123" })),
+                closed: Some(false),
+                ..build_request(None, None)
             };
 
-            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
                 .await
-                .expect("Room update failed");
+                .expect("Rooms listing failed");
 
             // Assert response.
-            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
+            let (resp, respp, _) = find_response::<ListResponse>(messages.as_slice());
             assert_eq!(respp.status(), ResponseStatus::OK);
-            assert_eq!(resp_room.id(), room.id());
-            assert_eq!(resp_room.audience(), room.audience());
-            assert_eq!(resp_room.time(), &time);
-            assert_eq!(
-                resp_room.rtc_sharing_policy(),
-                db::rtc::SharingPolicy::Shared
-            );
-            assert_eq!(resp_room.reserve(), Some(123));
-            assert_eq!(resp_room.tags(), &json!({"foo": "bar"}));
-            assert_eq!(resp_room.classroom_id(), Some(classroom_id));
+            assert_eq!(resp.rooms.len(), 1);
+            assert_eq!(resp.rooms[0].id(), tagged_room.id());
+            assert!(resp.next_cursor.is_none());
         }
 
         #[async_std::test]
-        async fn update_room_with_wrong_time() {
+        async fn list_rooms_paginated() {
             let local_deps = LocalDeps::new();
             let postgres = local_deps.run_postgres();
             let db = TestDb::with_local_postgres(&postgres);
-            let now = Utc::now().trunc_subsecs(0);
 
-            let room = {
+            let mut rooms = {
                 let conn = db
                     .connection_pool()
                     .get()
                     .expect("Failed to get DB connection");
 
-                // Create room.
-                factory::Room::new()
-                    .audience(USR_AUDIENCE)
-                    .time((
-                        Bound::Included(now - Duration::hours(1)),
-                        Bound::Excluded(now + Duration::hours(2)),
-                    ))
-                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
-                    .insert(&conn)
+                (0..3)
+                    .map(|_| shared_helpers::insert_room(&conn))
+                    .collect::<Vec<_>>()
             };
 
-            // Allow agent to update the room.
+            // Rooms are listed from the newest.
+            rooms.sort_by_key(|r| std::cmp::Reverse((r.created_at(), r.id().to_string())));
+            let room_ids = rooms.iter().map(|r| r.id()).collect::<Vec<_>>();
+
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
-            let room_id = room.id().to_string();
-            authz.allow(agent.account_id(), vec!["rooms", &room_id], "update");
-
-            // Make room.update request.
+            allow_listing(&mut authz, &agent);
             let mut context = TestContext::new(db, authz);
 
-            let time = (
-                Bound::Included(now + Duration::hours(3)),
-                Bound::Excluded(now - Duration::hours(2)),
-            );
+            // Get the first page.
+            let messages =
+                handle_request::<ListHandler>(&mut context, &agent, build_request(Some(2), None))
+                    .await
+                    .expect("Rooms listing failed");
 
-            let payload = UpdateRequest {
-                id: room.id(),
-                time: Some(time),
-                reserve: Some(Some(123)),
-                tags: Some(json!({"foo": "bar"})),
-                classroom_id: None,
-            };
+            let (resp, _, _) = find_response::<ListResponse>(messages.as_slice());
+            let ids = resp.rooms.iter().map(|r| r.id()).collect::<Vec<_>>();
+            assert_eq!(ids, &room_ids[..2]);
+            let cursor = resp.next_cursor.expect("Missing next cursor");
 
-            handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            // Get the second page.
+            let payload = build_request(Some(2), Some(cursor));
+
+            let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
                 .await
-                .expect_err("Room update succeeded when it should've failed");
+                .expect("Rooms listing failed");
+
+            let (resp, _, _) = find_response::<ListResponse>(messages.as_slice());
+            let ids = resp.rooms.iter().map(|r| r.id()).collect::<Vec<_>>();
+            assert_eq!(ids, &room_ids[2..]);
+            assert!(resp.next_cursor.is_none());
         }
 
         #[async_std::test]
-        async fn update_and_close_room() {
+        async fn list_rooms_invalid_cursor() {
             let local_deps = LocalDeps::new();
             let postgres = local_deps.run_postgres();
             let db = TestDb::with_local_postgres(&postgres);
-            let now = Utc::now().trunc_subsecs(0);
 
-            let room = {
-                let conn = db
-                    .connection_pool()
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            allow_listing(&mut authz, &agent);
+            let mut context = TestContext::new(db, authz);
+            let payload = build_request(None, Some("garbage".to_owned()));
+
+            let err = handle_request::<ListHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rooms listing");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+
+        #[async_std::test]
+        async fn list_rooms_zero_limit() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            allow_listing(&mut authz, &agent);
+            let mut context = TestContext::new(db, authz);
+            let payload = build_request(Some(0), None);
+
+            let err = handle_request::<ListHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rooms listing");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+
+        #[async_std::test]
+        async fn list_rooms_unauthorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let err =
+                handle_request::<ListHandler>(&mut context, &agent, build_request(None, None))
+                    .await
+                    .expect_err("Unexpected success on rooms listing");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+
+    mod update {
+        use std::ops::Bound;
+
+        use chrono::{Duration, SubsecRound, Utc};
+        use serde_json::json;
+        use uuid::Uuid;
+
+        use crate::{
+            db::room::Object as Room,
+            test_helpers::{find_event_by_predicate, prelude::*, test_deps::LocalDeps},
+        };
+
+        use super::super::*;
+
+        #[async_std::test]
+        async fn update_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let now = Utc::now().trunc_subsecs(0);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                // Create room.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Unbounded, Bound::Unbounded))
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .insert(&conn)
+            };
+
+            // Allow agent to update the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "update");
+
+            // Make room.update request.
+            let mut context = TestContext::new(db, authz);
+            let classroom_id = Uuid::new_v4();
+
+            let time = (
+                Bound::Included(now + Duration::minutes(50)),
+                Bound::Unbounded,
+            );
+
+            let payload = UpdateRequest {
+                id: room.id(),
+                time: Some(time),
+                reserve: Some(Some(123)),
+                tags: Some(json!({"foo": "bar"})),
+                classroom_id: Some(classroom_id),
+                locked: Some(true),
+                requires_admission: None,
+                max_agents: Some(Some(30)),
+                stage_mode: None,
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room update failed");
+
+            // Assert response.
+            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp_room.id(), room.id());
+            assert_eq!(resp_room.audience(), room.audience());
+            assert_eq!(resp_room.time(), &time);
+            assert_eq!(
+                resp_room.rtc_sharing_policy(),
+                db::rtc::SharingPolicy::Shared
+            );
+            assert_eq!(resp_room.reserve(), Some(123));
+            assert_eq!(resp_room.tags(), &json!({"foo": "bar"}));
+            assert_eq!(resp_room.classroom_id(), Some(classroom_id));
+            assert!(resp_room.is_locked());
+            assert_eq!(resp_room.max_agents(), Some(30));
+        }
+
+        #[async_std::test]
+        async fn update_room_with_wrong_time() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let now = Utc::now().trunc_subsecs(0);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                // Create room.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((
+                        Bound::Included(now - Duration::hours(1)),
+                        Bound::Excluded(now + Duration::hours(2)),
+                    ))
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .insert(&conn)
+            };
+
+            // Allow agent to update the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "update");
+
+            // Make room.update request.
+            let mut context = TestContext::new(db, authz);
+
+            let time = (
+                Bound::Included(now + Duration::hours(3)),
+                Bound::Excluded(now - Duration::hours(2)),
+            );
+
+            let payload = UpdateRequest {
+                id: room.id(),
+                time: Some(time),
+                reserve: Some(Some(123)),
+                tags: Some(json!({"foo": "bar"})),
+                classroom_id: None,
+                locked: None,
+                requires_admission: None,
+                max_agents: None,
+                stage_mode: None,
+            };
+
+            handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Room update succeeded when it should've failed");
+        }
+
+        #[async_std::test]
+        async fn update_and_close_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let now = Utc::now().trunc_subsecs(0);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
                     .get()
                     .expect("Failed to get DB connection");
 
@@ -858,113 +1853,434 @@ mod test {
                 reserve: Some(Some(123)),
                 tags: Default::default(),
                 classroom_id: Default::default(),
+                locked: Default::default(),
+                requires_admission: Default::default(),
+                max_agents: Default::default(),
+                stage_mode: Default::default(),
             };
 
             let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
                 .await
                 .expect("Room update failed");
 
-            assert_eq!(messages.len(), 4);
+            assert_eq!(messages.len(), 4);
+
+            let (closed_tenant_notification, _, _) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                    evp.label() == "room.close" && topic.contains("audiences")
+                })
+                .expect("Failed to find room.close event");
+
+            assert_eq!(
+                closed_tenant_notification
+                    .get("id")
+                    .and_then(|v| v.as_str()),
+                Some(room.id().to_string()).as_deref()
+            );
+
+            let (closed_room_notification, _, _) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                    evp.label() == "room.close" && topic.contains("rooms")
+                })
+                .expect("Failed to find room.close event");
+
+            assert_eq!(
+                closed_room_notification.get("id").and_then(|v| v.as_str()),
+                Some(room.id().to_string()).as_deref()
+            );
+
+            // The room must not be closed by the scheduler once again.
+            let conn = context
+                .get_conn()
+                .await
+                .expect("Failed to get DB connection");
+
+            let room =
+                helpers::find_room_by_id(room.id(), helpers::RoomTimeRequirement::Any, &conn)
+                    .expect("Failed to find room");
+
+            assert!(room.closed_at().is_some());
+        }
+
+        #[async_std::test]
+        async fn update_and_close_unbounded_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let now = Utc::now().trunc_subsecs(0);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                // Create room.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(now - Duration::hours(1)), Bound::Unbounded))
+                    .insert(&conn)
+            };
+
+            // Allow agent to update the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "update");
+
+            // Make room.update request.
+            let mut context = TestContext::new(db, authz);
+
+            let time = (
+                Bound::Included(now - Duration::hours(1)),
+                Bound::Excluded(now - Duration::seconds(5)),
+            );
+
+            let payload = UpdateRequest {
+                id: room.id(),
+                time: Some(time),
+                reserve: Default::default(),
+                tags: Default::default(),
+                classroom_id: Default::default(),
+                locked: Default::default(),
+                requires_admission: Default::default(),
+                max_agents: Default::default(),
+                stage_mode: Default::default(),
+            };
+
+            handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room update failed");
+        }
+
+        #[async_std::test]
+        async fn update_room_missing() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let payload = UpdateRequest {
+                id: db::room::Id::random(),
+                time: Default::default(),
+                reserve: Default::default(),
+                tags: Default::default(),
+                classroom_id: Default::default(),
+                locked: Default::default(),
+                requires_admission: Default::default(),
+                max_agents: Default::default(),
+                stage_mode: Default::default(),
+            };
+
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room update");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "room_not_found");
+        }
+
+        #[async_std::test]
+        async fn update_room_closed() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                // Create closed room.
+                shared_helpers::insert_closed_room(&conn)
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let payload = UpdateRequest {
+                id: room.id(),
+                time: Some((Bound::Included(Utc::now()), Bound::Excluded(Utc::now()))),
+                reserve: Default::default(),
+                tags: Default::default(),
+                classroom_id: Default::default(),
+                locked: Default::default(),
+                requires_admission: Default::default(),
+                max_agents: Default::default(),
+                stage_mode: Default::default(),
+            };
+
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room update");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "room_closed");
+        }
+    }
+
+    mod enter {
+        use chrono::{Duration, Utc};
+
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+        use super::{super::*, DynSubRequest};
+
+        #[async_std::test]
+        async fn enter_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                // Create room.
+                shared_helpers::insert_room(&conn)
+            };
+
+            // Allow agent to subscribe to the rooms' events.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "read");
+
+            // Make room.enter request.
+            let mut context = TestContext::new(db, authz);
+            let payload = EnterRequest { id: room.id() };
+
+            let messages = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room entrance failed");
+
+            // Assert dynamic subscription request.
+            let (payload, reqp, topic) = find_request::<DynSubRequest>(messages.as_slice());
+
+            let expected_topic = format!(
+                "agents/{}.{}/api/{}/out/{}",
+                context.config().agent_label,
+                context.config().id,
+                API_VERSION,
+                context.config().broker_id,
+            );
+
+            assert_eq!(topic, expected_topic);
+            assert_eq!(reqp.method(), "subscription.create");
+            assert_eq!(payload.subject, agent.agent_id().to_owned());
+            assert_eq!(payload.object, vec!["rooms", &room_id, "events"]);
+        }
+
+        fn insert_locked_room(db: &TestDb) -> db::room::Object {
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let room = shared_helpers::insert_room(&conn);
+
+            db::room::UpdateQuery::new(room.id())
+                .locked(Some(true))
+                .execute(&conn)
+                .expect("Failed to lock room")
+        }
+
+        #[async_std::test]
+        async fn enter_locked_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let room = insert_locked_room(&db);
+
+            // Allow agent to subscribe to the rooms' events but not to moderate it.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "read");
+
+            // Make room.enter request.
+            let mut context = TestContext::new(db, authz);
+            let payload = EnterRequest { id: room.id() };
+
+            let err = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room entering");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "room_locked");
+        }
+
+        #[async_std::test]
+        async fn enter_locked_room_as_moderator() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let room = insert_locked_room(&db);
+
+            // Allow agent to subscribe to the rooms' events and to moderate it.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "read");
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "moderate");
+
+            // Make room.enter request.
+            let mut context = TestContext::new(db, authz);
+            let payload = EnterRequest { id: room.id() };
+
+            let messages = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room entrance failed");
+
+            let (_, reqp, _) = find_request::<DynSubRequest>(messages.as_slice());
+            assert_eq!(reqp.method(), "subscription.create");
+        }
+
+        fn insert_room_requiring_admission(db: &TestDb) -> db::room::Object {
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let room = shared_helpers::insert_room(&conn);
+
+            db::room::UpdateQuery::new(room.id())
+                .requires_admission(Some(true))
+                .execute(&conn)
+                .expect("Failed to update room")
+        }
+
+        #[async_std::test]
+        async fn enter_room_requiring_admission() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let room = insert_room_requiring_admission(&db);
+
+            // Allow agent to subscribe to the rooms' events but not to moderate it.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "read");
+
+            // Make room.enter request.
+            let mut context = TestContext::new(db.clone(), authz);
+            let payload = EnterRequest { id: room.id() };
+
+            let messages = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room entrance failed");
+
+            // Assert the agent is put to the waiting room instead of being subscribed.
+            assert_eq!(messages.len(), 2);
 
-            let (closed_tenant_notification, _, _) =
-                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
-                    evp.label() == "room.close" && topic.contains("audiences")
-                })
-                .expect("Failed to find room.close event");
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
 
-            assert_eq!(
-                closed_tenant_notification
-                    .get("id")
-                    .and_then(|v| v.as_str()),
-                Some(room.id().to_string()).as_deref()
-            );
+            let (event, evp, topic) = find_event::<JsonValue>(messages.as_slice());
+            assert_eq!(evp.label(), "agent.waiting");
+            assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+            assert_eq!(event["agent_id"], agent.agent_id().to_string());
 
-            let (closed_room_notification, _, _) =
-                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
-                    evp.label() == "room.close" && topic.contains("rooms")
-                })
-                .expect("Failed to find room.close event");
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
 
-            assert_eq!(
-                closed_room_notification.get("id").and_then(|v| v.as_str()),
-                Some(room.id().to_string()).as_deref()
-            );
+            let db_agent = db::agent::FindQuery::new(agent.agent_id(), room.id())
+                .execute(&conn)
+                .expect("Failed to find agent")
+                .expect("Agent not found");
+
+            assert_eq!(db_agent.status(), db::agent::Status::Waiting);
         }
 
         #[async_std::test]
-        async fn update_and_close_unbounded_room() {
+        async fn reenter_room_requiring_admission_after_admission() {
             let local_deps = LocalDeps::new();
             let postgres = local_deps.run_postgres();
             let db = TestDb::with_local_postgres(&postgres);
-            let now = Utc::now().trunc_subsecs(0);
+            let room = insert_room_requiring_admission(&db);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-            let room = {
+            // The agent has already been admitted.
+            {
                 let conn = db
                     .connection_pool()
                     .get()
                     .expect("Failed to get DB connection");
 
-                // Create room.
-                factory::Room::new()
-                    .audience(USR_AUDIENCE)
-                    .time((Bound::Included(now - Duration::hours(1)), Bound::Unbounded))
-                    .insert(&conn)
-            };
+                factory::Agent::new()
+                    .agent_id(agent.agent_id())
+                    .room_id(room.id())
+                    .status(db::agent::Status::Ready)
+                    .insert(&conn);
+            }
 
-            // Allow agent to update the room.
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            // Allow agent to subscribe to the rooms' events but not to moderate it.
             let mut authz = TestAuthz::new();
             let room_id = room.id().to_string();
-            authz.allow(agent.account_id(), vec!["rooms", &room_id], "update");
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "read");
 
-            // Make room.update request.
-            let mut context = TestContext::new(db, authz);
+            // Make room.enter request.
+            let mut context = TestContext::new(db.clone(), authz);
+            let payload = EnterRequest { id: room.id() };
 
-            let time = (
-                Bound::Included(now - Duration::hours(1)),
-                Bound::Excluded(now - Duration::seconds(5)),
-            );
+            let messages = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room entrance failed");
 
-            let payload = UpdateRequest {
-                id: room.id(),
-                time: Some(time),
-                reserve: Default::default(),
-                tags: Default::default(),
-                classroom_id: Default::default(),
-            };
+            // Assert the agent gets subscribed instead of being put to the waiting room.
+            let (_, reqp, _) = find_request::<DynSubRequest>(messages.as_slice());
+            assert_eq!(reqp.method(), "subscription.create");
 
-            handle_request::<UpdateHandler>(&mut context, &agent, payload)
-                .await
-                .expect("Room update failed");
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let db_agent = db::agent::FindQuery::new(agent.agent_id(), room.id())
+                .execute(&conn)
+                .expect("Failed to find agent")
+                .expect("Agent not found");
+
+            assert_ne!(db_agent.status(), db::agent::Status::Waiting);
         }
 
         #[async_std::test]
-        async fn update_room_missing() {
+        async fn enter_room_requiring_admission_as_moderator() {
             let local_deps = LocalDeps::new();
             let postgres = local_deps.run_postgres();
             let db = TestDb::with_local_postgres(&postgres);
+            let room = insert_room_requiring_admission(&db);
 
+            // Allow agent to subscribe to the rooms' events and to moderate it.
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
-            let mut context = TestContext::new(db, TestAuthz::new());
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "read");
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "moderate");
 
-            let payload = UpdateRequest {
-                id: db::room::Id::random(),
-                time: Default::default(),
-                reserve: Default::default(),
-                tags: Default::default(),
-                classroom_id: Default::default(),
-            };
+            // Make room.enter request.
+            let mut context = TestContext::new(db, authz);
+            let payload = EnterRequest { id: room.id() };
 
-            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            let messages = handle_request::<EnterHandler>(&mut context, &agent, payload)
                 .await
-                .expect_err("Unexpected success on room update");
+                .expect("Room entrance failed");
 
-            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
-            assert_eq!(err.kind(), "room_not_found");
+            let (_, reqp, _) = find_request::<DynSubRequest>(messages.as_slice());
+            assert_eq!(reqp.method(), "subscription.create");
         }
 
         #[async_std::test]
-        async fn update_room_closed() {
+        async fn enter_full_room() {
             let local_deps = LocalDeps::new();
             let postgres = local_deps.run_postgres();
             let db = TestDb::with_local_postgres(&postgres);
@@ -976,41 +2292,48 @@ mod test {
                     .get()
                     .expect("Failed to get DB connection");
 
-                // Create closed room.
-                shared_helpers::insert_closed_room(&conn)
-            };
+                let room = shared_helpers::insert_room(&conn);
 
-            let mut context = TestContext::new(db, TestAuthz::new());
+                let room = db::room::UpdateQuery::new(room.id())
+                    .max_agents(Some(Some(1)))
+                    .execute(&conn)
+                    .expect("Failed to update room");
 
-            let payload = UpdateRequest {
-                id: room.id(),
-                time: Some((Bound::Included(Utc::now()), Bound::Excluded(Utc::now()))),
-                reserve: Default::default(),
-                tags: Default::default(),
-                classroom_id: Default::default(),
-            };
+                // The only slot is taken by another agent which is still entering.
+                let other_agent = TestAgent::new("web", "user456", USR_AUDIENCE);
 
-            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
-                .await
-                .expect_err("Unexpected success on room update");
+                factory::Agent::new()
+                    .agent_id(other_agent.agent_id())
+                    .room_id(room.id())
+                    .status(db::agent::Status::InProgress)
+                    .insert(&conn);
 
-            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
-            assert_eq!(err.kind(), "room_closed");
-        }
-    }
+                room
+            };
 
-    mod enter {
-        use chrono::{Duration, Utc};
+            // Allow agent to subscribe to the rooms' events.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "read");
 
-        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+            // Make room.enter request.
+            let mut context = TestContext::new(db, authz);
+            let payload = EnterRequest { id: room.id() };
 
-        use super::{super::*, DynSubRequest};
+            let err = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room entering");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "room_full");
+        }
 
         #[async_std::test]
-        async fn enter_room() {
+        async fn reenter_full_room() {
             let local_deps = LocalDeps::new();
             let postgres = local_deps.run_postgres();
             let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
             let room = {
                 let conn = db
@@ -1018,15 +2341,21 @@ mod test {
                     .get()
                     .expect("Failed to get DB connection");
 
-                // Create room.
-                shared_helpers::insert_room(&conn)
+                let room = shared_helpers::insert_room(&conn);
+
+                let room = db::room::UpdateQuery::new(room.id())
+                    .max_agents(Some(Some(1)))
+                    .execute(&conn)
+                    .expect("Failed to update room");
+
+                // The only slot is taken by the agent itself.
+                shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
+                room
             };
 
             // Allow agent to subscribe to the rooms' events.
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let room_id = room.id().to_string();
-
             authz.allow(agent.account_id(), vec!["rooms", &room_id], "read");
 
             // Make room.enter request.
@@ -1037,21 +2366,49 @@ mod test {
                 .await
                 .expect("Room entrance failed");
 
-            // Assert dynamic subscription request.
-            let (payload, reqp, topic) = find_request::<DynSubRequest>(messages.as_slice());
+            let (_, reqp, _) = find_request::<DynSubRequest>(messages.as_slice());
+            assert_eq!(reqp.method(), "subscription.create");
+        }
 
-            let expected_topic = format!(
-                "agents/{}.{}/api/{}/out/{}",
-                context.config().agent_label,
-                context.config().id,
-                API_VERSION,
-                context.config().broker_id,
-            );
+        #[async_std::test]
+        async fn enter_room_banned() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-            assert_eq!(topic, expected_topic);
-            assert_eq!(reqp.method(), "subscription.create");
-            assert_eq!(payload.subject, agent.agent_id().to_owned());
-            assert_eq!(payload.object, vec!["rooms", &room_id, "events"]);
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                db::room_ban::InsertQuery::new(room.id(), agent.account_id(), moderator.agent_id())
+                    .execute(&conn)
+                    .expect("Failed to ban agent");
+
+                room
+            };
+
+            // The ban applies to all agents of the account.
+            let agent = TestAgent::new("mobile", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "read");
+
+            // Make room.enter request.
+            let mut context = TestContext::new(db, authz);
+            let payload = EnterRequest { id: room.id() };
+
+            let err = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room entering");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "agent_banned");
         }
 
         #[async_std::test]
@@ -1312,4 +2669,127 @@ mod test {
             assert_eq!(err.kind(), "room_not_found");
         }
     }
+
+    mod mute_all {
+        use std::ops::Bound;
+
+        use chrono::Utc;
+
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+        use super::super::*;
+
+        #[async_std::test]
+        async fn mute_all() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let student1 = TestAgent::new("web", "student1", USR_AUDIENCE);
+            let student2 = TestAgent::new("web", "student2", USR_AUDIENCE);
+
+            // Insert an owned RTC room with RTCs of the host and students.
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                    .rtc_sharing_policy(RtcSharingPolicy::Owned)
+                    .insert(&conn);
+
+                for agent in &[&host, &student1, &student2] {
+                    factory::Rtc::new(room.id())
+                        .created_by(agent.agent_id().to_owned())
+                        .insert(&conn);
+                }
+
+                room
+            };
+
+            // Allow the host to update the room.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            authz.allow(host.account_id(), vec!["rooms", &room_id], "update");
+
+            // Make room.mute_all request excluding the host.
+            let mut context = TestContext::new(db, authz);
+
+            let payload = MuteAllRequest {
+                id: room.id(),
+                exclude: vec![host.agent_id().to_owned()],
+            };
+
+            let messages = handle_request::<MuteAllHandler>(&mut context, &host, payload)
+                .await
+                .expect("Room muting failed");
+
+            // Assert response.
+            let (state, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            let configs = state["configs"].as_array().expect("Missing configs");
+            assert_eq!(configs.len(), 2);
+
+            for config in configs {
+                assert_ne!(config["agent_id"], json!(host.agent_id()));
+                assert_eq!(config["send_audio"], json!(false));
+                assert_eq!(config["send_video"], json!(true));
+            }
+
+            // Assert notification.
+            let (_, evp, _) = find_event::<JsonValue>(messages.as_slice());
+            assert_eq!(evp.label(), "agent_writer_config.update");
+
+            // Assert snapshots.
+            let conn = context
+                .get_conn()
+                .await
+                .expect("Failed to get DB connection");
+
+            let snapshots = db::rtc_writer_config_snapshot::ListWithRtcQuery::new(room.id())
+                .execute(&conn)
+                .expect("Failed to list writer config snapshots");
+
+            assert_eq!(snapshots.len(), 2);
+        }
+
+        #[async_std::test]
+        async fn mute_all_not_authorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                    .rtc_sharing_policy(RtcSharingPolicy::Owned)
+                    .insert(&conn)
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let payload = MuteAllRequest {
+                id: room.id(),
+                exclude: vec![],
+            };
+
+            let err = handle_request::<MuteAllHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room muting");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
 }
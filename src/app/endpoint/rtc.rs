@@ -1,10 +1,11 @@
 use anyhow::{anyhow, Context as AnyhowContext};
 use async_std::{stream, task};
 use async_trait::async_trait;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use slog::{o, warn};
-use std::{fmt, ops::Bound};
+use serde_json::json;
+use slog::{error, o, warn};
+use std::{fmt, ops::Bound, result::Result as StdResult};
 use svc_agent::{
     mqtt::{
         IncomingRequestProperties, IntoPublishableMessage, OutgoingResponse, ResponseStatus,
@@ -18,9 +19,16 @@ use crate::{
         context::Context, endpoint, endpoint::prelude::*, handle_id::HandleId,
         metrics::HistogramExt,
     },
-    backend::janus::{client::create_handle::CreateHandleRequest, JANUS_API_VERSION},
-    db::{self, agent, agent_connection, rtc::SharingPolicy as RtcSharingPolicy},
-    diesel::{Connection, Identifiable},
+    backend::janus::{
+        balancer::{self, ChoiceReason},
+        client::{create_handle::CreateHandleRequest, detach::DetachRequest},
+        JANUS_API_VERSION,
+    },
+    db::{
+        self, agent, agent_connection, idempotency_key::Kind as IdempotencyKeyKind,
+        rtc::SharingPolicy as RtcSharingPolicy,
+    },
+    diesel::{pg::PgConnection, Connection, Identifiable},
 };
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -43,6 +51,9 @@ pub type ConnectResponse = OutgoingResponse<ConnectResponseData>;
 #[derive(Debug, Deserialize)]
 pub struct CreateRequest {
     room_id: db::room::Id,
+    /// Retries with the same key return the rtc created by the first request.
+    #[serde(default)]
+    idempotency_key: Option<String>,
 }
 
 pub struct CreateHandler;
@@ -58,8 +69,9 @@ impl RequestHandler for CreateHandler {
         reqp: &IncomingRequestProperties,
     ) -> Result {
         let conn = context.get_conn().await?;
-        let room = task::spawn_blocking(move || {
-            helpers::find_room_by_id(payload.room_id, helpers::RoomTimeRequirement::Open, &conn)
+        let room = task::spawn_blocking({
+            let room_id = payload.room_id;
+            move || helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::Open, &conn)
         })
         .await?;
         helpers::add_room_logger_tags(context, &room);
@@ -73,14 +85,45 @@ impl RequestHandler for CreateHandler {
             .authorize(room.audience(), reqp, object, "create")
             .await?;
 
+        // Respond with the rtc created by the original request if it's a retry.
+        if let Some(ref key) = payload.idempotency_key {
+            let created_after = helpers::idempotency_keys_expired_before(context)?;
+            let conn = context.get_conn().await?;
+
+            let maybe_rtc = task::spawn_blocking({
+                let audience = room.audience().to_owned();
+                let key = key.clone();
+
+                let room_id = room.id();
+
+                move || find_rtc_by_idempotency_key(&audience, room_id, &key, created_after, &conn)
+            })
+            .await?;
+
+            if let Some(rtc) = maybe_rtc {
+                context.add_logger_tags(o!("rtc_id" => rtc.id().to_string()));
+
+                return Ok(Box::new(stream::once(helpers::build_response(
+                    ResponseStatus::CREATED,
+                    rtc,
+                    reqp,
+                    context.start_timestamp(),
+                    Some(authz_time),
+                ))));
+            }
+        }
+
         // Create an rtc.
+        let expired_before = helpers::idempotency_keys_expired_before(context)?;
         let conn = context.get_conn().await?;
         let max_room_duration = context.config().max_room_duration;
         let room_id = room.id();
-        let rtc = task::spawn_blocking({
+        let (rtc, is_created) = task::spawn_blocking({
             let agent_id = reqp.as_agent_id().clone();
+            let idempotency_key = payload.idempotency_key;
+
             move || {
-                conn.transaction::<_, diesel::result::Error, _>(|| {
+                let result = conn.transaction::<_, diesel::result::Error, _>(|| {
                     if let Some(max_room_duration) = max_room_duration {
                         if let (start, Bound::Unbounded) = room.time() {
                             let new_time = (
@@ -94,8 +137,53 @@ impl RequestHandler for CreateHandler {
                         }
                     }
 
-                    db::rtc::InsertQuery::new(room.id(), &agent_id).execute(&conn)
-                })
+                    let rtc = db::rtc::InsertQuery::new(room.id(), &agent_id).execute(&conn)?;
+
+                    // Apply the room's default quality preset to the new writer.
+                    if let (Some(preset), Some(video_remb)) =
+                        (room.quality_preset(), room.video_remb())
+                    {
+                        db::rtc_writer_config::UpsertQuery::new(rtc.id())
+                            .send_video(preset.send_video())
+                            .video_remb(video_remb)
+                            .execute(&conn)?;
+                    }
+
+                    if let Some(ref key) = idempotency_key {
+                        let maybe_key = db::idempotency_key::InsertQuery::new(
+                            room.audience(),
+                            key,
+                            room.id(),
+                            expired_before,
+                        )
+                        .rtc_id(rtc.id())
+                        .execute(&conn)?;
+
+                        if maybe_key.is_none() {
+                            return Err(diesel::result::Error::RollbackTransaction);
+                        }
+                    }
+
+                    Ok(rtc)
+                });
+
+                match (result, idempotency_key) {
+                    (Ok(rtc), _) => Ok((rtc, true)),
+                    // A concurrent request with the same key has stored it first.
+                    (Err(diesel::result::Error::RollbackTransaction), Some(key)) => {
+                        find_rtc_by_idempotency_key(
+                            room.audience(),
+                            room.id(),
+                            &key,
+                            expired_before,
+                            &conn,
+                        )?
+                        .map(|rtc| (rtc, false))
+                        .ok_or_else(|| anyhow!("Rtc for the idempotency key not found"))
+                        .error(AppErrorKind::RtcNotFound)
+                    }
+                    (Err(err), _) => Err(err.into()),
+                }
             }
         })
         .await?;
@@ -111,6 +199,11 @@ impl RequestHandler for CreateHandler {
             Some(authz_time),
         );
 
+        // The concurrent request has already broadcasted the rtc.
+        if !is_created {
+            return Ok(Box::new(stream::once(response)));
+        }
+
         let notification = helpers::build_notification(
             "rtc.create",
             &format!("rooms/{}/events", room_id),
@@ -128,6 +221,30 @@ impl RequestHandler for CreateHandler {
     }
 }
 
+fn find_rtc_by_idempotency_key(
+    audience: &str,
+    room_id: db::room::Id,
+    key: &str,
+    created_after: DateTime<Utc>,
+    conn: &PgConnection,
+) -> StdResult<Option<db::rtc::Object>, AppError> {
+    let maybe_key =
+        db::idempotency_key::FindQuery::new(audience, IdempotencyKeyKind::Rtc, key, created_after)
+            .execute(conn)?;
+
+    match maybe_key {
+        None => Ok(None),
+        Some(key) if key.room_id() != room_id => Err(anyhow!(
+            "Idempotency key is used by a request to another room"
+        ))
+        .error(AppErrorKind::InvalidPayload),
+        Some(key) => match key.rtc_id() {
+            Some(rtc_id) => Ok(db::rtc::FindQuery::new().id(rtc_id).execute(conn)?),
+            None => Ok(None),
+        },
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Deserialize)]
@@ -368,18 +485,34 @@ impl RequestHandler for ConnectHandler {
             .authorize(room.audience(), reqp, object, action)
             .await?;
         context.metrics().observe_auth(authz_time);
+
+        if payload.intent == ConnectIntent::Write {
+            helpers::check_stage(context, &room, reqp).await?;
+
+            // Writers get recorded so they're not allowed to connect when the recording quota
+            // is over.
+            helpers::check_quota(
+                context,
+                room.audience(),
+                helpers::Quota::MonthlyRecordedMinutes,
+            )
+            .await?;
+        }
+
         // Choose backend to connect.
         let group = context.config().janus_group.clone();
+        let strategy_kind = context.config().balancer.strategy(room.audience());
         let conn = context.get_conn().await?;
         let logger = context.logger().clone();
         let room_id = room.id();
-        let backend = task::spawn_blocking(move || {
+        let (backend, rtc_writer_configs_with_rtcs) = task::spawn_blocking(move || {
             // There are 3 cases:
             // 1. Connecting as writer for the first time. There's no `backend_id` in that case.
-            //    Select the most loaded backend that is capable to host the room's reservation.
-            //    If there's no capable backend then select the least loaded and send a warning
-            //    to Sentry. If there are no backends at all then return `no available backends`
-            //    error and also send it to Sentry.
+            //    Select a backend that is capable to host the room's reservation with the
+            //    balancing strategy configured for the room's audience (the most loaded one
+            //    by default). If there's no capable backend then select the least loaded and
+            //    send a warning to Sentry. If there are no backends at all then return
+            //    `no available backends` error and also send it to Sentry.
             // 2. Connecting as reader with existing `backend_id`. Choose it because Janus doesn't
             //    support clustering and it must be the same server that the writer is connected to.
             // 3. Reconnecting as writer with existing `backend_id`. Select it to avoid partitioning
@@ -390,37 +523,45 @@ impl RequestHandler for ConnectHandler {
                     .execute(&conn)?
                     .ok_or_else(|| anyhow!("No backend found for stream"))
                     .error(AppErrorKind::BackendNotFound)?,
-                None => match db::janus_backend::most_loaded(room.id(), group.as_deref(), &conn)? {
-                    Some(backend) => backend,
-                    None => db::janus_backend::least_loaded(room.id(), group.as_deref(), &conn)?
-                        .map(|backend| {
-                            use sentry::protocol::{value::Value, Event, Level};
-                            let backend_id = backend.id().to_string();
-
-                            warn!(logger, "No capable backends to host the reserve; falling back to the least loaded backend: room_id = {}, rtc_id = {}, backend_id = {}", room_id, rtc_id, backend_id);
-
-                            let mut extra = std::collections::BTreeMap::new();
-                            extra.insert(String::from("room_id"), Value::from(room_id.to_string()));
-                            extra.insert(String::from("rtc_id"), Value::from(rtc_id));
-                            extra.insert(String::from("backend_id"), Value::from(backend_id));
-
-                            if let Some(reserve) = room.reserve() {
-                                extra.insert(String::from("reserve"), Value::from(reserve));
-                            }
-
-
-                            sentry::capture_event(Event {
-                                message: Some(String::from("No capable backends to host the reserve; falling back to the least loaded backend")),
-                                level: Level::Warning,
-                                extra,
-                                ..Default::default()
-                            });
-
-                            backend
-                        })
+                None => {
+                    let backends = db::janus_backend::balancing_snapshot(group.as_deref(), &conn)?;
+                    let strategy = balancer::strategy(strategy_kind);
+
+                    let choice = strategy
+                        .choose(&backends, room.balancing_reserve())
                         .ok_or_else(|| anyhow!("No available backends"))
-                        .error(AppErrorKind::NoAvailableBackends)?,
-                },
+                        .error(AppErrorKind::NoAvailableBackends)?;
+
+                    if choice.reason == ChoiceReason::LeastLoadedFallback {
+                        use sentry::protocol::{value::Value, Event, Level};
+                        let backend_id = choice.backend.id.to_string();
+
+                        warn!(logger, "No capable backends to host the reserve; falling back to the least loaded backend: room_id = {}, rtc_id = {}, backend_id = {}", room_id, rtc_id, backend_id);
+
+                        let mut extra = std::collections::BTreeMap::new();
+                        extra.insert(String::from("room_id"), Value::from(room_id.to_string()));
+                        extra.insert(String::from("rtc_id"), Value::from(rtc_id));
+                        extra.insert(String::from("backend_id"), Value::from(backend_id));
+
+                        if let Some(reserve) = room.reserve() {
+                            extra.insert(String::from("reserve"), Value::from(reserve));
+                        }
+
+
+                        sentry::capture_event(Event {
+                            message: Some(String::from("No capable backends to host the reserve; falling back to the least loaded backend")),
+                            level: Level::Warning,
+                            extra,
+                            ..Default::default()
+                        });
+                    }
+
+                    db::janus_backend::FindQuery::new()
+                        .id(&choice.backend.id)
+                        .execute(&conn)?
+                        .ok_or_else(|| anyhow!("Chosen backend has gone"))
+                        .error(AppErrorKind::BackendNotFound)?
+                }
             };
 
             // Create recording if a writer connects for the first time.
@@ -452,9 +593,38 @@ impl RequestHandler for ConnectHandler {
                 .error(AppErrorKind::CapacityExceeded);
             }
 
-            Ok::<_, AppError>(backend)
+            // Writer configs of rooms with a quality preset may have been set before the room
+            // got its backend so the backend has to be informed about them.
+            let rtc_writer_configs_with_rtcs =
+                if payload.intent == ConnectIntent::Write && room.quality_preset().is_some() {
+                    db::rtc_writer_config::ListWithRtcQuery::new(room.id()).execute(&conn)?
+                } else {
+                    vec![]
+                };
+
+            Ok::<_, AppError>((backend, rtc_writer_configs_with_rtcs))
         }).await?;
 
+        // The handle is already bound to the backend so the connection doesn't fail because of it.
+        if !rtc_writer_configs_with_rtcs.is_empty() {
+            if let Err(app_error) = endpoint::agent_writer_config::update_backend(
+                context,
+                &backend,
+                &rtc_writer_configs_with_rtcs,
+            )
+            .await
+            {
+                error!(
+                    context.logger(),
+                    "Failed to update writer configs on backend {}: {:?}",
+                    backend.id(),
+                    app_error
+                );
+
+                app_error.notify_sentry(context.logger());
+            }
+        }
+
         context.add_logger_tags(o!("backend_id" => backend.id().to_string()));
         let rtc_stream_id = db::janus_rtc_stream::Id::random();
 
@@ -529,6 +699,125 @@ impl RequestHandler for ConnectHandler {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Deserialize)]
+pub struct UnpublishRequest {
+    id: db::rtc::Id,
+}
+
+pub struct UnpublishHandler;
+
+#[async_trait]
+impl RequestHandler for UnpublishHandler {
+    type Payload = UnpublishRequest;
+    const ERROR_TITLE: &'static str = "Failed to unpublish rtc";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let conn = context.get_conn().await?;
+        let room = task::spawn_blocking({
+            let payload_id = payload.id;
+            move || {
+                helpers::find_room_by_rtc_id(payload_id, helpers::RoomTimeRequirement::Open, &conn)
+            }
+        })
+        .await?;
+        helpers::add_room_logger_tags(context, &room);
+
+        // Authorize room updating on the tenant since it's a moderator's action.
+        let room_id = room.id().to_string();
+        let object = vec!["rooms", &room_id];
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience(), reqp, object, "update")
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Find the publisher's stream and the backend it's on.
+        let conn = context.get_conn().await?;
+        let (rtc_stream, backend) = task::spawn_blocking(move || {
+            let rtc_stream = db::janus_rtc_stream::ListQuery::new()
+                .rtc_id(payload.id)
+                .active(true)
+                .limit(1)
+                .execute(&conn)?
+                .pop()
+                .ok_or_else(|| anyhow!("The RTC has no active stream"))
+                .error(AppErrorKind::RtcStreamNotFound)?;
+
+            let backend = db::janus_backend::FindQuery::new()
+                .id(rtc_stream.backend_id())
+                .execute(&conn)?
+                .ok_or_else(|| anyhow!("Backend not found"))
+                .error(AppErrorKind::BackendNotFound)?;
+
+            Ok::<_, AppError>((rtc_stream, backend))
+        })
+        .await?;
+
+        // Detach the publisher's handle so it can't send media anymore.
+        let request = DetachRequest {
+            session_id: backend.session_id(),
+            handle_id: rtc_stream.handle_id(),
+        };
+
+        context
+            .janus_clients()
+            .get_or_insert(&backend)
+            .error(AppErrorKind::BackendClientCreationFailed)?
+            .detach(request)
+            .await
+            .error(AppErrorKind::BackendRequestFailed)?;
+
+        // Stop the stream and disconnect its readers without waiting for Janus events.
+        let conn = context.get_conn().await?;
+        let maybe_stopped_stream = task::spawn_blocking({
+            let rtc_stream_id = rtc_stream.id();
+            let rtc_id = rtc_stream.rtc_id();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    let maybe_stopped_stream = db::janus_rtc_stream::stop(rtc_stream_id, &conn)?;
+                    agent_connection::BulkDisconnectByRtcQuery::new(rtc_id).execute(&conn)?;
+                    Ok(maybe_stopped_stream)
+                })
+            }
+        })
+        .await?;
+
+        let mut messages = vec![helpers::build_response(
+            ResponseStatus::OK,
+            json!({}),
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        )];
+
+        if let Some(stopped_stream) = maybe_stopped_stream {
+            let event = endpoint::rtc_stream::update_event(
+                room.id(),
+                stopped_stream,
+                context.start_timestamp(),
+            )?;
+
+            messages.push(Box::new(event) as Box<dyn IntoPublishableMessage + Send>);
+        }
+
+        context
+            .metrics()
+            .request_duration
+            .rtc_unpublish
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::from_iter(messages)))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 #[cfg(test)]
 mod test {
     mod create {
@@ -565,7 +854,10 @@ mod test {
 
             // Make rtc.create request.
             let mut context = TestContext::new(db, authz);
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -583,6 +875,64 @@ mod test {
             assert_eq!(rtc.room_id(), room.id());
         }
 
+        #[async_std::test]
+        async fn create_in_room_with_quality_preset() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+
+            // Insert an audio only room.
+            let room = db
+                .connection_pool()
+                .get()
+                .map(|conn| {
+                    factory::Room::new()
+                        .audience(USR_AUDIENCE)
+                        .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                        .rtc_sharing_policy(RtcSharingPolicy::Owned)
+                        .quality_preset(db::room::QualityPreset::AudioOnly, 64_000)
+                        .insert(&conn)
+                })
+                .unwrap();
+
+            // Allow user to create rtcs in the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let room_id = room.id().to_string();
+            let object = vec!["rooms", &room_id, "rtcs"];
+            authz.allow(agent.account_id(), object, "create");
+
+            // Make rtc.create request.
+            let mut context = TestContext::new(db.clone(), authz);
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
+
+            let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Rtc creation failed");
+
+            let (rtc, respp, _) = find_response::<Rtc>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::CREATED);
+
+            // Assert the room's preset is applied to the writer config.
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let configs = db::rtc_writer_config::ListWithRtcQuery::new(room.id())
+                .execute(&conn)
+                .expect("Failed to list writer configs");
+
+            assert_eq!(configs.len(), 1);
+            let (config, config_rtc) = &configs[0];
+            assert_eq!(config_rtc.id(), rtc.id());
+            assert!(!config.send_video());
+            assert_eq!(config.video_remb(), Some(64_000));
+        }
+
         #[async_std::test]
         async fn create_in_unbounded_room() {
             let local_deps = LocalDeps::new();
@@ -614,7 +964,10 @@ mod test {
 
             // Make rtc.create request.
             let mut context = TestContext::new(db, authz);
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -646,6 +999,7 @@ mod test {
             let mut context = TestContext::new(db, TestAuthz::new());
             let payload = CreateRequest {
                 room_id: db::room::Id::random(),
+                idempotency_key: None,
             };
 
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
@@ -678,7 +1032,10 @@ mod test {
 
             // Make rtc.create request.
             let mut context = TestContext::new(db, authz);
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -690,7 +1047,10 @@ mod test {
             assert_eq!(rtc.room_id(), room.id());
 
             // Make rtc.create request second time.
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
                 .expect_err("Unexpected success on rtc creation");
@@ -700,6 +1060,109 @@ mod test {
             assert_eq!(err.kind(), "database_query_failed");
         }
 
+        #[async_std::test]
+        async fn create_rtc_retry_with_idempotency_key() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+
+            // Insert a room.
+            let room = db
+                .connection_pool()
+                .get()
+                .map(|conn| shared_helpers::insert_room(&conn))
+                .unwrap();
+
+            // Allow user to create rtcs in the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let room_id = room.id().to_string();
+            let object = vec!["rooms", &room_id, "rtcs"];
+            authz.allow(agent.account_id(), object, "create");
+
+            // Make rtc.create request.
+            let mut context = TestContext::new(db, authz);
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: Some("lms-retry-key".to_owned()),
+            };
+
+            let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Rtc creation failed");
+
+            let (rtc, _, _) = find_response::<Rtc>(messages.as_slice());
+
+            // Retry it with the same key.
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: Some("lms-retry-key".to_owned()),
+            };
+
+            let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Rtc creation retry failed");
+
+            // Assert the retry responds with the same rtc without a notification.
+            assert_eq!(messages.len(), 1);
+            let (retried_rtc, respp, _) = find_response::<Rtc>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::CREATED);
+            assert_eq!(retried_rtc.id(), rtc.id());
+        }
+
+        #[async_std::test]
+        async fn create_rtc_retry_with_idempotency_key_in_another_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+
+            // Insert rooms.
+            let (room, other_room) = db
+                .connection_pool()
+                .get()
+                .map(|conn| {
+                    (
+                        shared_helpers::insert_room(&conn),
+                        shared_helpers::insert_room(&conn),
+                    )
+                })
+                .unwrap();
+
+            // Allow user to create rtcs in both rooms.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            for room in &[&room, &other_room] {
+                let room_id = room.id().to_string();
+                let object = vec!["rooms", &room_id, "rtcs"];
+                authz.allow(agent.account_id(), object, "create");
+            }
+
+            // Make rtc.create request.
+            let mut context = TestContext::new(db, authz);
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: Some("lms-retry-key".to_owned()),
+            };
+
+            handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Rtc creation failed");
+
+            // Reuse the key in another room.
+            let payload = CreateRequest {
+                room_id: other_room.id(),
+                idempotency_key: Some("lms-retry-key".to_owned()),
+            };
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rtc creation");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+
         #[async_std::test]
         async fn create_rtc_for_different_agents_with_owned_sharing_policy() {
             let local_deps = LocalDeps::new();
@@ -724,13 +1187,19 @@ mod test {
 
             // Make two rtc.create requests.
             let mut context = TestContext::new(db, authz);
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
 
             let messages1 = handle_request::<CreateHandler>(&mut context, &agent1, payload)
                 .await
                 .expect("RTC creation failed");
 
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
 
             let messages2 = handle_request::<CreateHandler>(&mut context, &agent2, payload)
                 .await
@@ -770,7 +1239,10 @@ mod test {
 
             // Make the first rtc.create request.
             let mut context = TestContext::new(db, authz);
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -783,7 +1255,10 @@ mod test {
             assert_eq!(rtc.created_by(), agent.agent_id());
 
             // Make the second rtc.create request and expect fail.
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
 
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -810,7 +1285,10 @@ mod test {
 
             // Make rtc.create request.
             let mut context = TestContext::new(db, TestAuthz::new());
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                idempotency_key: None,
+            };
 
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -2276,6 +2754,98 @@ mod test {
             assert_eq!(err.kind(), "access_denied");
         }
 
+        #[async_std::test]
+        async fn connect_to_rtc_as_writer_not_on_stage() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let rtc = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+
+                db::room::UpdateQuery::new(room.id())
+                    .stage_mode(Some(true))
+                    .execute(&conn)
+                    .expect("Failed to enable stage mode");
+
+                shared_helpers::insert_rtc_with_room(&conn, &room)
+            };
+
+            // Allow agent to update the RTC but not to moderate the room.
+            let mut authz = TestAuthz::new();
+            let room_id = rtc.room_id().to_string();
+            let rtc_id = rtc.id().to_string();
+            let object = vec!["rooms", &room_id, "rtcs", &rtc_id];
+            authz.allow(agent.account_id(), object, "update");
+
+            let mut context = TestContext::new(db, authz);
+
+            let payload = ConnectRequest {
+                id: rtc.id(),
+                intent: ConnectIntent::Write,
+            };
+
+            let err = handle_request::<ConnectHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rtc connecting");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "not_on_stage");
+        }
+
+        #[async_std::test]
+        async fn connect_to_rtc_as_writer_quota_exceeded() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let rtc = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_rtc(&conn)
+            };
+
+            // Allow agent to update the RTC.
+            let mut authz = TestAuthz::new();
+            let room_id = rtc.room_id().to_string();
+            let rtc_id = rtc.id().to_string();
+            let object = vec!["rooms", &room_id, "rtcs", &rtc_id];
+            authz.allow(agent.account_id(), object, "update");
+
+            let mut context = TestContext::new(db, authz);
+
+            // The audience is not allowed to record anything.
+            context.config_mut().quotas.insert(
+                USR_AUDIENCE.to_owned(),
+                crate::config::QuotaConfig {
+                    max_monthly_recorded_minutes: Some(0.0),
+                    ..Default::default()
+                },
+            );
+
+            let payload = ConnectRequest {
+                id: rtc.id(),
+                intent: ConnectIntent::Write,
+            };
+
+            let err = handle_request::<ConnectHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rtc connecting");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "quota_exceeded");
+        }
+
         #[async_std::test]
         async fn connect_to_rtc_missing() {
             let local_deps = LocalDeps::new();
@@ -2298,4 +2868,146 @@ mod test {
             assert_eq!(err.kind(), "room_not_found");
         }
     }
+
+    mod unpublish {
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+        use super::super::*;
+
+        #[async_std::test]
+        async fn unpublish_rtc() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let janus = local_deps.run_janus();
+            let db = TestDb::with_local_postgres(&postgres);
+            let (session_id, handle_id) = shared_helpers::init_janus(&janus.url).await;
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            // Insert a room with an rtc being published.
+            let (backend, rtc, rtc_stream) = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let backend =
+                    shared_helpers::insert_janus_backend(&conn, &janus.url, session_id, handle_id);
+
+                let room = shared_helpers::insert_room_with_backend_id(&conn, backend.id());
+                let rtc = shared_helpers::insert_rtc_with_room(&conn, &room);
+
+                let rtc_stream = factory::JanusRtcStream::new(USR_AUDIENCE)
+                    .backend(&backend)
+                    .rtc(&rtc)
+                    .insert(&conn);
+
+                db::janus_rtc_stream::start(rtc_stream.id(), &conn)
+                    .expect("Failed to start rtc stream");
+
+                (backend, rtc, rtc_stream)
+            };
+
+            // Allow agent to update the room.
+            let mut authz = TestAuthz::new();
+            let room_id = rtc.room_id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "update");
+
+            // Make rtc.unpublish request.
+            let mut context = TestContext::new(db, authz);
+            let (tx, _rx) = crossbeam_channel::unbounded();
+            context.with_janus(tx);
+            let payload = UnpublishRequest { id: rtc.id() };
+
+            let messages = handle_request::<UnpublishHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Rtc unpublishing failed");
+
+            context.janus_clients().remove_client(backend.id());
+
+            // Assert response.
+            let (_, respp, _) = find_response::<serde_json::Value>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            // Assert notification.
+            let (payload, evp, _) = find_event::<serde_json::Value>(messages.as_slice());
+            assert_eq!(evp.label(), "rtc_stream.update");
+            assert_eq!(payload["id"], rtc_stream.id().to_string());
+
+            // Assert the stream is stopped.
+            let conn = context
+                .get_conn()
+                .await
+                .expect("Failed to get DB connection");
+
+            let active_streams = db::janus_rtc_stream::ListQuery::new()
+                .rtc_id(rtc.id())
+                .active(true)
+                .execute(&conn)
+                .expect("Failed to list rtc streams");
+
+            assert!(active_streams.is_empty());
+        }
+
+        #[async_std::test]
+        async fn unpublish_rtc_without_stream() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let rtc = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_rtc_with_room(&conn, &room)
+            };
+
+            // Allow agent to update the room.
+            let mut authz = TestAuthz::new();
+            let room_id = rtc.room_id().to_string();
+            authz.allow(agent.account_id(), vec!["rooms", &room_id], "update");
+
+            // Make rtc.unpublish request.
+            let mut context = TestContext::new(db, authz);
+            let payload = UnpublishRequest { id: rtc.id() };
+
+            let err = handle_request::<UnpublishHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rtc unpublishing");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "rtc_stream_not_found");
+        }
+
+        #[async_std::test]
+        async fn unpublish_rtc_not_authorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let rtc = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_rtc_with_room(&conn, &room)
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+            let payload = UnpublishRequest { id: rtc.id() };
+
+            let err = handle_request::<UnpublishHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rtc unpublishing");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
 }
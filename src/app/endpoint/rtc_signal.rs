@@ -189,6 +189,15 @@ impl RequestHandler for CreateHandler {
                             let _authz_time =
                                 authorize(context, &payload, reqp, "update", &room).await?;
 
+                            helpers::check_stage(context, &room, reqp).await?;
+
+                            helpers::check_quota(
+                                context,
+                                room.audience(),
+                                helpers::Quota::ActiveStreams,
+                            )
+                            .await?;
+
                             // Updating the Real-Time Connection state
                             let label = payload
                                 .label
@@ -346,8 +355,14 @@ mod test {
             backend::janus::client::{
                 events::EventResponse, transactions::Transaction, IncomingEvent, SessionId,
             },
-            db::rtc::SharingPolicy as RtcSharingPolicy,
-            test_helpers::{prelude::*, test_deps::LocalDeps},
+            db::{
+                agent_connection::Object as AgentConnection,
+                janus_backend::Object as JanusBackend,
+                rtc::{Object as Rtc, SharingPolicy as RtcSharingPolicy},
+            },
+            test_helpers::{
+                fake_janus::FakeJanusFailure, parse_messages, prelude::*, test_deps::LocalDeps,
+            },
         };
 
         use super::super::*;
@@ -398,66 +413,62 @@ a=rtcp-fb:120 ccm fir
 a=extmap:2 urn:ietf:params:rtp-hdrext:sdes:mid
 "#;
 
-        #[async_std::test]
-        async fn offer() -> std::io::Result<()> {
-            let local_deps = LocalDeps::new();
-            let postgres = local_deps.run_postgres();
-            let janus = local_deps.run_janus();
-            let db = TestDb::with_local_postgres(&postgres);
-            let (session_id, handle_id) = shared_helpers::init_janus(&janus.url).await;
-            let user_handle = shared_helpers::create_handle(&janus.url, session_id).await;
+        // Allows the agent to perform the action on the rtc.
+        fn allow_rtc(agent: &TestAgent, rtc: &Rtc, action: &str) -> TestAuthz {
             let mut authz = TestAuthz::new();
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
-
-            // Insert room with backend and rtc and an agent connection.
-            let (backend, rtc, agent_connection) = db
-                .connection_pool()
-                .get()
-                .map(|conn| {
-                    let backend = shared_helpers::insert_janus_backend(
-                        &conn, &janus.url, session_id, handle_id,
-                    );
-                    let room = shared_helpers::insert_room_with_backend_id(&conn, backend.id());
-                    let rtc = shared_helpers::insert_rtc_with_room(&conn, &room);
-
-                    let (_, agent_connection) = shared_helpers::insert_connected_to_handle_agent(
-                        &conn,
-                        agent.agent_id(),
-                        rtc.room_id(),
-                        rtc.id(),
-                        user_handle,
-                    );
-                    (backend, rtc, agent_connection)
-                })
-                .unwrap();
-            // Allow user to update the rtc.
             let room_id = rtc.room_id().to_string();
             let rtc_id = rtc.id().to_string();
             let object = vec!["rooms", &room_id, "rtcs", &rtc_id];
-            authz.allow(agent.account_id(), object.clone(), "update");
+            authz.allow(agent.account_id(), object, action);
+            authz
+        }
 
-            // Make rtc_signal.create request.
-            let mut context = TestContext::new(db, authz);
-            let (tx, rx) = crossbeam_channel::unbounded();
-            context.with_janus(tx);
-            let rtc_stream_id = db::janus_rtc_stream::Id::random();
-            let handle_id = HandleId::new(
+        fn build_handle_id(
+            backend: &JanusBackend,
+            rtc: &Rtc,
+            agent_connection: &AgentConnection,
+            rtc_stream_id: db::janus_rtc_stream::Id,
+        ) -> HandleId {
+            HandleId::new(
                 rtc_stream_id,
                 rtc.id(),
                 agent_connection.handle_id(),
                 backend.session_id(),
                 backend.id().to_owned(),
-            );
+            )
+        }
+
+        fn build_offer(handle_id: HandleId) -> CreateRequest {
             let jsep = serde_json::from_value::<Jsep>(json!({ "type": "offer", "sdp": SDP_OFFER }))
                 .expect("Failed to build JSEP");
 
-            let payload = CreateRequest {
-                handle_id: handle_id.clone(),
+            CreateRequest {
+                handle_id,
                 jsep,
                 label: Some(String::from("whatever")),
-            };
+            }
+        }
+
+        #[async_std::test]
+        async fn offer() -> std::io::Result<()> {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let janus = local_deps.run_janus();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-            handle_request::<CreateHandler>(&mut context, &agent, payload)
+            let (backend, rtc, agent_connection) =
+                shared_helpers::insert_connected_to_janus_agent(&db, &janus.url, agent.agent_id())
+                    .await;
+
+            // Make rtc_signal.create request.
+            let mut context = TestContext::new(db, allow_rtc(&agent, &rtc, "update"));
+            let (tx, rx) = crossbeam_channel::unbounded();
+            context.with_janus(tx);
+            let rtc_stream_id = db::janus_rtc_stream::Id::random();
+            let handle_id = build_handle_id(&backend, &rtc, &agent_connection, rtc_stream_id);
+
+            handle_request::<CreateHandler>(&mut context, &agent, build_offer(handle_id.clone()))
                 .await
                 .expect("Rtc signal creation failed");
             context.janus_clients().remove_client(agent.agent_id());
@@ -468,7 +479,7 @@ a=extmap:2 urn:ietf:params:rtp-hdrext:sdes:mid
                     session_id: s_id,
                     plugindata: _,
                 }) => {
-                    assert_eq!(session_id, s_id);
+                    assert_eq!(backend.session_id(), s_id);
                 }
                 _ => {
                     panic!("Got wrong event")
@@ -488,6 +499,122 @@ a=extmap:2 urn:ietf:params:rtp-hdrext:sdes:mid
             Ok(())
         }
 
+        #[async_std::test]
+        async fn offer_backend_failure() -> std::io::Result<()> {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let janus = local_deps.run_janus();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let (backend, rtc, agent_connection) =
+                shared_helpers::insert_connected_to_janus_agent(&db, &janus.url, agent.agent_id())
+                    .await;
+
+            // Make Janus fail stream creation.
+            janus.fail_next("stream.create", FakeJanusFailure::Error);
+
+            // Make rtc_signal.create request.
+            let mut context = TestContext::new(db, allow_rtc(&agent, &rtc, "update"));
+            let (tx, _rx) = crossbeam_channel::unbounded();
+            context.with_janus(tx);
+            let rtc_stream_id = db::janus_rtc_stream::Id::random();
+            let handle_id = build_handle_id(&backend, &rtc, &agent_connection, rtc_stream_id);
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, build_offer(handle_id))
+                .await
+                .expect_err("Unexpected success on rtc signal creation");
+
+            context.janus_clients().remove_client(backend.id());
+            assert_eq!(err.status(), ResponseStatus::FAILED_DEPENDENCY);
+            assert_eq!(err.kind(), "backend_request_failed");
+            Ok(())
+        }
+
+        #[async_std::test]
+        async fn offer_quota_exceeded() -> std::io::Result<()> {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let janus = local_deps.run_janus();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let (backend, rtc, agent_connection) =
+                shared_helpers::insert_connected_to_janus_agent(&db, &janus.url, agent.agent_id())
+                    .await;
+
+            let mut context = TestContext::new(db, allow_rtc(&agent, &rtc, "update"));
+
+            // The audience is not allowed to publish anything.
+            context.config_mut().quotas.insert(
+                USR_AUDIENCE.to_owned(),
+                crate::config::QuotaConfig {
+                    max_active_streams: Some(0),
+                    ..Default::default()
+                },
+            );
+
+            // Make rtc_signal.create request.
+            let rtc_stream_id = db::janus_rtc_stream::Id::random();
+            let handle_id = build_handle_id(&backend, &rtc, &agent_connection, rtc_stream_id);
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, build_offer(handle_id))
+                .await
+                .expect_err("Unexpected success on rtc signal creation");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "quota_exceeded");
+
+            // Assert no rtc stream has been created.
+            let conn = context.get_conn().await.unwrap();
+            let query = crate::schema::janus_rtc_stream::table.find(rtc_stream_id);
+            let maybe_rtc_stream: Option<crate::db::janus_rtc_stream::Object> =
+                query.get_result(&conn).optional().unwrap();
+
+            assert!(maybe_rtc_stream.is_none());
+            Ok(())
+        }
+
+        #[async_std::test]
+        async fn offer_error_status() -> std::io::Result<()> {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let janus = local_deps.run_janus();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let (backend, rtc, agent_connection) =
+                shared_helpers::insert_connected_to_janus_agent(&db, &janus.url, agent.agent_id())
+                    .await;
+
+            // Make the plugin respond to stream creation with an error status.
+            janus.fail_next("stream.create", FakeJanusFailure::ErrorStatus);
+
+            // Make rtc_signal.create request.
+            let mut context = TestContext::new(db, allow_rtc(&agent, &rtc, "update"));
+            let (tx, rx) = crossbeam_channel::unbounded();
+            context.with_janus(tx);
+            let rtc_stream_id = db::janus_rtc_stream::Id::random();
+            let handle_id = build_handle_id(&backend, &rtc, &agent_connection, rtc_stream_id);
+
+            handle_request::<CreateHandler>(&mut context, &agent, build_offer(handle_id))
+                .await
+                .expect("Rtc signal creation failed");
+
+            // The error comes asynchronously with the plugin event.
+            let event = rx.recv().unwrap();
+            context.janus_clients().remove_client(backend.id());
+            assert!(matches!(event, IncomingEvent::Event(_)));
+
+            let messages =
+                parse_messages(crate::backend::janus::handle_event(&mut context, event).await)
+                    .await;
+
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::FAILED_DEPENDENCY);
+            Ok(())
+        }
+
         #[async_std::test]
         async fn offer_unauthorized() -> std::io::Result<()> {
             let local_deps = LocalDeps::new();
@@ -667,51 +794,25 @@ a=rtcp-fb:120 ccm fir
             let postgres = local_deps.run_postgres();
             let janus = local_deps.run_janus();
             let db = TestDb::with_local_postgres(&postgres);
-            let (session_id, handle_id) = shared_helpers::init_janus(&janus.url).await;
-            let user_handle = shared_helpers::create_handle(&janus.url, session_id).await;
-            let mut authz = TestAuthz::new();
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-            // Insert room with backend and rtc and an agent connection.
-            let (backend, rtc, agent_connection) = db
-                .connection_pool()
-                .get()
-                .map(|conn| {
-                    let backend = shared_helpers::insert_janus_backend(
-                        &conn, &janus.url, session_id, handle_id,
-                    );
-                    let room = shared_helpers::insert_room_with_backend_id(&conn, backend.id());
-                    let rtc = shared_helpers::insert_rtc_with_room(&conn, &room);
-
-                    let (_, agent_connection) = shared_helpers::insert_connected_to_handle_agent(
-                        &conn,
-                        agent.agent_id(),
-                        rtc.room_id(),
-                        rtc.id(),
-                        user_handle,
-                    );
-
-                    (backend, rtc, agent_connection)
-                })
-                .unwrap();
+            let (backend, rtc, agent_connection) =
+                shared_helpers::insert_connected_to_janus_agent(&db, &janus.url, agent.agent_id())
+                    .await;
 
             // Allow user to read the rtc.
-            let room_id = rtc.room_id().to_string();
-            let rtc_id = rtc.id().to_string();
-            let object = vec!["rooms", &room_id, "rtcs", &rtc_id];
-            authz.allow(agent.account_id(), object, "read");
+            let authz = allow_rtc(&agent, &rtc, "read");
 
             // Make rtc_signal.create request.
             let mut context = TestContext::new(db, authz);
             let (tx, _) = crossbeam_channel::unbounded();
             context.with_janus(tx);
 
-            let handle_id = HandleId::new(
+            let handle_id = build_handle_id(
+                &backend,
+                &rtc,
+                &agent_connection,
                 db::janus_rtc_stream::Id::random(),
-                rtc.id(),
-                agent_connection.handle_id(),
-                backend.session_id(),
-                backend.id().to_owned(),
             );
 
             let jsep = serde_json::from_value::<Jsep>(json!({
@@ -2,7 +2,7 @@ use anyhow::anyhow;
 use async_std::{stream, task};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use slog::o;
 use std::result::Result as StdResult;
 use svc_agent::mqtt::{
@@ -125,6 +125,69 @@ pub fn update_event(
 
 ////////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityEventKind {
+    SlowLink,
+    Media,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QualityEventData {
+    id: db::janus_rtc_stream::Id,
+    rtc_id: db::rtc::Id,
+    kind: QualityEventKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uplink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    media: Option<String>,
+    slow_link_count: i32,
+    media_loss_count: i32,
+}
+
+impl QualityEventData {
+    pub fn slow_link(object: &db::janus_rtc_stream::Object, uplink: bool) -> Self {
+        Self {
+            uplink: Some(uplink),
+            ..Self::new(object, QualityEventKind::SlowLink)
+        }
+    }
+
+    pub fn media(object: &db::janus_rtc_stream::Object, media: &str) -> Self {
+        Self {
+            media: Some(media.to_owned()),
+            ..Self::new(object, QualityEventKind::Media)
+        }
+    }
+
+    fn new(object: &db::janus_rtc_stream::Object, kind: QualityEventKind) -> Self {
+        Self {
+            id: object.id(),
+            rtc_id: object.rtc_id(),
+            kind,
+            uplink: None,
+            media: None,
+            slow_link_count: object.slow_link_count(),
+            media_loss_count: object.media_loss_count(),
+        }
+    }
+}
+
+pub type QualityEvent = OutgoingMessage<QualityEventData>;
+
+pub fn quality_event(
+    room_id: db::room::Id,
+    data: QualityEventData,
+    start_timestamp: DateTime<Utc>,
+) -> QualityEvent {
+    let uri = format!("rooms/{}/events", room_id);
+    let timing = ShortTermTimingProperties::until_now(start_timestamp);
+    let props = OutgoingEventProperties::new("rtc_stream.quality", timing);
+    OutgoingEvent::broadcast(data, props, &uri)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 #[cfg(test)]
 mod test {
     mod list {
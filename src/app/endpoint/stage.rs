@@ -0,0 +1,607 @@
+use std::result::Result as StdResult;
+
+use anyhow::anyhow;
+use async_std::{stream, task};
+use async_trait::async_trait;
+use chrono::Duration;
+use diesel::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use slog::warn;
+use svc_agent::{
+    mqtt::{IncomingRequestProperties, ResponseStatus},
+    Addressable, AgentId,
+};
+
+use crate::{
+    app::{
+        context::Context, endpoint::prelude::*, message_handler::MessageStream,
+        metrics::HistogramExt,
+    },
+    backend::janus::client::hangup::HangupRequest,
+    db,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StageEvent {
+    room_id: db::room::Id,
+    agent_id: AgentId,
+}
+
+impl StageEvent {
+    pub fn new(room_id: db::room::Id, agent_id: AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct InviteRequest {
+    room_id: db::room::Id,
+    agent_id: AgentId,
+}
+
+pub struct InviteHandler;
+
+#[async_trait]
+impl RequestHandler for InviteHandler {
+    type Payload = InviteRequest;
+    const ERROR_TITLE: &'static str = "Failed to invite agent to stage";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let room = find_room(context, payload.room_id).await?;
+        let authz_time = authorize(context, &room, &payload.agent_id, reqp).await?;
+
+        // Invite the agent if it's online in the room.
+        let conn = context.get_conn().await?;
+        task::spawn_blocking({
+            let room = room.clone();
+            let agent_id = payload.agent_id.clone();
+            let invited_by = reqp.as_agent_id().to_owned();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    helpers::check_room_presence(&room, &agent_id, &conn)?;
+
+                    db::stage_member::InsertQuery::new(room.id(), &agent_id, &invited_by)
+                        .execute(&conn)?;
+
+                    Ok(())
+                })
+            }
+        })
+        .await?;
+
+        let messages = build_messages(
+            context,
+            "stage.invite",
+            &room,
+            payload.agent_id,
+            reqp,
+            Some(authz_time),
+        );
+
+        context
+            .metrics()
+            .request_duration
+            .stage_invite
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(messages)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptRequest {
+    room_id: db::room::Id,
+}
+
+pub struct AcceptHandler;
+
+#[async_trait]
+impl RequestHandler for AcceptHandler {
+    type Payload = AcceptRequest;
+    const ERROR_TITLE: &'static str = "Failed to accept stage invitation";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let room = find_room(context, payload.room_id).await?;
+
+        // The invitation is the authorization so there's no authz request.
+        let conn = context.get_conn().await?;
+        task::spawn_blocking({
+            let room = room.clone();
+            let agent_id = reqp.as_agent_id().to_owned();
+
+            move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    helpers::check_room_presence(&room, &agent_id, &conn)?;
+
+                    db::stage_member::AcceptQuery::new(room.id(), &agent_id)
+                        .execute(&conn)?
+                        .ok_or_else(|| anyhow!("Agent is not invited to the stage"))
+                        .error(AppErrorKind::StageInvitationNotFound)?;
+
+                    Ok(())
+                })
+            }
+        })
+        .await?;
+
+        let agent_id = reqp.as_agent_id().to_owned();
+        let messages = build_messages(context, "stage.accept", &room, agent_id, reqp, None);
+
+        context
+            .metrics()
+            .request_duration
+            .stage_accept
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(messages)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub type RemoveRequest = InviteRequest;
+pub struct RemoveHandler;
+
+#[async_trait]
+impl RequestHandler for RemoveHandler {
+    type Payload = RemoveRequest;
+    const ERROR_TITLE: &'static str = "Failed to remove agent from stage";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        let room = find_room(context, payload.room_id).await?;
+
+        // Agents may leave the stage on their own.
+        let maybe_authz_time = if &payload.agent_id == reqp.as_agent_id() {
+            None
+        } else {
+            Some(authorize(context, &room, &payload.agent_id, reqp).await?)
+        };
+
+        let conn = context.get_conn().await?;
+        let row_count = task::spawn_blocking({
+            let room_id = room.id();
+            let agent_id = payload.agent_id.clone();
+            move || db::stage_member::DeleteQuery::new(room_id, &agent_id).execute(&conn)
+        })
+        .await?;
+
+        if row_count == 0 {
+            return Err(anyhow!("Agent is not on the stage"))
+                .error(AppErrorKind::StageInvitationNotFound);
+        }
+
+        // Stop publishing of the agent since it's not allowed anymore.
+        if room.is_stage_mode() {
+            hang_up_publisher(context, &room, &payload.agent_id).await?;
+        }
+
+        let messages = build_messages(
+            context,
+            "stage.remove",
+            &room,
+            payload.agent_id,
+            reqp,
+            maybe_authz_time,
+        );
+
+        context
+            .metrics()
+            .request_duration
+            .stage_remove
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(messages)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+async fn find_room<C: Context>(
+    context: &mut C,
+    room_id: db::room::Id,
+) -> StdResult<db::room::Object, AppError> {
+    let conn = context.get_conn().await?;
+    let room = task::spawn_blocking(move || {
+        helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::Open, &conn)
+    })
+    .await?;
+
+    helpers::add_room_logger_tags(context, &room);
+    Ok(room)
+}
+
+// Authorizes managing the agent in the room like for `agent.admit`.
+async fn authorize<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    agent_id: &AgentId,
+    reqp: &IncomingRequestProperties,
+) -> StdResult<Duration, AppError> {
+    let room_id = room.id().to_string();
+    let agent_id = agent_id.to_string();
+    let object = vec!["rooms", &room_id, "agents", &agent_id];
+
+    let authz_time = context
+        .authz()
+        .authorize(room.audience(), reqp, object, "update")
+        .await?;
+
+    context.metrics().observe_auth(authz_time);
+    Ok(authz_time)
+}
+
+fn build_messages<C: Context>(
+    context: &C,
+    label: &str,
+    room: &db::room::Object,
+    agent_id: AgentId,
+    reqp: &IncomingRequestProperties,
+    maybe_authz_time: Option<Duration>,
+) -> MessageStream {
+    let response = helpers::build_response(
+        ResponseStatus::OK,
+        json!({}),
+        reqp,
+        context.start_timestamp(),
+        maybe_authz_time,
+    );
+
+    let notification = helpers::build_notification(
+        label,
+        &format!("rooms/{}/events", room.id()),
+        StageEvent::new(room.id(), agent_id),
+        reqp,
+        context.start_timestamp(),
+    );
+
+    Box::new(stream::from_iter(vec![response, notification]))
+}
+
+/// Hangs up Janus handles of the agent's active streams in the room.
+///
+/// Janus notifies about the hang up afterwards so the streams get stopped as usual.
+async fn hang_up_publisher<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    agent_id: &AgentId,
+) -> StdResult<(), AppError> {
+    let conn = context.get_conn().await?;
+
+    let streams = task::spawn_blocking({
+        let room_id = room.id();
+        let agent_id = agent_id.clone();
+
+        move || {
+            let streams = db::janus_rtc_stream::ListQuery::new()
+                .room_id(room_id)
+                .active(true)
+                .execute(&conn)?;
+
+            let mut result = Vec::new();
+
+            for stream in streams {
+                if stream.sent_by() != &agent_id {
+                    continue;
+                }
+
+                let maybe_backend = db::janus_backend::FindQuery::new()
+                    .id(stream.backend_id())
+                    .execute(&conn)?;
+
+                if let Some(backend) = maybe_backend {
+                    result.push((stream, backend));
+                }
+            }
+
+            Ok::<_, AppError>(result)
+        }
+    })
+    .await?;
+
+    for (stream, backend) in streams {
+        let client = context
+            .janus_clients()
+            .get_or_insert(&backend)
+            .error(AppErrorKind::BackendClientCreationFailed)?;
+
+        let request = HangupRequest {
+            session_id: backend.session_id(),
+            handle_id: stream.handle_id(),
+        };
+
+        if let Err(err) = client.hangup(request).await {
+            warn!(
+                context.logger(),
+                "Failed to hang up handle {} of agent {}: {:?}",
+                stream.handle_id(),
+                agent_id,
+                err
+            );
+        }
+    }
+
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use diesel::pg::PgConnection;
+    use serde_json::Value as JsonValue;
+
+    use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+    use super::*;
+
+    fn insert_stage_room(conn: &PgConnection) -> db::room::Object {
+        let room = shared_helpers::insert_room(conn);
+
+        db::room::UpdateQuery::new(room.id())
+            .stage_mode(Some(true))
+            .execute(conn)
+            .expect("Failed to enable stage mode")
+    }
+
+    fn find_stage_member(
+        db: &TestDb,
+        room_id: db::room::Id,
+        agent_id: &AgentId,
+    ) -> Option<db::stage_member::Object> {
+        let conn = db
+            .connection_pool()
+            .get()
+            .expect("Failed to get DB connection");
+
+        db::stage_member::FindQuery::new(room_id, agent_id)
+            .execute(&conn)
+            .expect("Failed to find stage member")
+    }
+
+    mod invite {
+        use super::*;
+
+        #[async_std::test]
+        async fn invite_agent() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = insert_stage_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), room.id());
+                room
+            };
+
+            // Allow the host to manage the student.
+            let mut authz = TestAuthz::new();
+            let room_id = room.id().to_string();
+            let student_id = student.agent_id().to_string();
+
+            authz.allow(
+                host.account_id(),
+                vec!["rooms", &room_id, "agents", &student_id],
+                "update",
+            );
+
+            // Make stage.invite request.
+            let mut context = TestContext::new(db.clone(), authz);
+
+            let payload = InviteRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<InviteHandler>(&mut context, &host, payload)
+                .await
+                .expect("Stage invitation failed");
+
+            // Assert response.
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            // Assert notification.
+            let (event, evp, topic) = find_event::<StageEvent>(messages.as_slice());
+            assert_eq!(evp.label(), "stage.invite");
+            assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+            assert_eq!(&event.agent_id, student.agent_id());
+
+            // Assert the student is invited but not on the stage yet.
+            let member = find_stage_member(&db, room.id(), student.agent_id())
+                .expect("Stage member not found");
+
+            assert_eq!(member.status(), db::stage_member::Status::Invited);
+        }
+
+        #[async_std::test]
+        async fn invite_agent_not_authorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+            let other_student = TestAgent::new("web", "other_student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = insert_stage_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), room.id());
+                room
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+
+            let payload = InviteRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let err = handle_request::<InviteHandler>(&mut context, &other_student, payload)
+                .await
+                .expect_err("Unexpected success on stage invitation");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+
+    mod accept {
+        use super::*;
+
+        #[async_std::test]
+        async fn accept_invitation() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = insert_stage_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), room.id());
+
+                db::stage_member::InsertQuery::new(room.id(), student.agent_id(), host.agent_id())
+                    .execute(&conn)
+                    .expect("Failed to invite agent to stage");
+
+                room
+            };
+
+            // Make stage.accept request.
+            let mut context = TestContext::new(db.clone(), TestAuthz::new());
+            let payload = AcceptRequest { room_id: room.id() };
+
+            let messages = handle_request::<AcceptHandler>(&mut context, &student, payload)
+                .await
+                .expect("Stage invitation accepting failed");
+
+            // Assert notification.
+            let (event, evp, _) = find_event::<StageEvent>(messages.as_slice());
+            assert_eq!(evp.label(), "stage.accept");
+            assert_eq!(&event.agent_id, student.agent_id());
+
+            // Assert the student is on the stage.
+            let member = find_stage_member(&db, room.id(), student.agent_id())
+                .expect("Stage member not found");
+
+            assert_eq!(member.status(), db::stage_member::Status::Accepted);
+        }
+
+        #[async_std::test]
+        async fn accept_missing_invitation() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = insert_stage_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), room.id());
+                room
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+            let payload = AcceptRequest { room_id: room.id() };
+
+            let err = handle_request::<AcceptHandler>(&mut context, &student, payload)
+                .await
+                .expect_err("Unexpected success on stage invitation accepting");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "stage_invitation_not_found");
+        }
+    }
+
+    mod remove {
+        use super::*;
+
+        #[async_std::test]
+        async fn leave_stage() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = insert_stage_room(&conn);
+                shared_helpers::insert_agent(&conn, student.agent_id(), room.id());
+
+                db::stage_member::InsertQuery::new(room.id(), student.agent_id(), host.agent_id())
+                    .execute(&conn)
+                    .expect("Failed to invite agent to stage");
+
+                db::stage_member::AcceptQuery::new(room.id(), student.agent_id())
+                    .execute(&conn)
+                    .expect("Failed to accept stage invitation");
+
+                room
+            };
+
+            // Make stage.remove request by the student itself which requires no authorization.
+            let mut context = TestContext::new(db.clone(), TestAuthz::new());
+
+            let payload = RemoveRequest {
+                room_id: room.id(),
+                agent_id: student.agent_id().to_owned(),
+            };
+
+            let messages = handle_request::<RemoveHandler>(&mut context, &student, payload)
+                .await
+                .expect("Stage leaving failed");
+
+            // Assert notification.
+            let (event, evp, _) = find_event::<StageEvent>(messages.as_slice());
+            assert_eq!(evp.label(), "stage.remove");
+            assert_eq!(&event.agent_id, student.agent_id());
+
+            // Assert the student is not on the stage anymore.
+            assert!(find_stage_member(&db, room.id(), student.agent_id()).is_none());
+        }
+    }
+}
@@ -14,9 +14,13 @@ use svc_agent::{
 };
 
 use crate::{
-    app::{context::Context, endpoint::prelude::*, metrics::HistogramExt},
+    app::{
+        context::Context,
+        endpoint::{hand, prelude::*},
+        metrics::HistogramExt,
+    },
     backend::janus::client::agent_leave::{AgentLeaveRequest, AgentLeaveRequestBody},
-    db,
+    db::{self, agent_session::LeaveReason as SessionLeaveReason},
 };
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -38,15 +42,44 @@ impl CorrelationDataPayload {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaveReason {
+    Kicked,
+    Banned,
+}
+
+impl From<LeaveReason> for SessionLeaveReason {
+    fn from(reason: LeaveReason) -> Self {
+        match reason {
+            LeaveReason::Kicked => Self::Kicked,
+            LeaveReason::Banned => Self::Banned,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct RoomEnterLeaveEvent {
     id: db::room::Id,
     agent_id: AgentId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<LeaveReason>,
 }
 
 impl RoomEnterLeaveEvent {
     pub fn new(id: db::room::Id, agent_id: AgentId) -> Self {
-        Self { id, agent_id }
+        Self {
+            id,
+            agent_id,
+            reason: None,
+        }
+    }
+
+    pub fn reason(self, reason: LeaveReason) -> Self {
+        Self {
+            reason: Some(reason),
+            ..self
+        }
     }
 }
 
@@ -84,10 +117,12 @@ impl ResponseHandler for CreateResponseHandler {
             let room =
                 helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::NotClosed, &conn)?;
 
-            // Update agent state to `ready`.
+            // Update agent state to `ready` and open an attendance session.
             db::agent::UpdateQuery::new(&subject, room_id)
                 .status(db::agent::Status::Ready)
                 .execute(&conn)?;
+
+            db::agent_session::StartQuery::new(&subject, room_id).execute(&conn)?;
             Ok::<_, AppError>(room)
         })
         .await?;
@@ -136,7 +171,13 @@ impl ResponseHandler for DeleteResponseHandler {
     ) -> Result {
         ensure_broker(context, respp)?;
         let room_id = try_room_id(&corr_data.object)?;
-        let maybe_left = leave_room(context, &corr_data.subject, room_id).await?;
+        let maybe_left = leave_room(
+            context,
+            &corr_data.subject,
+            room_id,
+            SessionLeaveReason::Left,
+        )
+        .await?;
         if maybe_left {
             let response = helpers::build_response(
                 ResponseStatus::OK,
@@ -166,6 +207,81 @@ impl ResponseHandler for DeleteResponseHandler {
     }
 }
 
+/// Handles the broker's response to `subscription.delete` sent on `agent.kick`, `agent.ban`
+/// or on moving the agent out of a room by `room.breakout.*` requests.
+///
+/// The agent has already been removed from the room and the moderator has got the response
+/// so there's nothing left to do.
+pub struct KickResponseHandler;
+
+#[async_trait]
+impl ResponseHandler for KickResponseHandler {
+    type Payload = CreateDeleteResponsePayload;
+    type CorrelationData = CorrelationDataPayload;
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        _payload: Self::Payload,
+        respp: &IncomingResponseProperties,
+        _corr_data: &Self::CorrelationData,
+    ) -> Result {
+        ensure_broker(context, respp)?;
+        Ok(Box::new(stream::empty()))
+    }
+}
+
+/// Handles the broker's response to `subscription.create` sent on moving the agent
+/// into a room by `room.breakout.*` requests.
+///
+/// Unlike `room.enter` the moderator has already got the response so only
+/// the room-wide notification is being sent.
+pub struct BreakoutEnterResponseHandler;
+
+#[async_trait]
+impl ResponseHandler for BreakoutEnterResponseHandler {
+    type Payload = CreateDeleteResponsePayload;
+    type CorrelationData = CorrelationDataPayload;
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        _payload: Self::Payload,
+        respp: &IncomingResponseProperties,
+        corr_data: &Self::CorrelationData,
+    ) -> Result {
+        ensure_broker(context, respp)?;
+
+        // Update agent state to `ready`.
+        let room_id = try_room_id(&corr_data.object)?;
+        let conn = context.get_conn().await?;
+        let subject = corr_data.subject.clone();
+
+        task::spawn_blocking(move || {
+            db::agent::UpdateQuery::new(&subject, room_id)
+                .status(db::agent::Status::Ready)
+                .execute(&conn)?;
+
+            db::agent_session::StartQuery::new(&subject, room_id).execute(&conn)
+        })
+        .await?;
+
+        let notification = helpers::build_notification(
+            "room.enter",
+            &format!("rooms/{}/events", room_id),
+            RoomEnterLeaveEvent::new(room_id, corr_data.subject.to_owned()),
+            &corr_data.reqp,
+            context.start_timestamp(),
+        );
+
+        context
+            .metrics()
+            .request_duration
+            .subscription_create
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::once(notification)))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DeleteEventPayload {
     subject: AgentId,
@@ -185,24 +301,43 @@ impl EventHandler for DeleteEventHandler {
     ) -> Result {
         ensure_broker(context, evp)?;
         let room_id = try_room_id(&payload.object)?;
-        if leave_room(context, &payload.subject, room_id).await? {
+        let to_uri = format!("rooms/{}/events", room_id);
+        let mut notifications = Vec::new();
+
+        if leave_room(context, &payload.subject, room_id, SessionLeaveReason::Left).await? {
             let outgoing_event_payload =
                 RoomEnterLeaveEvent::new(room_id, payload.subject.to_owned());
             let short_term_timing = ShortTermTimingProperties::until_now(context.start_timestamp());
             let props = evp.to_event("room.leave", short_term_timing);
-            let to_uri = format!("rooms/{}/events", room_id);
             let outgoing_event = OutgoingEvent::broadcast(outgoing_event_payload, props, &to_uri);
-            let notification = Box::new(outgoing_event) as Box<dyn IntoPublishableMessage + Send>;
+            notifications.push(Box::new(outgoing_event) as Box<dyn IntoPublishableMessage + Send>);
+        }
+
+        // Lower the hand of the agent who has gone. The agent may have already been removed
+        // from the room by `agent.kick` so it's done regardless of `leave_room` result.
+        let conn = context.get_conn().await?;
+        let maybe_hand_update = task::spawn_blocking({
+            let agent_id = payload.subject.clone();
+            move || hand::lower_hand(room_id, &agent_id, &conn)
+        })
+        .await?;
+
+        if let Some(hand_update) = maybe_hand_update {
+            let short_term_timing = ShortTermTimingProperties::until_now(context.start_timestamp());
+            let props = evp.to_event("hand.update", short_term_timing);
+            let outgoing_event = OutgoingEvent::broadcast(hand_update, props, &to_uri);
+            notifications.push(Box::new(outgoing_event) as Box<dyn IntoPublishableMessage + Send>);
+        }
+
+        if !notifications.is_empty() {
             context
                 .metrics()
                 .request_duration
                 .subscription_delete_event
                 .observe_timestamp(context.start_timestamp());
-
-            Ok(Box::new(stream::once(notification)))
-        } else {
-            Ok(Box::new(stream::empty()))
         }
+
+        Ok(Box::new(stream::from_iter(notifications)))
     }
 }
 
@@ -239,10 +374,11 @@ fn try_room_id(object: &[String]) -> StdResult<db::room::Id, AppError> {
     .error(AppErrorKind::InvalidSubscriptionObject)
 }
 
-async fn leave_room<C: Context>(
+pub(crate) async fn leave_room<C: Context>(
     context: &mut C,
     agent_id: &AgentId,
     room_id: db::room::Id,
+    reason: SessionLeaveReason,
 ) -> StdResult<bool, AppError> {
     // Delete agent from the DB.
     context.add_logger_tags(o!("room_id" => room_id.to_string()));
@@ -257,6 +393,13 @@ async fn leave_room<C: Context>(
                 .room_id(room_id)
                 .execute(&conn)?;
 
+            db::agent_session::FinishQuery::new(room_id, reason)
+                .agent_id(&agent_id)
+                .execute(&conn)?;
+
+            // The agent has to be invited to the stage once again after re-entering.
+            db::stage_member::DeleteQuery::new(room_id, &agent_id).execute(&conn)?;
+
             if row_count != 1 {
                 return Ok::<_, AppError>(None);
             }
@@ -424,6 +567,15 @@ mod tests {
 
             let db_agent = db_agents.first().expect("Missing agent in the DB");
             assert_eq!(db_agent.status(), AgentStatus::Ready);
+
+            // Assert attendance session opened.
+            let sessions = db::agent_session::ListQuery::new(room.id())
+                .execute(&conn)
+                .expect("Failed to list agent sessions");
+
+            assert_eq!(sessions.len(), 1);
+            assert_eq!(sessions[0].agent_id(), agent.agent_id());
+            assert_eq!(sessions[0].finished_at(), None);
         }
 
         #[async_std::test]
@@ -528,6 +680,11 @@ mod tests {
 
                 let room = shared_helpers::insert_room(&conn);
                 shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
+
+                db::agent_session::StartQuery::new(agent.agent_id(), room.id())
+                    .execute(&conn)
+                    .expect("Failed to start agent session");
+
                 room
             };
 
@@ -589,6 +746,15 @@ mod tests {
                 .expect("Failed to execute agent list query");
 
             assert_eq!(db_agents.len(), 0);
+
+            // Assert attendance session finished.
+            let sessions = db::agent_session::ListQuery::new(room.id())
+                .execute(&conn)
+                .expect("Failed to list agent sessions");
+
+            assert_eq!(sessions.len(), 1);
+            assert!(sessions[0].finished_at().is_some());
+            assert_eq!(sessions[0].leave_reason(), Some(SessionLeaveReason::Left));
         }
 
         #[async_std::test]
@@ -666,9 +832,11 @@ mod tests {
     }
 
     mod delete_event {
+        use serde_json::Value as JsonValue;
+
         use crate::{
             db::agent::ListQuery as AgentListQuery,
-            test_helpers::{prelude::*, test_deps::LocalDeps},
+            test_helpers::{find_event_by_predicate, prelude::*, test_deps::LocalDeps},
         };
 
         use super::super::*;
@@ -764,5 +932,131 @@ mod tests {
 
             assert!(messages.is_empty());
         }
+
+        #[async_std::test]
+        async fn delete_subscription_lowers_hand() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                // Create room, put the agent online and raise its hand.
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
+
+                db::raised_hand::InsertQuery::new(room.id(), agent.agent_id())
+                    .execute(&conn)
+                    .expect("Failed to raise hand");
+
+                room
+            };
+
+            // Send subscription.delete event.
+            let mut context = TestContext::new(db, TestAuthz::new());
+            let room_id = room.id().to_string();
+
+            let payload = DeleteEventPayload {
+                subject: agent.agent_id().to_owned(),
+                object: vec!["rooms".to_string(), room_id, "events".to_string()],
+            };
+
+            let broker_account_label = context.config().broker_id.label();
+            let broker = TestAgent::new("alpha", broker_account_label, SVC_AUDIENCE);
+
+            let messages = handle_event::<DeleteEventHandler>(&mut context, &broker, payload)
+                .await
+                .expect("Subscription deletion failed");
+
+            // Assert hand.update notification.
+            let (payload, _, topic) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, _| {
+                    evp.label() == "hand.update"
+                })
+                .expect("Failed to find hand.update event");
+
+            assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+            assert_eq!(payload["hands"], json!([]));
+
+            // Assert the hand is lowered in the DB.
+            let conn = context
+                .get_conn()
+                .await
+                .expect("Failed to get DB connection");
+
+            let hands = db::raised_hand::ListQuery::new(room.id())
+                .execute(&conn)
+                .expect("Failed to list raised hands");
+
+            assert!(hands.is_empty());
+        }
+
+        #[async_std::test]
+        async fn delete_subscription_removes_from_stage() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+
+            let room = {
+                // Create room, put the agent online and on the stage.
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let room = shared_helpers::insert_room(&conn);
+                shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
+
+                db::stage_member::InsertQuery::new(
+                    room.id(),
+                    agent.agent_id(),
+                    moderator.agent_id(),
+                )
+                .execute(&conn)
+                .expect("Failed to invite to the stage");
+
+                db::stage_member::AcceptQuery::new(room.id(), agent.agent_id())
+                    .execute(&conn)
+                    .expect("Failed to accept stage invitation")
+                    .expect("Stage invitation not found");
+
+                room
+            };
+
+            // Send subscription.delete event.
+            let mut context = TestContext::new(db, TestAuthz::new());
+            let room_id = room.id().to_string();
+
+            let payload = DeleteEventPayload {
+                subject: agent.agent_id().to_owned(),
+                object: vec!["rooms".to_string(), room_id, "events".to_string()],
+            };
+
+            let broker_account_label = context.config().broker_id.label();
+            let broker = TestAgent::new("alpha", broker_account_label, SVC_AUDIENCE);
+
+            handle_event::<DeleteEventHandler>(&mut context, &broker, payload)
+                .await
+                .expect("Subscription deletion failed");
+
+            // Assert the agent is no longer on the stage.
+            let conn = context
+                .get_conn()
+                .await
+                .expect("Failed to get DB connection");
+
+            let maybe_stage_member = db::stage_member::FindQuery::new(room.id(), agent.agent_id())
+                .execute(&conn)
+                .expect("Failed to find stage member");
+
+            assert!(maybe_stage_member.is_none());
+        }
     }
 }
@@ -3,20 +3,32 @@ use async_std::{stream, task};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::{ops::Bound, result::Result as StdResult};
+use slog::error;
+use std::{collections::HashSet, ops::Bound, result::Result as StdResult};
 use svc_agent::{
     mqtt::{
         IncomingRequestProperties, OutgoingEvent, OutgoingEventProperties, OutgoingMessage,
-        ShortTermTimingProperties,
+        ResponseStatus, ShortTermTimingProperties,
     },
     AgentId,
 };
 use svc_authn::Authenticable;
 
 use crate::{
-    app::{context::Context, endpoint::prelude::*, error::Error as AppError},
-    backend::janus::client::upload_stream::{
-        UploadStreamRequest, UploadStreamRequestBody, UploadStreamTransaction,
+    app::{
+        context::Context,
+        endpoint::{
+            attendance::{build_attendance, AgentAttendance},
+            prelude::*,
+        },
+        error::Error as AppError,
+        room_closing,
+    },
+    backend::janus::{
+        balancer::{self, BalancingStrategyKind, ChoiceReason},
+        client::upload_stream::{
+            UploadStreamRequest, UploadStreamRequestBody, UploadStreamTransaction,
+        },
     },
     config::UploadConfig,
     db,
@@ -33,6 +45,7 @@ use crate::{
 pub struct RoomUploadEventData {
     id: db::room::Id,
     rtcs: Vec<RtcUploadEventData>,
+    attendance: Vec<AgentAttendance>,
 }
 
 #[derive(Debug, Serialize)]
@@ -87,13 +100,56 @@ impl RequestHandler for VacuumHandler {
             .authorize(audience, reqp, vec!["system"], "update")
             .await?;
 
+        // Clean up expired idempotency keys.
+        let expired_before = helpers::idempotency_keys_expired_before(context)?;
+        let conn = context.get_conn().await?;
+
+        task::spawn_blocking(move || {
+            db::idempotency_key::DeleteQuery::new(expired_before).execute(&conn)
+        })
+        .await?;
+
         let mut requests = Vec::new();
         let conn = context.get_conn().await?;
         let rooms =
             task::spawn_blocking(move || db::room::finished_with_in_progress_recordings(&conn))
                 .await?;
 
+        let mut closed_rooms = HashSet::new();
+
         for (room, recording, backend) in rooms.into_iter() {
+            // Closing uploads all the room's recordings so the following rows of the same room
+            // are skipped.
+            if closed_rooms.contains(&room.id()) {
+                continue;
+            }
+
+            // The room has already been closed but uploading the recording has failed.
+            if room.closed_at().is_some() {
+                if let Err(app_error) = upload_recording(context, &room, &recording, &backend).await
+                {
+                    error!(
+                        context.logger(),
+                        "Failed to retry uploading recording of rtc {} in room {}: {:?}",
+                        recording.rtc_id(),
+                        room.id(),
+                        app_error
+                    );
+
+                    app_error.notify_sentry(context.logger());
+                }
+
+                continue;
+            }
+
+            // TODO: Send the error as an event to "app/${APP}/audiences/${AUD}" topic
+            let room = match room_closing::close_room(context, room).await? {
+                Some(room) => room,
+                None => continue,
+            };
+
+            closed_rooms.insert(room.id());
+
             let conn = context.get_conn().await?;
             let room_id = room.id();
             task::spawn_blocking(move || {
@@ -103,30 +159,6 @@ impl RequestHandler for VacuumHandler {
             })
             .await?;
 
-            let config = upload_config(context, &room)?;
-            let request = UploadStreamRequest {
-                body: UploadStreamRequestBody::new(
-                    recording.rtc_id(),
-                    &config.backend,
-                    &config.bucket,
-                    &record_name(&recording, &room),
-                ),
-                handle_id: backend.handle_id(),
-                session_id: backend.session_id(),
-            };
-            let transaction = UploadStreamTransaction {
-                rtc_id: recording.rtc_id(),
-                start_timestamp: context.start_timestamp(),
-            };
-            // TODO: Send the error as an event to "app/${APP}/audiences/${AUD}" topic
-            context
-                .janus_clients()
-                .get_or_insert(&backend)
-                .error(AppErrorKind::BackendClientCreationFailed)?
-                .upload_stream(request, transaction)
-                .await
-                .error(AppErrorKind::BackendRequestFailed)?;
-
             // Publish room closed notification
             let closed_notification = helpers::build_notification(
                 "room.close",
@@ -145,10 +177,231 @@ impl RequestHandler for VacuumHandler {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Deserialize)]
+pub struct BackendUpdateRequest {
+    id: AgentId,
+    draining: bool,
+}
+
+pub struct BackendUpdateHandler;
+
+#[async_trait]
+impl RequestHandler for BackendUpdateHandler {
+    type Payload = BackendUpdateRequest;
+    const ERROR_TITLE: &'static str = "Failed to update backend";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(audience, reqp, vec!["system"], "update")
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Draining backend keeps its rooms but is skipped when choosing a backend for a new one.
+        let conn = context.get_conn().await?;
+        let backend = task::spawn_blocking(move || {
+            db::janus_backend::UpdateQuery::new(payload.id)
+                .draining(payload.draining)
+                .execute(&conn)
+        })
+        .await?
+        .ok_or_else(|| anyhow!("Backend not found"))
+        .error(AppErrorKind::BackendNotFound)?;
+
+        Ok(Box::new(stream::once(helpers::build_response(
+            ResponseStatus::OK,
+            backend,
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct BalancerExplainRequest {
+    room_id: db::room::Id,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalancerExplainResponseData {
+    room_id: db::room::Id,
+    strategy: BalancingStrategyKind,
+    reserve: i32,
+    candidates: Vec<BalancerCandidate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    choice: Option<BalancerChoice>,
+}
+
+#[derive(Debug, Serialize)]
+struct BalancerCandidate {
+    #[serde(flatten)]
+    load: db::janus_backend::BackendLoad,
+    effective_capacity: i32,
+    fits_reserve: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct BalancerChoice {
+    backend_id: AgentId,
+    reason: ChoiceReason,
+}
+
+pub struct BalancerExplainHandler;
+
+#[async_trait]
+impl RequestHandler for BalancerExplainHandler {
+    type Payload = BalancerExplainRequest;
+    const ERROR_TITLE: &'static str = "Failed to explain balancer decision";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(audience, reqp, vec!["system"], "read")
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let conn = context.get_conn().await?;
+        let room = task::spawn_blocking(move || {
+            helpers::find_room_by_id(payload.room_id, helpers::RoomTimeRequirement::Any, &conn)
+        })
+        .await?;
+        helpers::add_room_logger_tags(context, &room);
+
+        // Mirrors backend choosing in `rtc.connect` but doesn't bind the room to the backend.
+        let group = context.config().janus_group.clone();
+        let strategy_kind = context.config().balancer.strategy(room.audience());
+        let reserve = room.balancing_reserve();
+        let conn = context.get_conn().await?;
+
+        let backends = task::spawn_blocking(move || {
+            db::janus_backend::balancing_snapshot(group.as_deref(), &conn)
+        })
+        .await?;
+
+        let choice = match room.backend_id() {
+            Some(backend_id) => Some(BalancerChoice {
+                backend_id: backend_id.to_owned(),
+                reason: ChoiceReason::AlreadyAssigned,
+            }),
+            None => balancer::strategy(strategy_kind)
+                .dry_run(&backends, reserve)
+                .map(|choice| BalancerChoice {
+                    backend_id: choice.backend.id.to_owned(),
+                    reason: choice.reason,
+                }),
+        };
+
+        let candidates = backends
+            .into_iter()
+            .map(|load| BalancerCandidate {
+                effective_capacity: load.effective_capacity(),
+                fits_reserve: balancer::fits(&load, reserve),
+                load,
+            })
+            .collect();
+
+        let data = BalancerExplainResponseData {
+            room_id: room.id(),
+            strategy: strategy_kind,
+            reserve,
+            candidates,
+            choice,
+        };
+
+        Ok(Box::new(stream::once(helpers::build_response(
+            ResponseStatus::OK,
+            data,
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct UsageReadRequest {
+    audience: Option<String>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    from: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    to: DateTime<Utc>,
+}
+
+pub struct UsageReadHandler;
+
+#[async_trait]
+impl RequestHandler for UsageReadHandler {
+    type Payload = UsageReadRequest;
+    const ERROR_TITLE: &'static str = "Failed to read usage";
+
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: &IncomingRequestProperties,
+    ) -> Result {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(audience, reqp, vec!["system"], "read")
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        if payload.from >= payload.to {
+            return Err(anyhow!("Usage period must not be empty"))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        // Per-audience, per-room, per-day usage within the period.
+        let conn = context.get_conn().await?;
+
+        let usage = task::spawn_blocking(move || {
+            let query = db::usage::UsageQuery::new(payload.from, payload.to);
+
+            match payload.audience {
+                Some(ref audience) => query.audience(audience).execute(&conn),
+                None => query.execute(&conn),
+            }
+        })
+        .await?;
+
+        Ok(Box::new(stream::once(helpers::build_response(
+            ResponseStatus::OK,
+            usage,
+            reqp,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 pub fn upload_event<C: Context, I>(
     context: &C,
     room: &db::room::Object,
     recordings: I,
+    sessions: &[db::agent_session::Object],
 ) -> StdResult<RoomUploadEvent, AppError>
 where
     I: Iterator<Item = (db::recording::Object, db::rtc::Object)>,
@@ -190,14 +443,52 @@ where
     let timing = ShortTermTimingProperties::until_now(context.start_timestamp());
     let props = OutgoingEventProperties::new("room.upload", timing);
 
+    // Sessions that haven't been finished for some reason last until the room closure.
+    let until = match room.closes_at() {
+        Some(closes_at) => std::cmp::min(closes_at, Utc::now()),
+        None => Utc::now(),
+    };
+
     let event = RoomUploadEventData {
         id: room.id(),
         rtcs: event_entries,
+        attendance: build_attendance(sessions, until),
     };
 
     Ok(OutgoingEvent::broadcast(event, props, &uri))
 }
 
+pub(crate) async fn upload_recording<C: Context>(
+    context: &C,
+    room: &Room,
+    recording: &Recording,
+    backend: &db::janus_backend::Object,
+) -> StdResult<(), AppError> {
+    let config = upload_config(context, room)?;
+    let request = UploadStreamRequest {
+        body: UploadStreamRequestBody::new(
+            recording.rtc_id(),
+            &config.backend,
+            &config.bucket,
+            &record_name(recording, room),
+        ),
+        handle_id: backend.handle_id(),
+        session_id: backend.session_id(),
+    };
+    let transaction = UploadStreamTransaction {
+        rtc_id: recording.rtc_id(),
+        start_timestamp: context.start_timestamp(),
+    };
+
+    context
+        .janus_clients()
+        .get_or_insert(backend)
+        .error(AppErrorKind::BackendClientCreationFailed)?
+        .upload_stream(request, transaction)
+        .await
+        .error(AppErrorKind::BackendRequestFailed)
+}
+
 fn upload_config<'a, C: Context>(
     context: &'a C,
     room: &Room,
@@ -331,6 +622,32 @@ mod test {
             assert!(tx.is_empty());
             assert!(messages.len() > 0);
             assert_eq!(recv_rtcs, rtcs);
+
+            // The rooms have been closed so they must not be closed once again but uploading
+            // the recordings that are still in progress gets retried.
+            let messages = handle_request::<VacuumHandler>(&mut context, &agent, VacuumRequest {})
+                .await
+                .expect("System vacuum failed");
+
+            context.janus_clients().remove_client(backend.id());
+            assert!(messages.is_empty());
+
+            let mut retried_rtcs: Vec<db::rtc::Id> = [rx.recv().unwrap(), rx.recv().unwrap()]
+                .iter()
+                .map(|resp| match resp {
+                    IncomingEvent::Event(EventResponse {
+                        transaction:
+                            Transaction::UploadStream(UploadStreamTransaction { rtc_id, .. }),
+                        ..
+                    }) => *rtc_id,
+                    _ => panic!("Got wrong event"),
+                })
+                .collect();
+
+            retried_rtcs.sort_by_key(|id| id.to_string());
+            let mut rtcs = rtcs;
+            rtcs.sort_by_key(|id| id.to_string());
+            assert_eq!(retried_rtcs, rtcs);
         }
 
         #[async_std::test]
@@ -354,4 +671,380 @@ mod test {
             assert_eq!(err.kind(), "access_denied");
         }
     }
+
+    mod backend_update {
+        use svc_agent::mqtt::ResponseStatus;
+
+        use crate::{
+            backend::janus::client::{HandleId, SessionId},
+            test_helpers::{prelude::*, test_deps::LocalDeps},
+        };
+
+        use super::super::*;
+
+        #[async_std::test]
+        async fn update_backend_draining() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+            authz.set_audience(SVC_AUDIENCE);
+
+            let backend = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_janus_backend(
+                    &conn,
+                    "test",
+                    SessionId::random(),
+                    HandleId::random(),
+                )
+            };
+
+            assert!(!backend.is_draining());
+
+            // Allow cron to update backends.
+            let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["system"], "update");
+
+            // Make system.backend.update request.
+            let mut context = TestContext::new(db, authz);
+
+            let payload = BackendUpdateRequest {
+                id: backend.id().to_owned(),
+                draining: true,
+            };
+
+            let messages = handle_request::<BackendUpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Backend update failed");
+
+            let (resp_backend, respp, _) = find_response::<db::janus_backend::Object>(&messages);
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp_backend.id(), backend.id());
+            assert!(resp_backend.is_draining());
+
+            // Draining backend must be persisted.
+            let conn = context
+                .get_conn()
+                .await
+                .expect("Failed to get DB connection");
+
+            let db_backend = db::janus_backend::FindQuery::new()
+                .id(backend.id())
+                .execute(&conn)
+                .expect("Failed to find backend")
+                .expect("Backend not found");
+
+            assert!(db_backend.is_draining());
+        }
+
+        #[async_std::test]
+        async fn update_missing_backend() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+            authz.set_audience(SVC_AUDIENCE);
+
+            let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["system"], "update");
+
+            let mut context = TestContext::new(db, authz);
+            let backend = TestAgent::new("alpha", "janus", SVC_AUDIENCE);
+
+            let payload = BackendUpdateRequest {
+                id: backend.agent_id().to_owned(),
+                draining: true,
+            };
+
+            let err = handle_request::<BackendUpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on backend update");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "backend_not_found");
+        }
+
+        #[async_std::test]
+        async fn update_backend_unauthorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+            authz.set_audience(SVC_AUDIENCE);
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, authz);
+            let backend = TestAgent::new("alpha", "janus", SVC_AUDIENCE);
+
+            let payload = BackendUpdateRequest {
+                id: backend.agent_id().to_owned(),
+                draining: true,
+            };
+
+            let err = handle_request::<BackendUpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on backend update");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+
+    mod balancer_explain {
+        use serde_json::Value as JsonValue;
+        use svc_agent::mqtt::ResponseStatus;
+
+        use crate::{
+            backend::janus::client::{HandleId, SessionId},
+            test_helpers::{prelude::*, test_deps::LocalDeps},
+        };
+
+        use super::super::*;
+
+        #[async_std::test]
+        async fn explain_without_binding_room() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+            authz.set_audience(SVC_AUDIENCE);
+
+            let (room, backend1) = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                let backend1 = shared_helpers::insert_janus_backend(
+                    &conn,
+                    "test",
+                    SessionId::random(),
+                    HandleId::random(),
+                );
+
+                let backend2 = shared_helpers::insert_janus_backend(
+                    &conn,
+                    "test",
+                    SessionId::random(),
+                    HandleId::random(),
+                );
+
+                // The first backend is more loaded so it's preferred by bin packing.
+                let busy_room = factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                    .rtc_sharing_policy(SharingPolicy::Shared)
+                    .backend_id(backend1.id())
+                    .reserve(5)
+                    .insert(&conn);
+
+                let _idle_room = factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                    .rtc_sharing_policy(SharingPolicy::Shared)
+                    .backend_id(backend2.id())
+                    .reserve(1)
+                    .insert(&conn);
+
+                let room = factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                    .rtc_sharing_policy(SharingPolicy::Shared)
+                    .insert(&conn);
+
+                assert_ne!(busy_room.id(), room.id());
+                (room, backend1)
+            };
+
+            let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["system"], "read");
+
+            let mut context = TestContext::new(db, authz);
+            let payload = BalancerExplainRequest { room_id: room.id() };
+
+            let messages = handle_request::<BalancerExplainHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Balancer explain failed");
+
+            let (resp, respp, _) = find_response::<JsonValue>(&messages);
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp["strategy"], "bin_packing");
+            assert_eq!(resp["candidates"].as_array().map(|c| c.len()), Some(2));
+            assert_eq!(resp["choice"]["backend_id"], backend1.id().to_string());
+            assert_eq!(resp["choice"]["reason"], "most_loaded");
+
+            // The room must not be bound to the backend.
+            let conn = context
+                .get_conn()
+                .await
+                .expect("Failed to get DB connection");
+
+            let db_room =
+                helpers::find_room_by_id(room.id(), helpers::RoomTimeRequirement::Any, &conn)
+                    .expect("Failed to find room");
+
+            assert!(db_room.backend_id().is_none());
+        }
+
+        #[async_std::test]
+        async fn explain_unauthorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+            authz.set_audience(SVC_AUDIENCE);
+
+            let room = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                shared_helpers::insert_room(&conn)
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, authz);
+            let payload = BalancerExplainRequest { room_id: room.id() };
+
+            let err = handle_request::<BalancerExplainHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on balancer explain");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+
+    mod usage_read {
+        use chrono::Duration;
+        use serde_json::Value as JsonValue;
+        use svc_agent::mqtt::ResponseStatus;
+
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+        use super::super::*;
+
+        #[async_std::test]
+        async fn read_usage() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+            authz.set_audience(SVC_AUDIENCE);
+            let participant = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room_id = {
+                let conn = db
+                    .connection_pool()
+                    .get()
+                    .expect("Failed to get DB connection");
+
+                // Publish a stream and attend the room.
+                let stream = factory::JanusRtcStream::new(USR_AUDIENCE).insert(&conn);
+
+                db::janus_rtc_stream::start(stream.id(), &conn)
+                    .expect("Failed to start stream")
+                    .expect("Stream not found");
+
+                db::janus_rtc_stream::stop(stream.id(), &conn)
+                    .expect("Failed to stop stream")
+                    .expect("Stream not found");
+
+                let rtc = db::rtc::FindQuery::new()
+                    .id(stream.rtc_id())
+                    .execute(&conn)
+                    .expect("Failed to find rtc")
+                    .expect("Rtc not found");
+
+                db::agent_session::StartQuery::new(participant.agent_id(), rtc.room_id())
+                    .execute(&conn)
+                    .expect("Failed to start agent session");
+
+                rtc.room_id()
+            };
+
+            // Allow cron to read usage.
+            let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["system"], "read");
+
+            // Make system.usage.read request.
+            let mut context = TestContext::new(db, authz);
+
+            let payload = UsageReadRequest {
+                audience: Some(USR_AUDIENCE.to_owned()),
+                from: Utc::now() - Duration::hours(1),
+                to: Utc::now() + Duration::hours(1),
+            };
+
+            let messages = handle_request::<UsageReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Usage reading failed");
+
+            let (usage, respp, _) = find_response::<Vec<JsonValue>>(&messages);
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(usage.len(), 1);
+            assert_eq!(usage[0]["audience"], USR_AUDIENCE);
+            assert_eq!(usage[0]["room_id"], room_id.to_string());
+            assert!(usage[0]["publisher_minutes"].as_f64().unwrap() > 0.0);
+            assert!(usage[0]["participant_minutes"].as_f64().unwrap() >= 0.0);
+        }
+
+        #[async_std::test]
+        async fn read_usage_for_empty_period() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+            authz.set_audience(SVC_AUDIENCE);
+
+            let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["system"], "read");
+            let mut context = TestContext::new(db, authz);
+            let now = Utc::now();
+
+            let payload = UsageReadRequest {
+                audience: None,
+                from: now,
+                to: now,
+            };
+
+            let err = handle_request::<UsageReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on usage reading");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+
+        #[async_std::test]
+        async fn read_usage_unauthorized() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let mut authz = TestAuthz::new();
+            authz.set_audience(SVC_AUDIENCE);
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, authz);
+
+            let payload = UsageReadRequest {
+                audience: None,
+                from: Utc::now() - Duration::hours(1),
+                to: Utc::now(),
+            };
+
+            let err = handle_request::<UsageReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on usage reading");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
 }
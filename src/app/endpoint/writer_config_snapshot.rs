@@ -51,12 +51,7 @@ impl RequestHandler for ReadHandler {
                 .error(AppErrorKind::AccessDenied)?;
             }
 
-            if room.rtc_sharing_policy() != db::rtc::SharingPolicy::Owned {
-                return Err(anyhow!(
-                    "Agent writer config is available only for rooms with owned RTC sharing policy"
-                ))
-                .error(AppErrorKind::InvalidPayload)?;
-            }
+            helpers::check_rtc_configs_availability(&room)?;
 
             let snapshots =
                 db::rtc_writer_config_snapshot::ListWithRtcQuery::new(room.id()).execute(&conn)?;
@@ -178,7 +173,7 @@ mod tests {
                     let room = factory::Room::new()
                         .audience(USR_AUDIENCE)
                         .time((Bound::Included(Utc::now()), Bound::Unbounded))
-                        .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                        .rtc_sharing_policy(RtcSharingPolicy::None)
                         .insert(&conn);
 
                     shared_helpers::insert_agent(&conn, agent.agent_id(), room.id());
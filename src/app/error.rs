@@ -16,13 +16,14 @@ struct ErrorKindProperties {
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, IntoEnumIterator)]
 pub enum ErrorKind {
     AccessDenied,
+    AgentBanned,
     AgentNotConnected,
     AgentNotEnteredTheRoom,
     AuthorizationFailed,
     BackendRecordingMissing,
     BackendRequestFailed,
     BackendClientCreationFailed,
-    _BackendRequestTimedOut,
+    BackendRequestTimedOut,
     BackendNotFound,
     BrokerRequestFailed,
     CapacityExceeded,
@@ -40,12 +41,18 @@ pub enum ErrorKind {
     MessageParsingFailed,
     NoAvailableBackends,
     NotImplemented,
+    NotOnStage,
     PublishFailed,
+    QuotaExceeded,
     ResubscriptionFailed,
     RoomClosed,
+    RoomFull,
+    RoomLocked,
     RoomNotFound,
     RoomTimeChangingForbidden,
     RtcNotFound,
+    RtcStreamNotFound,
+    StageInvitationNotFound,
 }
 
 impl ErrorKind {
@@ -86,6 +93,12 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Access denied",
                 is_notify_sentry: false,
             },
+            ErrorKind::AgentBanned => ErrorKindProperties {
+                status: ResponseStatus::FORBIDDEN,
+                kind: "agent_banned",
+                title: "Agent banned from the room",
+                is_notify_sentry: false,
+            },
             ErrorKind::AgentNotConnected => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "agent_not_connected",
@@ -122,7 +135,7 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Janus create client failed",
                 is_notify_sentry: true,
             },
-            ErrorKind::_BackendRequestTimedOut => ErrorKindProperties {
+            ErrorKind::BackendRequestTimedOut => ErrorKindProperties {
                 status: ResponseStatus::FAILED_DEPENDENCY,
                 kind: "backend_request_timed_out",
                 title: "Janus request timed out",
@@ -230,12 +243,24 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Not implemented",
                 is_notify_sentry: true,
             },
+            ErrorKind::NotOnStage => ErrorKindProperties {
+                status: ResponseStatus::FORBIDDEN,
+                kind: "not_on_stage",
+                title: "Not on stage",
+                is_notify_sentry: false,
+            },
             ErrorKind::PublishFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "publish_failed",
                 title: "Publish failed",
                 is_notify_sentry: true,
             },
+            ErrorKind::QuotaExceeded => ErrorKindProperties {
+                status: ResponseStatus::FORBIDDEN,
+                kind: "quota_exceeded",
+                title: "Quota exceeded",
+                is_notify_sentry: false,
+            },
             ErrorKind::ResubscriptionFailed => ErrorKindProperties {
                 status: ResponseStatus::INTERNAL_SERVER_ERROR,
                 kind: "resubscription_failed",
@@ -248,6 +273,18 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Room closed",
                 is_notify_sentry: false,
             },
+            ErrorKind::RoomFull => ErrorKindProperties {
+                status: ResponseStatus::FORBIDDEN,
+                kind: "room_full",
+                title: "Room full",
+                is_notify_sentry: false,
+            },
+            ErrorKind::RoomLocked => ErrorKindProperties {
+                status: ResponseStatus::FORBIDDEN,
+                kind: "room_locked",
+                title: "Room locked",
+                is_notify_sentry: false,
+            },
             ErrorKind::RoomNotFound => ErrorKindProperties {
                 status: ResponseStatus::NOT_FOUND,
                 kind: "room_not_found",
@@ -266,6 +303,18 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "RTC not found",
                 is_notify_sentry: false,
             },
+            ErrorKind::RtcStreamNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                kind: "rtc_stream_not_found",
+                title: "RTC stream not found",
+                is_notify_sentry: false,
+            },
+            ErrorKind::StageInvitationNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                kind: "stage_invitation_not_found",
+                title: "Stage invitation not found",
+                is_notify_sentry: false,
+            },
         }
     }
 }
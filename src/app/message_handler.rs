@@ -21,7 +21,7 @@ use crate::{
         context::{AppMessageContext, Context, GlobalContext, MessageContext},
         endpoint,
         error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
-        API_VERSION,
+        room_closing, API_VERSION,
     },
     backend::{janus, janus::handle_event},
 };
@@ -76,6 +76,18 @@ impl<C: GlobalContext + Sync> MessageHandler<C> {
         }
     }
 
+    pub async fn handle_room_closing(&self) {
+        let mut msg_context = AppMessageContext::new(&self.global_context, Utc::now());
+
+        let messages = room_closing::warn_closing_rooms(&mut msg_context)
+            .await
+            .chain(room_closing::close_finished_rooms(&mut msg_context).await);
+
+        if let Err(err) = self.publish_outgoing_messages(Box::new(messages)).await {
+            warn!(msg_context.logger(), "Room closing error: {:?}", err);
+        }
+    }
+
     async fn report_error(
         msg_context: &mut AppMessageContext<'_, C>,
         message: &Result<IncomingMessage<String>, String>,
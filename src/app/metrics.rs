@@ -3,9 +3,11 @@ use std::{collections::HashMap, sync::Arc, time::Duration};
 use chrono::{DateTime, Utc};
 use enum_iterator::IntoEnumIterator;
 use prometheus::{
-    Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry,
 };
 use prometheus_static_metric::make_static_metric;
+use svc_agent::AgentId;
 
 use super::{endpoint, error::ErrorKind};
 
@@ -31,28 +33,46 @@ fn duration_to_seconds(d: Duration) -> f64 {
 make_static_metric! {
     struct RequestDuration: Histogram {
         "method" => {
+            agent_admit,
+            agent_ban,
+            agent_kick,
             agent_list,
             agent_reader_config_read,
             agent_reader_config_update,
+            agent_reject,
             agent_writer_config_read,
             agent_writer_config_update,
+            attendance_list,
+            hand_dismiss,
+            hand_list,
+            hand_lower,
+            hand_raise,
             message_broadcast,
             message_callback,
             message_unicast_request,
             message_unicast_response,
+            room_breakout_assign,
+            room_breakout_close,
+            room_breakout_create,
             room_create,
             room_enter,
             room_leave,
+            room_list,
+            room_mute_all,
             room_read,
             room_update,
             rtc_connect,
             rtc_create,
             rtc_list,
             rtc_read,
+            rtc_unpublish,
             rtc_signal_create,
             rtc_signal_trickle,
             rtc_signal_read,
             rtc_stream_list,
+            stage_accept,
+            stage_invite,
+            stage_remove,
             upload_stream,
             subscription_create,
             subscription_delete_event,
@@ -71,6 +91,10 @@ pub struct Metrics {
     pub total_requests: IntCounter,
     pub authorization_time: Histogram,
     pub running_requests_total: IntGauge,
+    pub janus_slow_links: IntCounterVec,
+    pub janus_media_losses: IntCounterVec,
+    pub room_full_rejections: IntCounterVec,
+    pub quota_usage: GaugeVec,
 }
 
 impl Metrics {
@@ -90,12 +114,35 @@ impl Metrics {
             Opts::new("mqtt_messages", "Mqtt message types"),
             &["status"],
         )?;
+        let janus_slow_links = IntCounterVec::new(
+            Opts::new("janus_slow_links", "Janus slow link events"),
+            &["agent", "uplink"],
+        )?;
+        let janus_media_losses = IntCounterVec::new(
+            Opts::new("janus_media_losses", "Janus stopped receiving media"),
+            &["agent", "kind"],
+        )?;
+        let room_full_rejections = IntCounterVec::new(
+            Opts::new(
+                "room_full_rejections",
+                "Room entrances rejected due to max agents",
+            ),
+            &["audience"],
+        )?;
+        let quota_usage = GaugeVec::new(
+            Opts::new("quota_usage", "Audience quota usage"),
+            &["quota", "audience"],
+        )?;
         registry.register(Box::new(mqtt_errors.clone()))?;
         registry.register(Box::new(request_duration.clone()))?;
         registry.register(Box::new(request_stats.clone()))?;
         registry.register(Box::new(total_requests.clone()))?;
         registry.register(Box::new(authorization_time.clone()))?;
         registry.register(Box::new(running_requests_total.clone()))?;
+        registry.register(Box::new(janus_slow_links.clone()))?;
+        registry.register(Box::new(janus_media_losses.clone()))?;
+        registry.register(Box::new(room_full_rejections.clone()))?;
+        registry.register(Box::new(quota_usage.clone()))?;
         Ok(Self {
             request_duration: RequestDuration::from(&request_duration),
             total_requests,
@@ -114,6 +161,10 @@ impl Metrics {
                 .get_metric_with_label_values(&["connection_error"])?,
             mqtt_disconnect: mqtt_errors.get_metric_with_label_values(&["disconnect"])?,
             mqtt_reconnection: mqtt_errors.get_metric_with_label_values(&["reconnect"])?,
+            janus_slow_links,
+            janus_media_losses,
+            room_full_rejections,
+            quota_usage,
         })
     }
 
@@ -137,6 +188,44 @@ impl Metrics {
         }
     }
 
+    pub fn observe_janus_slow_link(&self, backend_id: &AgentId, uplink: bool) {
+        let uplink = if uplink { "true" } else { "false" };
+
+        if let Ok(counter) = self
+            .janus_slow_links
+            .get_metric_with_label_values(&[backend_id.label(), uplink])
+        {
+            counter.inc();
+        }
+    }
+
+    pub fn observe_janus_media_loss(&self, backend_id: &AgentId, kind: &str) {
+        if let Ok(counter) = self
+            .janus_media_losses
+            .get_metric_with_label_values(&[backend_id.label(), kind])
+        {
+            counter.inc();
+        }
+    }
+
+    pub fn observe_room_full(&self, audience: &str) {
+        if let Ok(counter) = self
+            .room_full_rejections
+            .get_metric_with_label_values(&[audience])
+        {
+            counter.inc();
+        }
+    }
+
+    pub fn observe_quota_usage(&self, quota: &str, audience: &str, usage: f64) {
+        if let Ok(gauge) = self
+            .quota_usage
+            .get_metric_with_label_values(&[quota, audience])
+        {
+            gauge.set(usage);
+        }
+    }
+
     pub fn request_started(self: Arc<Self>) -> StartedRequest {
         StartedRequest::new(self)
     }
@@ -10,7 +10,9 @@ use std::{
 
 use crate::{
     app::error::{Error as AppError, ErrorKind as AppErrorKind},
-    backend::janus::{client_pool::Clients, JANUS_API_VERSION},
+    backend::janus::{
+        client_pool::Clients, transaction_watchdog::TransactionWatchdog, JANUS_API_VERSION,
+    },
     config::{self, Config, KruonisConfig},
     db::ConnectionPool,
 };
@@ -81,11 +83,17 @@ pub async fn run(
     let metrics_registry = Registry::new();
     let metrics = crate::app::metrics::Metrics::new(&metrics_registry)?;
     let janus_metrics = crate::backend::janus::metrics::Metrics::new(&metrics_registry)?;
+    let poller_metrics = crate::backend::janus::metrics::PollerMetrics::new(&metrics_registry)?;
     thread::spawn({
         let db = db.clone();
         let collect_interval = config.metrics.janus_metrics_collect_interval;
         move || janus_metrics.start_collector(db.clone(), collect_interval)
     });
+    if let Some(collect_interval) = config.metrics.usage_metrics_collect_interval {
+        let usage_metrics = crate::app::usage::Metrics::new(&metrics_registry)?;
+        let db = db.clone();
+        thread::spawn(move || usage_metrics.start_collector(db, collect_interval));
+    }
     task::spawn(start_metrics_collector(
         metrics_registry,
         config.metrics.http.bind_address,
@@ -94,7 +102,12 @@ pub async fn run(
     // Subscribe to topics
     let janus_topics = subscribe(&mut agent, &agent_id, &config)?;
     let (ev_tx, ev_rx) = crossbeam_channel::unbounded();
-    let clients = Clients::new(ev_tx, config.janus_group.clone());
+    let watchdog = TransactionWatchdog::new(&config.backend);
+    task::spawn(watchdog.clone().start(
+        ev_tx.clone(),
+        Duration::from_secs(config.backend.transaction_watchdog_check_period),
+    ));
+    let clients = Clients::new(ev_tx, config.janus_group.clone(), poller_metrics, watchdog);
     // Context
     let metrics = Arc::new(metrics);
     let context = AppContext::new(
@@ -113,6 +126,21 @@ pub async fn run(
 
     // Message handler
     let message_handler = Arc::new(MessageHandler::new(agent, context));
+
+    // Room closing
+    task::spawn({
+        let message_handler = message_handler.clone();
+        let is_stopped = is_stopped.clone();
+        let check_period = config.room_closing.check_period;
+
+        async move {
+            while !is_stopped.load(Ordering::SeqCst) {
+                message_handler.handle_room_closing().await;
+                task::sleep(check_period).await;
+            }
+        }
+    });
+
     {
         let is_stopped = is_stopped.clone();
         thread::spawn(move || loop {
@@ -301,3 +329,5 @@ pub mod error;
 pub mod handle_id;
 pub mod message_handler;
 pub mod metrics;
+pub mod room_closing;
+pub mod usage;
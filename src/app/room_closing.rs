@@ -0,0 +1,573 @@
+use async_std::{stream, task};
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use diesel::Connection;
+use serde::{Deserialize, Serialize};
+use slog::{error, warn};
+use svc_agent::mqtt::{
+    IntoPublishableMessage, OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties,
+};
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::system::upload_recording,
+        error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
+        message_handler::MessageStream,
+    },
+    backend::janus::client::hangup::HangupRequest,
+    db::{self, room::Object as Room},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Closes rooms which time is over and publishes `room.close` notifications for them.
+///
+/// Unbounded rooms opened longer than `max_room_duration` get closed at the current time.
+pub async fn close_finished_rooms<C: Context>(context: &mut C) -> MessageStream {
+    close_finished_rooms_impl(context)
+        .await
+        .unwrap_or_else(|app_error| {
+            error!(
+                context.logger(),
+                "Failed to close finished rooms: {:?}", app_error
+            );
+
+            app_error.notify_sentry(context.logger());
+            Box::new(stream::empty())
+        })
+}
+
+async fn close_finished_rooms_impl<C: Context>(context: &mut C) -> Result<MessageStream, AppError> {
+    let max_room_duration = context.config().max_room_duration;
+    let conn = context.get_conn().await?;
+
+    let rooms = task::spawn_blocking(move || {
+        if let Some(max_room_duration) = max_room_duration {
+            db::room::bound_overlong(max_room_duration, &conn)?;
+        }
+
+        db::room::finished_unclosed(&conn)
+    })
+    .await?;
+
+    let mut messages = Vec::with_capacity(rooms.len() * 2);
+
+    for room in rooms {
+        let room_id = room.id();
+
+        match close_room(context, room).await {
+            Ok(Some(room)) => {
+                let start_timestamp = context.start_timestamp();
+                let room_topic = format!("rooms/{}/events", room.id());
+                let audience_topic = format!("audiences/{}/events", room.audience());
+                messages.push(close_notification(&room_topic, &room, start_timestamp));
+                messages.push(close_notification(&audience_topic, &room, start_timestamp));
+            }
+            // Another instance has closed it.
+            Ok(None) => (),
+            Err(app_error) => {
+                error!(
+                    context.logger(),
+                    "Failed to close room {}: {:?}", room_id, app_error
+                );
+
+                app_error.notify_sentry(context.logger());
+            }
+        }
+    }
+
+    Ok(Box::new(stream::from_iter(messages)))
+}
+
+fn close_notification(
+    path: &str,
+    room: &Room,
+    start_timestamp: DateTime<Utc>,
+) -> Box<dyn IntoPublishableMessage + Send> {
+    let timing = ShortTermTimingProperties::until_now(start_timestamp);
+    let props = OutgoingEventProperties::new("room.close", timing);
+    Box::new(OutgoingEvent::broadcast(room.to_owned(), props, path))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClosingSoonNotification {
+    room_id: db::room::Id,
+    #[serde(with = "ts_seconds")]
+    closes_at: DateTime<Utc>,
+    // Seconds left before closing.
+    remaining: i64,
+}
+
+/// Publishes `room.closing_soon` notifications to rooms which closing is within one of
+/// `room_closing.warning_offsets`.
+///
+/// The offset participants have been warned at is stored in the room so they get warned
+/// only once per offset even after restart.
+pub async fn warn_closing_rooms<C: Context>(context: &mut C) -> MessageStream {
+    warn_closing_rooms_impl(context)
+        .await
+        .unwrap_or_else(|app_error| {
+            error!(
+                context.logger(),
+                "Failed to warn about closing rooms: {:?}", app_error
+            );
+
+            app_error.notify_sentry(context.logger());
+            Box::new(stream::empty())
+        })
+}
+
+async fn warn_closing_rooms_impl<C: Context>(context: &mut C) -> Result<MessageStream, AppError> {
+    let offsets = context.config().room_closing.warning_offsets.clone();
+
+    let max_offset = match offsets.iter().max() {
+        Some(max_offset) => *max_offset,
+        None => return Ok(Box::new(stream::empty())),
+    };
+
+    let conn = context.get_conn().await?;
+
+    let notifications = task::spawn_blocking(move || {
+        let now = Utc::now();
+        let mut notifications = vec![];
+
+        for room in db::room::closing_within(max_offset, &conn)? {
+            let closes_at = match room.closes_at() {
+                Some(closes_at) => closes_at,
+                None => continue,
+            };
+
+            let remaining = (closes_at - now).num_seconds();
+
+            // When several offsets have passed, e.g. after downtime, warn only at the closest one.
+            let offset = match offsets.iter().filter(|o| i64::from(**o) >= remaining).min() {
+                Some(offset) => *offset,
+                None => continue,
+            };
+
+            if let Some(warned_offset) = room.closing_warning_offset() {
+                if warned_offset <= offset {
+                    continue;
+                }
+            }
+
+            if db::room::WarnClosingQuery::new(room.id(), offset)
+                .execute(&conn)?
+                .is_some()
+            {
+                notifications.push(ClosingSoonNotification {
+                    room_id: room.id(),
+                    closes_at,
+                    remaining,
+                });
+            }
+        }
+
+        Ok::<_, AppError>(notifications)
+    })
+    .await?;
+
+    let start_timestamp = context.start_timestamp();
+
+    let messages = notifications.into_iter().map(|notification| {
+        let path = format!("rooms/{}/events", notification.room_id);
+        let timing = ShortTermTimingProperties::until_now(start_timestamp);
+        let props = OutgoingEventProperties::new("room.closing_soon", timing);
+        let event = OutgoingEvent::broadcast(notification, props, &path);
+        Box::new(event) as Box<dyn IntoPublishableMessage + Send>
+    });
+
+    Ok(Box::new(stream::from_iter(messages.collect::<Vec<_>>())))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Marks the room as closed, disconnects its agents, hangs up their Janus handles
+/// and starts uploading the room's recordings.
+///
+/// Returns `None` if the room has already been closed.
+pub async fn close_room<C: Context>(context: &mut C, room: Room) -> Result<Option<Room>, AppError> {
+    let conn = context.get_conn().await?;
+
+    let maybe_closed = task::spawn_blocking(move || {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let room = match db::room::CloseQuery::new(room.id()).execute(&conn)? {
+                Some(room) => room,
+                None => return Ok(None),
+            };
+
+            let connections =
+                db::agent_connection::ListByRoomQuery::new(room.id()).execute(&conn)?;
+
+            db::agent_connection::BulkDisconnectByRoomQuery::new(room.id()).execute(&conn)?;
+
+            db::agent_session::FinishQuery::new(room.id(), db::agent_session::LeaveReason::Closed)
+                .execute(&conn)?;
+
+            let maybe_backend = match room.backend_id() {
+                Some(backend_id) => db::janus_backend::FindQuery::new()
+                    .id(backend_id)
+                    .execute(&conn)?,
+                None => None,
+            };
+
+            let recordings = db::recording::in_progress_by_room(room.id(), &conn)?;
+            Ok(Some((room, connections, maybe_backend, recordings)))
+        })
+    })
+    .await?;
+
+    let (room, connections, maybe_backend, recordings) = match maybe_closed {
+        Some(closed) => closed,
+        None => return Ok(None),
+    };
+
+    // Rooms without a backend have neither handles nor recordings.
+    let backend = match maybe_backend {
+        Some(backend) => backend,
+        None => return Ok(Some(room)),
+    };
+
+    let client = context
+        .janus_clients()
+        .get_or_insert(&backend)
+        .error(AppErrorKind::BackendClientCreationFailed)?;
+
+    for connection in connections {
+        let request = HangupRequest {
+            session_id: backend.session_id(),
+            handle_id: connection.handle_id(),
+        };
+
+        if let Err(err) = client.hangup(request).await {
+            warn!(
+                context.logger(),
+                "Failed to hang up handle {} of room {}: {:?}",
+                connection.handle_id(),
+                room.id(),
+                err
+            );
+        }
+    }
+
+    // A failed upload doesn't prevent uploading the other recordings. Its recording stays
+    // in progress so `system.vacuum` retries it later.
+    for recording in recordings.iter() {
+        if let Err(app_error) = upload_recording(context, &room, recording, &backend).await {
+            error!(
+                context.logger(),
+                "Failed to upload recording of rtc {} in room {}: {:?}",
+                recording.rtc_id(),
+                room.id(),
+                app_error
+            );
+
+            app_error.notify_sentry(context.logger());
+        }
+    }
+
+    Ok(Some(room))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use chrono::Duration;
+
+    use crate::{
+        app::endpoint::helpers,
+        backend::janus::client::{
+            events::EventResponse, transactions::Transaction,
+            upload_stream::UploadStreamTransaction, IncomingEvent,
+        },
+        test_helpers::{
+            fake_janus::FakeJanusFailure, outgoing_envelope::OutgoingEnvelope, parse_messages,
+            prelude::*, test_deps::LocalDeps,
+        },
+    };
+
+    use super::*;
+
+    fn closed_room_topics(messages: &[OutgoingEnvelope], room: &Room) -> Vec<String> {
+        messages
+            .iter()
+            .filter(|message| message.payload::<Room>().id() == room.id())
+            .map(|message| message.topic().to_owned())
+            .collect()
+    }
+
+    #[async_std::test]
+    async fn close_finished_room() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let db = TestDb::with_local_postgres(&postgres);
+
+        let (finished_room, open_room) = {
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let now = Utc::now();
+
+            let finished_room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((
+                    Bound::Included(now - Duration::hours(1)),
+                    Bound::Excluded(now - Duration::seconds(1)),
+                ))
+                .insert(&conn);
+
+            let rtc = factory::Rtc::new(finished_room.id()).insert(&conn);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            shared_helpers::insert_connected_agent(
+                &conn,
+                agent.agent_id(),
+                finished_room.id(),
+                rtc.id(),
+            );
+
+            (finished_room, shared_helpers::insert_room(&conn))
+        };
+
+        let mut context = TestContext::new(db.clone(), TestAuthz::new());
+        let messages = parse_messages(close_finished_rooms(&mut context).await).await;
+
+        // Notify both room and audience topics.
+        let mut topics = closed_room_topics(&messages, &finished_room);
+        topics.sort();
+        assert_eq!(topics.len(), 2);
+        assert!(topics[0].ends_with(&format!("/audiences/{}/events", USR_AUDIENCE)));
+        assert!(topics[1].ends_with(&format!("/rooms/{}/events", finished_room.id())));
+        assert!(closed_room_topics(&messages, &open_room).is_empty());
+
+        let conn = db
+            .connection_pool()
+            .get()
+            .expect("Failed to get DB connection");
+
+        let room =
+            helpers::find_room_by_id(finished_room.id(), helpers::RoomTimeRequirement::Any, &conn)
+                .expect("Failed to find room");
+
+        assert!(room.closed_at().is_some());
+
+        // Agents must be disconnected.
+        let connections = db::agent_connection::ListByRoomQuery::new(finished_room.id())
+            .execute(&conn)
+            .expect("Failed to list agent connections");
+
+        assert!(connections.is_empty());
+
+        let room =
+            helpers::find_room_by_id(open_room.id(), helpers::RoomTimeRequirement::Any, &conn)
+                .expect("Failed to find room");
+
+        assert!(room.closed_at().is_none());
+
+        // The room gets closed only once.
+        let messages = parse_messages(close_finished_rooms(&mut context).await).await;
+        assert!(messages.is_empty());
+    }
+
+    #[async_std::test]
+    async fn upload_recordings_despite_failure() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let janus = local_deps.run_janus();
+        let db = TestDb::with_local_postgres(&postgres);
+        let (session_id, handle_id) = shared_helpers::init_janus(&janus.url).await;
+
+        let (backend, rtcs) = {
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let backend =
+                shared_helpers::insert_janus_backend(&conn, &janus.url, session_id, handle_id);
+
+            let room = shared_helpers::insert_closed_room_with_backend_id(&conn, backend.id());
+
+            let rtcs = vec![
+                shared_helpers::insert_rtc_with_room(&conn, &room),
+                shared_helpers::insert_rtc_with_room(&conn, &room),
+            ];
+
+            for rtc in rtcs.iter() {
+                shared_helpers::insert_recording(&conn, rtc);
+            }
+
+            (backend, rtcs)
+        };
+
+        // Make Janus fail the first upload.
+        janus.fail_next("stream.upload", FakeJanusFailure::Error);
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let (tx, rx) = crossbeam_channel::unbounded();
+        context.with_janus(tx);
+
+        let messages = parse_messages(close_finished_rooms(&mut context).await).await;
+        context.janus_clients().remove_client(backend.id());
+        assert_eq!(messages.len(), 2);
+
+        // The other recording must still be uploaded.
+        match rx.recv().expect("Failed to receive upload event") {
+            IncomingEvent::Event(EventResponse {
+                transaction: Transaction::UploadStream(UploadStreamTransaction { rtc_id, .. }),
+                ..
+            }) => assert!(rtcs.iter().any(|rtc| rtc.id() == rtc_id)),
+            _ => panic!("Got wrong event"),
+        }
+
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[async_std::test]
+    async fn close_overlong_unbounded_room() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let db = TestDb::with_local_postgres(&postgres);
+
+        let room = {
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            // `max_room_duration` is 7 hours in the test config.
+            factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((
+                    Bound::Included(Utc::now() - Duration::hours(8)),
+                    Bound::Unbounded,
+                ))
+                .insert(&conn)
+        };
+
+        let mut context = TestContext::new(db.clone(), TestAuthz::new());
+        let messages = parse_messages(close_finished_rooms(&mut context).await).await;
+        assert_eq!(closed_room_topics(&messages, &room).len(), 2);
+
+        let conn = db
+            .connection_pool()
+            .get()
+            .expect("Failed to get DB connection");
+
+        let room = helpers::find_room_by_id(room.id(), helpers::RoomTimeRequirement::Any, &conn)
+            .expect("Failed to find room");
+
+        assert!(room.is_closed());
+        assert!(room.closed_at().is_some());
+    }
+
+    fn insert_room_closing_in(db: &TestDb, seconds: i64) -> Room {
+        let conn = db
+            .connection_pool()
+            .get()
+            .expect("Failed to get DB connection");
+
+        let now = Utc::now();
+
+        factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((
+                Bound::Included(now - Duration::hours(1)),
+                Bound::Excluded(now + Duration::seconds(seconds)),
+            ))
+            .insert(&conn)
+    }
+
+    #[async_std::test]
+    async fn warn_closing_room() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let db = TestDb::with_local_postgres(&postgres);
+
+        // `warning_offsets` are 600 and 120 seconds in the test config.
+        let room = insert_room_closing_in(&db, 300);
+        let distant_room = insert_room_closing_in(&db, 3600);
+
+        let mut context = TestContext::new(db.clone(), TestAuthz::new());
+        let messages = parse_messages(warn_closing_rooms(&mut context).await).await;
+        assert_eq!(messages.len(), 1);
+
+        let message = &messages[0];
+        assert!(message
+            .topic()
+            .ends_with(&format!("/rooms/{}/events", room.id())));
+
+        let notification = message.payload::<ClosingSoonNotification>();
+        assert_eq!(notification.room_id, room.id());
+        assert!(notification.remaining > 120 && notification.remaining <= 300);
+
+        // Participants get warned at each offset only once.
+        let messages = parse_messages(warn_closing_rooms(&mut context).await).await;
+        assert!(messages.is_empty());
+
+        let conn = db
+            .connection_pool()
+            .get()
+            .expect("Failed to get DB connection");
+
+        let room = helpers::find_room_by_id(room.id(), helpers::RoomTimeRequirement::Any, &conn)
+            .expect("Failed to find room");
+
+        assert_eq!(room.closing_warning_offset(), Some(600));
+
+        let distant_room =
+            helpers::find_room_by_id(distant_room.id(), helpers::RoomTimeRequirement::Any, &conn)
+                .expect("Failed to find room");
+
+        assert_eq!(distant_room.closing_warning_offset(), None);
+    }
+
+    #[async_std::test]
+    async fn warn_rescheduled_room() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let db = TestDb::with_local_postgres(&postgres);
+        let room = insert_room_closing_in(&db, 300);
+
+        let mut context = TestContext::new(db.clone(), TestAuthz::new());
+        let messages = parse_messages(warn_closing_rooms(&mut context).await).await;
+        assert_eq!(messages.len(), 1);
+
+        // Move the closing closer so both offsets have passed.
+        {
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let now = Utc::now();
+
+            let time = (
+                Bound::Included(now - Duration::hours(1)),
+                Bound::Excluded(now + Duration::seconds(60)),
+            );
+
+            db::room::UpdateQuery::new(room.id())
+                .time(Some(time))
+                .execute(&conn)
+                .expect("Failed to update room");
+        }
+
+        // Participants get warned once again but only at the closest offset.
+        let messages = parse_messages(warn_closing_rooms(&mut context).await).await;
+        assert_eq!(messages.len(), 1);
+
+        let notification = messages[0].payload::<ClosingSoonNotification>();
+        assert!(notification.remaining <= 60);
+
+        let messages = parse_messages(warn_closing_rooms(&mut context).await).await;
+        assert!(messages.is_empty());
+    }
+}
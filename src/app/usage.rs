@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use prometheus::{CounterVec, Opts, Registry};
+use slog::error;
+
+use crate::db::{usage::UsageQuery, ConnectionPool};
+
+/// Exports participant, publisher and recorded minutes of each audience as counters.
+///
+/// Each collection adds the usage since the previous successful one.
+pub struct Metrics {
+    minutes: CounterVec,
+}
+
+impl Metrics {
+    pub fn new(registry: &Registry) -> anyhow::Result<Self> {
+        let minutes = CounterVec::new(
+            Opts::new("usage_minutes", "Usage minutes for billing"),
+            &["kind", "audience"],
+        )?;
+        registry.register(Box::new(minutes.clone()))?;
+        Ok(Self { minutes })
+    }
+
+    pub fn start_collector(self, connection_pool: ConnectionPool, collect_interval: Duration) {
+        let mut collected_until = Utc::now();
+
+        loop {
+            std::thread::sleep(collect_interval);
+            let now = Utc::now();
+
+            match self.collect(&connection_pool, collected_until, now) {
+                Ok(()) => collected_until = now,
+                Err(err) => error!(crate::LOG, "Usage metrics collecting errored: {:?}", err),
+            }
+        }
+    }
+
+    fn collect(
+        &self,
+        pool: &ConnectionPool,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let conn = pool.get()?;
+
+        let usage = UsageQuery::new(from, to)
+            .execute(&conn)
+            .context("Failed to get usage")?;
+
+        for row in usage {
+            let audience = row.audience();
+
+            self.minutes
+                .get_metric_with_label_values(&["participant", audience])?
+                .inc_by(row.participant_minutes());
+
+            self.minutes
+                .get_metric_with_label_values(&["publisher", audience])?
+                .inc_by(row.publisher_minutes());
+
+            self.minutes
+                .get_metric_with_label_values(&["recorded", audience])?
+                .inc_by(row.recorded_minutes());
+        }
+
+        Ok(())
+    }
+}
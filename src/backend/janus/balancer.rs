@@ -0,0 +1,368 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::janus_backend::BackendLoad;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BalancingStrategyKind {
+    BinPacking,
+    RoundRobin,
+    LeastConnections,
+    Weighted,
+    SpreadFirst,
+}
+
+impl Default for BalancingStrategyKind {
+    fn default() -> Self {
+        Self::BinPacking
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChoiceReason {
+    /// The most loaded backend that has enough free capacity for the room's reserve.
+    MostLoaded,
+    /// The next backend in turn that has enough free capacity for the room's reserve.
+    RoundRobin,
+    /// The backend with the least number of connected agents that has enough free capacity.
+    LeastConnections,
+    /// The backend with the lowest load to `balancer_capacity` ratio that has enough
+    /// free capacity.
+    LowestLoadRatio,
+    /// The backend with the most free capacity.
+    MostFreeCapacity,
+    /// No backend is capable to host the room's reserve so the least loaded one is chosen.
+    LeastLoadedFallback,
+    /// The room is already bound to a backend which all its agents must use.
+    AlreadyAssigned,
+}
+
+#[derive(Debug)]
+pub struct Choice<'a> {
+    pub backend: &'a BackendLoad,
+    pub reason: ChoiceReason,
+}
+
+/// Chooses a backend to host a room from a snapshot of backends' load.
+///
+/// Ties are broken by the order of `backends`.
+pub trait BalancingStrategy: Send + Sync {
+    fn choose<'a>(&self, backends: &'a [BackendLoad], reserve: i32) -> Option<Choice<'a>>;
+
+    /// The same as `choose` but doesn't affect the strategy's state.
+    fn dry_run<'a>(&self, backends: &'a [BackendLoad], reserve: i32) -> Option<Choice<'a>> {
+        self.choose(backends, reserve)
+    }
+}
+
+pub fn strategy(kind: BalancingStrategyKind) -> &'static dyn BalancingStrategy {
+    // Round-robin's turn has to survive between requests.
+    static ROUND_ROBIN: RoundRobin = RoundRobin::new();
+
+    match kind {
+        BalancingStrategyKind::BinPacking => &BinPacking,
+        BalancingStrategyKind::RoundRobin => &ROUND_ROBIN,
+        BalancingStrategyKind::LeastConnections => &LeastConnections,
+        BalancingStrategyKind::Weighted => &Weighted,
+        BalancingStrategyKind::SpreadFirst => &SpreadFirst,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn free_capacity(backend: &BackendLoad) -> f64 {
+    f64::from(backend.effective_capacity()) - backend.load
+}
+
+pub(crate) fn fits(backend: &BackendLoad, reserve: i32) -> bool {
+    free_capacity(backend) >= f64::from(reserve)
+}
+
+// Picks the first backend with the maximum key.
+fn max_by_key<'a, F>(
+    backends: impl Iterator<Item = &'a BackendLoad>,
+    key: F,
+) -> Option<&'a BackendLoad>
+where
+    F: Fn(&BackendLoad) -> f64,
+{
+    backends.fold(None, |maybe_best, backend| match maybe_best {
+        Some(best) if key(best) >= key(backend) => Some(best),
+        _ => Some(backend),
+    })
+}
+
+// The least loaded backend without considering the reserve when nobody fits it.
+fn least_loaded_fallback(backends: &[BackendLoad]) -> Option<Choice> {
+    let backend = max_by_key(backends.iter(), |b| {
+        f64::from(b.effective_capacity()) - b.taken
+    })?;
+
+    Some(Choice {
+        backend,
+        reason: ChoiceReason::LeastLoadedFallback,
+    })
+}
+
+fn choose_fitting<'a, F>(
+    backends: &'a [BackendLoad],
+    reserve: i32,
+    reason: ChoiceReason,
+    key: F,
+) -> Option<Choice<'a>>
+where
+    F: Fn(&BackendLoad) -> f64,
+{
+    let fitting = backends.iter().filter(|b| fits(b, reserve));
+
+    match max_by_key(fitting, key) {
+        Some(backend) => Some(Choice { backend, reason }),
+        None => least_loaded_fallback(backends),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Fills backends one by one to keep the others free for big rooms.
+pub struct BinPacking;
+
+impl BalancingStrategy for BinPacking {
+    fn choose<'a>(&self, backends: &'a [BackendLoad], reserve: i32) -> Option<Choice<'a>> {
+        choose_fitting(backends, reserve, ChoiceReason::MostLoaded, |b| b.load)
+    }
+}
+
+/// Takes turns over the backends ordered by id.
+pub struct RoundRobin {
+    turn: AtomicUsize,
+}
+
+impl RoundRobin {
+    pub const fn new() -> Self {
+        Self {
+            turn: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Default for RoundRobin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RoundRobin {
+    fn choose_in_turn<'a>(
+        &self,
+        backends: &'a [BackendLoad],
+        reserve: i32,
+        turn: usize,
+    ) -> Option<Choice<'a>> {
+        let mut fitting = backends
+            .iter()
+            .filter(|b| fits(b, reserve))
+            .collect::<Vec<_>>();
+
+        if fitting.is_empty() {
+            return least_loaded_fallback(backends);
+        }
+
+        fitting.sort_by(|a, b| a.id.to_string().cmp(&b.id.to_string()));
+
+        Some(Choice {
+            backend: fitting[turn % fitting.len()],
+            reason: ChoiceReason::RoundRobin,
+        })
+    }
+}
+
+impl BalancingStrategy for RoundRobin {
+    fn choose<'a>(&self, backends: &'a [BackendLoad], reserve: i32) -> Option<Choice<'a>> {
+        let turn = self.turn.fetch_add(1, Ordering::Relaxed);
+        self.choose_in_turn(backends, reserve, turn)
+    }
+
+    fn dry_run<'a>(&self, backends: &'a [BackendLoad], reserve: i32) -> Option<Choice<'a>> {
+        let turn = self.turn.load(Ordering::Relaxed);
+        self.choose_in_turn(backends, reserve, turn)
+    }
+}
+
+/// Prefers the backend with fewer connected agents.
+pub struct LeastConnections;
+
+impl BalancingStrategy for LeastConnections {
+    fn choose<'a>(&self, backends: &'a [BackendLoad], reserve: i32) -> Option<Choice<'a>> {
+        choose_fitting(backends, reserve, ChoiceReason::LeastConnections, |b| {
+            -(b.connections as f64)
+        })
+    }
+}
+
+/// Spreads the load proportionally to `balancer_capacity` of the backends.
+pub struct Weighted;
+
+impl BalancingStrategy for Weighted {
+    fn choose<'a>(&self, backends: &'a [BackendLoad], reserve: i32) -> Option<Choice<'a>> {
+        choose_fitting(backends, reserve, ChoiceReason::LowestLoadRatio, |b| {
+            -(b.load / f64::from(b.effective_capacity().max(1)))
+        })
+    }
+}
+
+/// Puts the room to the backend with the most free capacity.
+pub struct SpreadFirst;
+
+impl BalancingStrategy for SpreadFirst {
+    fn choose<'a>(&self, backends: &'a [BackendLoad], reserve: i32) -> Option<Choice<'a>> {
+        choose_fitting(
+            backends,
+            reserve,
+            ChoiceReason::MostFreeCapacity,
+            free_capacity,
+        )
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use svc_agent::{AccountId, AgentId};
+
+    use super::*;
+
+    fn backend(label: &str, capacity: i32, load: f64, connections: i64) -> BackendLoad {
+        BackendLoad {
+            id: AgentId::new(label, AccountId::new("janus", "svc.example.org")),
+            capacity: Some(capacity),
+            balancer_capacity: None,
+            load,
+            taken: load,
+            connections,
+        }
+    }
+
+    fn chosen(choice: Option<Choice>) -> (String, ChoiceReason) {
+        let choice = choice.expect("No backend chosen");
+        (choice.backend.id.label().to_owned(), choice.reason)
+    }
+
+    #[test]
+    fn bin_packing() {
+        let backends = vec![
+            backend("alpha", 100, 10.0, 10),
+            backend("beta", 100, 80.0, 80),
+            backend("gamma", 100, 95.0, 95),
+        ];
+
+        assert_eq!(
+            chosen(BinPacking.choose(&backends, 10)),
+            ("beta".to_owned(), ChoiceReason::MostLoaded)
+        );
+
+        assert_eq!(
+            chosen(BinPacking.choose(&backends, 5)),
+            ("gamma".to_owned(), ChoiceReason::MostLoaded)
+        );
+    }
+
+    #[test]
+    fn fall_back_to_least_loaded() {
+        let backends = vec![
+            backend("alpha", 100, 90.0, 90),
+            backend("beta", 100, 60.0, 60),
+        ];
+
+        assert_eq!(
+            chosen(BinPacking.choose(&backends, 50)),
+            ("beta".to_owned(), ChoiceReason::LeastLoadedFallback)
+        );
+    }
+
+    #[test]
+    fn no_backends() {
+        assert!(BinPacking.choose(&[], 1).is_none());
+        assert!(RoundRobin::new().choose(&[], 1).is_none());
+    }
+
+    #[test]
+    fn round_robin() {
+        let backends = vec![
+            backend("gamma", 100, 0.0, 0),
+            backend("alpha", 100, 0.0, 0),
+            backend("beta", 100, 0.0, 0),
+            backend("delta", 100, 100.0, 100),
+        ];
+
+        let strategy = RoundRobin::new();
+
+        let labels = (0..4)
+            .map(|_| chosen(strategy.choose(&backends, 1)).0)
+            .collect::<Vec<_>>();
+
+        assert_eq!(labels, vec!["alpha", "beta", "gamma", "alpha"]);
+
+        // Dry run shows the next turn without taking it.
+        assert_eq!(chosen(strategy.dry_run(&backends, 1)).0, "beta");
+        assert_eq!(chosen(strategy.choose(&backends, 1)).0, "beta");
+    }
+
+    #[test]
+    fn least_connections() {
+        let backends = vec![
+            backend("alpha", 100, 10.0, 20),
+            backend("beta", 100, 30.0, 5),
+        ];
+
+        assert_eq!(
+            chosen(LeastConnections.choose(&backends, 1)),
+            ("beta".to_owned(), ChoiceReason::LeastConnections)
+        );
+    }
+
+    #[test]
+    fn weighted() {
+        let mut backends = vec![
+            backend("alpha", 100, 50.0, 50),
+            backend("beta", 100, 80.0, 80),
+        ];
+        backends[1].balancer_capacity = Some(400);
+
+        assert_eq!(
+            chosen(Weighted.choose(&backends, 1)),
+            ("beta".to_owned(), ChoiceReason::LowestLoadRatio)
+        );
+    }
+
+    #[test]
+    fn spread_first() {
+        let backends = vec![
+            backend("alpha", 100, 50.0, 50),
+            backend("beta", 200, 120.0, 120),
+            backend("gamma", 100, 10.0, 10),
+        ];
+
+        assert_eq!(
+            chosen(SpreadFirst.choose(&backends, 1)),
+            ("gamma".to_owned(), ChoiceReason::MostFreeCapacity)
+        );
+    }
+
+    #[test]
+    fn strategy_kind_from_config() {
+        let kind: BalancingStrategyKind =
+            serde_json::from_str("\"least_connections\"").expect("Failed to parse strategy kind");
+
+        assert_eq!(kind, BalancingStrategyKind::LeastConnections);
+        assert_eq!(
+            BalancingStrategyKind::default(),
+            BalancingStrategyKind::BinPacking
+        );
+    }
+}
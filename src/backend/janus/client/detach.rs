@@ -0,0 +1,9 @@
+use serde::Serialize;
+
+use super::{HandleId, SessionId};
+
+#[derive(Serialize, Debug)]
+pub struct DetachRequest {
+    pub session_id: SessionId,
+    pub handle_id: HandleId,
+}
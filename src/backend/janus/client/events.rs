@@ -1,5 +1,6 @@
 use serde::{de, Deserialize};
 use serde_json::Value;
+use svc_agent::AgentId;
 
 use crate::{backend::janus::OpaqueId, util::from_base64};
 
@@ -70,6 +71,12 @@ pub struct MediaEvent {
     pub receiving: bool,
 }
 
+impl OpaqueId for MediaEvent {
+    fn opaque_id(&self) -> &str {
+        &self.opaque_id
+    }
+}
+
 // A session was torn down by the server because of timeout: 60 seconds (by default).
 #[derive(Debug, Deserialize)]
 pub struct TimeoutEvent {
@@ -86,6 +93,12 @@ pub struct SlowLinkEvent {
     pub uplink: bool,
 }
 
+impl OpaqueId for SlowLinkEvent {
+    fn opaque_id(&self) -> &str {
+        &self.opaque_id
+    }
+}
+
 // Janus handle detached.
 // This is being sent in case of abnormal shutdown or after `HangUpEvent` in Chrome.
 #[derive(Debug, Deserialize)]
@@ -100,3 +113,13 @@ impl OpaqueId for DetachedEvent {
         &self.opaque_id
     }
 }
+
+// Not a Janus event: the poller has found the backend's session gone and created a new one.
+// All the handles of the old session are lost.
+#[derive(Debug)]
+pub struct SessionResetEvent {
+    pub backend_id: AgentId,
+    pub janus_url: String,
+    pub session_id: SessionId,
+    pub handle_id: HandleId,
+}
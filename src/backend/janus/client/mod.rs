@@ -1,24 +1,28 @@
 use std::sync::Arc;
 
-use crate::util::to_base64;
+use crate::{backend::janus::transaction_watchdog::TransactionWatchdog, util::to_base64};
 
 use self::{
     agent_leave::AgentLeaveRequest,
     create_handle::{CreateHandleRequest, CreateHandleResponse},
     create_session::CreateSessionResponse,
     create_stream::{CreateStreamRequest, CreateStreamTransaction},
+    detach::DetachRequest,
     events::{
-        DetachedEvent, EventResponse, HangUpEvent, MediaEvent, SlowLinkEvent, TimeoutEvent,
-        WebRtcUpEvent,
+        DetachedEvent, EventResponse, HangUpEvent, MediaEvent, SessionResetEvent, SlowLinkEvent,
+        TimeoutEvent, WebRtcUpEvent,
     },
+    hangup::HangupRequest,
     read_stream::{ReadStreamRequest, ReadStreamTransaction},
     transactions::Transaction,
     trickle::TrickleRequest,
     update_agent_reader_config::UpdateReaderConfigRequest,
     update_agent_writer_config::UpdateWriterConfigRequest,
     upload_stream::{UploadStreamRequest, UploadStreamTransaction},
+    ws::WsTransport,
 };
-use anyhow::Context;
+use anyhow::{anyhow, Context};
+use crossbeam_channel::Sender;
 use diesel_derive_newtype::DieselNewType;
 use isahc::{
     http::{StatusCode, Uri},
@@ -34,39 +38,151 @@ pub mod agent_leave;
 pub mod create_handle;
 pub mod create_session;
 pub mod create_stream;
+pub mod detach;
 pub mod events;
+pub mod hangup;
 pub mod read_stream;
 pub mod transactions;
 pub mod trickle;
 pub mod update_agent_reader_config;
 pub mod update_agent_writer_config;
 pub mod upload_stream;
+mod ws;
 
 #[derive(Debug, Clone)]
 pub struct JanusClient {
-    http: Arc<HttpClient>,
+    transport: Transport,
     janus_url: Uri,
+    watchdog: Option<TransactionWatchdog>,
+}
+
+#[derive(Debug, Clone)]
+enum Transport {
+    Http(Arc<HttpClient>),
+    Ws(Arc<WsTransport>),
 }
 
 impl JanusClient {
+    /// Creates a client which doesn't receive the backend's events, e.g. when they are handled
+    /// by another service instance.
+    ///
+    /// Its transactions are not watched since their events don't come to this client.
     pub fn new(janus_url: &str) -> anyhow::Result<Self> {
+        Self::build(janus_url, None, None)
+    }
+
+    /// Creates a client for the service instance which handles the backend's events.
+    ///
+    /// Events pushed by Janus over WebSocket are sent to `events_sink`.
+    /// HTTP clients get events by long-polling instead so the sink is ignored for them.
+    /// Transactions sent by the client are tracked by `watchdog` until their events arrive.
+    pub fn new_events_receiver(
+        janus_url: &str,
+        events_sink: Sender<IncomingEvent>,
+        watchdog: TransactionWatchdog,
+    ) -> anyhow::Result<Self> {
+        Self::build(janus_url, Some(events_sink), Some(watchdog))
+    }
+
+    // The transport is chosen by `janus_url` scheme: WebSocket for `ws://` and `wss://`,
+    // HTTP otherwise.
+    fn build(
+        janus_url: &str,
+        events_sink: Option<Sender<IncomingEvent>>,
+        watchdog: Option<TransactionWatchdog>,
+    ) -> anyhow::Result<Self> {
+        let janus_url: Uri = janus_url.parse()?;
+
+        let transport = match janus_url.scheme_str() {
+            Some("ws") | Some("wss") => Transport::Ws(Arc::new(WsTransport::new(
+                &janus_url.to_string(),
+                events_sink,
+                watchdog.clone(),
+            ))),
+            _ => Transport::Http(Arc::new(HttpClient::new()?)),
+        };
+
         Ok(Self {
-            http: Arc::new(HttpClient::new()?),
-            janus_url: janus_url.parse()?,
+            transport,
+            janus_url,
+            watchdog,
         })
     }
 
+    #[cfg(test)]
+    pub(crate) fn is_watched(&self) -> bool {
+        self.watchdog.is_some()
+    }
+
+    pub fn is_websocket(&self) -> bool {
+        matches!(self.transport, Transport::Ws(_))
+    }
+
+    /// The number of WebSocket connections the client has established so far.
+    /// `None` for HTTP clients which don't keep a connection.
+    pub fn connections_count(&self) -> Option<u64> {
+        match &self.transport {
+            Transport::Http(_) => None,
+            Transport::Ws(ws) => Some(ws.connections_count()),
+        }
+    }
+
     pub async fn poll(&self, session_id: SessionId) -> anyhow::Result<PollResult> {
-        let mut response = self
-            .http
+        let http = match &self.transport {
+            Transport::Http(http) => http,
+            Transport::Ws(_) => {
+                return Err(anyhow!("Long-polling is not available over WebSocket"))
+            }
+        };
+
+        let mut response = http
             .get_async(format!("{}/{}?maxev=5", self.janus_url, session_id))
             .await?;
         if response.status() == StatusCode::NOT_FOUND {
             return Ok(PollResult::SessionNotFound);
         }
         let body = response.text().await?;
-        let body: Vec<IncomingEvent> = serde_json::from_str(&body).context(body)?;
-        Ok(PollResult::Events(body))
+        let events = serde_json::from_str::<Vec<serde_json::Value>>(&body)
+            .context(body.clone())?
+            .into_iter()
+            .filter(|event| self.complete_transaction(event))
+            .map(serde_json::from_value::<IncomingEvent>)
+            .collect::<Result<Vec<_>, _>>()
+            .context(body)?;
+        Ok(PollResult::Events(events))
+    }
+
+    // Returns `false` for late events of expired transactions which must be dropped.
+    fn complete_transaction(&self, event: &serde_json::Value) -> bool {
+        if let Some(ref watchdog) = self.watchdog {
+            if event.get("janus").and_then(|v| v.as_str()) == Some("event") {
+                if let Some(transaction) = event.get("transaction").and_then(|v| v.as_str()) {
+                    return watchdog.complete(transaction);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Makes the client's WebSocket connection the owner of the session so Janus pushes
+    /// the session's events to it.
+    pub async fn claim_session(&self, session_id: SessionId) -> anyhow::Result<PollResult> {
+        let response: serde_json::Value = self
+            .send_request(session_request("claim", session_id))
+            .await?;
+
+        session_request_result(response)
+    }
+
+    /// Prevents the session from being torn down by Janus by timeout.
+    /// Long-polling does the same implicitly so it's needed only for WebSocket clients.
+    pub async fn keep_alive(&self, session_id: SessionId) -> anyhow::Result<PollResult> {
+        let response: serde_json::Value = self
+            .send_request(session_request("keepalive", session_id))
+            .await?;
+
+        session_request_result(response)
     }
 
     pub async fn upload_stream(
@@ -74,10 +190,9 @@ impl JanusClient {
         request: UploadStreamRequest,
         transaction: UploadStreamTransaction,
     ) -> anyhow::Result<()> {
-        let _response: AckResponse = self
-            .send_request(upload_stream(request, transaction)?)
-            .await?;
-        Ok(())
+        let watched_transaction = Transaction::UploadStream(transaction.clone());
+        self.send_watched_request(upload_stream(request, transaction)?, watched_transaction)
+            .await
     }
 
     pub async fn agent_leave(&self, request: AgentLeaveRequest) -> anyhow::Result<()> {
@@ -85,6 +200,16 @@ impl JanusClient {
         Ok(())
     }
 
+    pub async fn hangup(&self, request: HangupRequest) -> anyhow::Result<()> {
+        let _response: SuccessResponse = self.send_request(hangup(request)).await?;
+        Ok(())
+    }
+
+    pub async fn detach(&self, request: DetachRequest) -> anyhow::Result<()> {
+        let _response: SuccessResponse = self.send_request(detach(request)).await?;
+        Ok(())
+    }
+
     pub async fn reader_update(&self, request: UpdateReaderConfigRequest) -> anyhow::Result<()> {
         let _response: AckResponse = self.send_request(update_reader(request)?).await?;
         Ok(())
@@ -100,10 +225,9 @@ impl JanusClient {
         request: CreateStreamRequest,
         transaction: CreateStreamTransaction,
     ) -> anyhow::Result<()> {
-        let _response: AckResponse = self
-            .send_request(create_stream(request, transaction)?)
-            .await?;
-        Ok(())
+        let watched_transaction = Transaction::CreateStream(transaction.clone());
+        self.send_watched_request(create_stream(request, transaction)?, watched_transaction)
+            .await
     }
 
     pub async fn read_stream(
@@ -111,10 +235,9 @@ impl JanusClient {
         request: ReadStreamRequest,
         transaction: ReadStreamTransaction,
     ) -> anyhow::Result<()> {
-        let _response: AckResponse = self
-            .send_request(read_stream(request, transaction)?)
-            .await?;
-        Ok(())
+        let watched_transaction = Transaction::ReadStream(transaction.clone());
+        self.send_watched_request(read_stream(request, transaction)?, watched_transaction)
+            .await
     }
 
     pub async fn trickle_request(&self, request: TrickleRequest) -> anyhow::Result<()> {
@@ -138,14 +261,64 @@ impl JanusClient {
         Ok(response.data)
     }
 
-    async fn send_request<R: DeserializeOwned>(&self, body: impl Serialize) -> anyhow::Result<R> {
-        let body = serde_json::to_vec(&body)?;
-        let request = Request::post(self.janus_url.clone()).body(body)?;
-        let response = self.http.send_async(request).await?.text().await?;
+    // Sends a request whose result comes later as an `event` and lets the watchdog expire it
+    // if it doesn't. Registering goes before sending not to miss a fast event.
+    async fn send_watched_request<T: Serialize>(
+        &self,
+        request: JanusRequest<T>,
+        transaction: Transaction,
+    ) -> anyhow::Result<()> {
+        let transaction_id = request.transaction.clone();
+
+        if let Some(ref watchdog) = self.watchdog {
+            watchdog.register(&transaction_id, transaction);
+        }
+
+        let result = self.send_request::<AckResponse, _>(request).await;
+
+        if let (Err(_), Some(watchdog)) = (&result, &self.watchdog) {
+            watchdog.complete(&transaction_id);
+        }
+
+        result.map(|_response| ())
+    }
+
+    async fn send_request<R: DeserializeOwned, T: Serialize>(
+        &self,
+        body: JanusRequest<T>,
+    ) -> anyhow::Result<R> {
+        let response = match &self.transport {
+            Transport::Http(http) => {
+                let body = serde_json::to_vec(&body)?;
+                let request = Request::post(self.janus_url.clone()).body(body)?;
+                http.send_async(request).await?.text().await?
+            }
+            Transport::Ws(ws) => {
+                let transaction = body.transaction.clone();
+                let body = serde_json::to_string(&body)?;
+                ws.send(&transaction, body).await?
+            }
+        };
+
         Ok(serde_json::from_str(&response).context(response)?)
     }
 }
 
+// https://janus.conf.meetecho.com/docs/rest.html#errors
+const JANUS_ERROR_SESSION_NOT_FOUND: u64 = 458;
+
+fn session_request_result(response: serde_json::Value) -> anyhow::Result<PollResult> {
+    let error_code = response
+        .get("error")
+        .map(|error| error.get("code").and_then(|code| code.as_u64()));
+
+    match error_code {
+        None => Ok(PollResult::Events(vec![])),
+        Some(Some(JANUS_ERROR_SESSION_NOT_FOUND)) => Ok(PollResult::SessionNotFound),
+        Some(_) => Err(anyhow!("Janus responded with an error: {}", response)),
+    }
+}
+
 pub enum PollResult {
     SessionNotFound,
     Events(Vec<IncomingEvent>),
@@ -245,6 +418,10 @@ pub enum IncomingEvent {
     Detached(DetachedEvent),
     Event(EventResponse),
     KeepAlive,
+    #[serde(skip_deserializing)]
+    SessionReset(SessionResetEvent),
+    #[serde(skip_deserializing)]
+    TransactionTimedOut(Transaction),
 }
 
 #[derive(Deserialize, Debug)]
@@ -264,6 +441,11 @@ enum Success {
     Success,
 }
 
+#[derive(Deserialize, Debug)]
+struct SuccessResponse {
+    janus: Success,
+}
+
 #[derive(Deserialize, Debug)]
 struct JanusResponse<T> {
     data: T,
@@ -280,6 +462,20 @@ struct JanusRequest<T> {
     data: T,
 }
 
+#[derive(Serialize, Debug)]
+struct SessionRequest {
+    session_id: SessionId,
+}
+
+fn session_request(janus: &'static str, session_id: SessionId) -> JanusRequest<SessionRequest> {
+    JanusRequest {
+        transaction: Uuid::new_v4().to_string(),
+        janus,
+        plugin: None,
+        data: SessionRequest { session_id },
+    }
+}
+
 fn create_session() -> JanusRequest<()> {
     JanusRequest {
         transaction: Uuid::new_v4().to_string(),
@@ -307,6 +503,24 @@ fn trickle(request: TrickleRequest) -> JanusRequest<TrickleRequest> {
     }
 }
 
+fn hangup(request: HangupRequest) -> JanusRequest<HangupRequest> {
+    JanusRequest {
+        transaction: Uuid::new_v4().to_string(),
+        janus: "hangup",
+        plugin: None,
+        data: request,
+    }
+}
+
+fn detach(request: DetachRequest) -> JanusRequest<DetachRequest> {
+    JanusRequest {
+        transaction: Uuid::new_v4().to_string(),
+        janus: "detach",
+        plugin: None,
+        data: request,
+    }
+}
+
 fn read_stream(
     request: ReadStreamRequest,
     transaction: ReadStreamTransaction,
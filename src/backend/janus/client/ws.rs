@@ -0,0 +1,431 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use async_tungstenite::tungstenite::{
+    client::IntoClientRequest,
+    http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue},
+    Message,
+};
+use crossbeam_channel::Sender;
+use futures::{FutureExt, SinkExt, StreamExt};
+use futures_channel::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+use serde::Deserialize;
+use slog::{error, warn};
+
+use super::IncomingEvent;
+use crate::backend::janus::{client_pool::Backoff, transaction_watchdog::TransactionWatchdog};
+
+// Janus requires this subprotocol to be negotiated on the WebSocket handshake.
+const JANUS_PROTOCOL: &str = "janus-protocol";
+
+// Responses are correlated with requests by the `transaction` field. Some transactions
+// (like `AgentLeave`) always serialize to the same string, so several requests may wait
+// for the same key. Janus replies in the order of requests so the queue is FIFO.
+// Waiters are tagged with request ids to be able to withdraw a particular one.
+type PendingRequests = Arc<Mutex<HashMap<String, VecDeque<(u64, oneshot::Sender<String>)>>>>;
+
+#[derive(Debug)]
+pub struct WsTransport {
+    outgoing: UnboundedSender<Message>,
+    pending: PendingRequests,
+    next_request_id: AtomicU64,
+    connections_count: Arc<AtomicU64>,
+}
+
+impl WsTransport {
+    /// Spawns a task maintaining the WebSocket connection to `janus_url`.
+    ///
+    /// Messages that are not responses to sent requests are pushed to `events_sink`.
+    /// The connection is re-established when lost and closed when the transport is dropped.
+    pub fn new(
+        janus_url: &str,
+        events_sink: Option<Sender<IncomingEvent>>,
+        watchdog: Option<TransactionWatchdog>,
+    ) -> Self {
+        let (outgoing, outgoing_rx) = mpsc::unbounded();
+        let pending = PendingRequests::default();
+        let connections_count = Arc::new(AtomicU64::new(0));
+
+        async_std::task::spawn(run(
+            janus_url.to_owned(),
+            outgoing_rx,
+            pending.clone(),
+            connections_count.clone(),
+            events_sink,
+            watchdog,
+        ));
+
+        Self {
+            outgoing,
+            pending,
+            next_request_id: AtomicU64::new(0),
+            connections_count,
+        }
+    }
+
+    /// The number of WebSocket connections established so far.
+    ///
+    /// Janus binds sessions to the connection which has created or claimed them
+    /// so the change of the number means that the sessions have to be claimed again.
+    pub fn connections_count(&self) -> u64 {
+        self.connections_count.load(Ordering::SeqCst)
+    }
+
+    pub async fn send(&self, transaction: &str, body: String) -> anyhow::Result<String> {
+        let (tx, rx) = oneshot::channel();
+        let request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+
+        self.pending
+            .lock()
+            .expect("Must not panic")
+            .entry(transaction.to_owned())
+            .or_default()
+            .push_back((request_id, tx));
+
+        if self.outgoing.unbounded_send(Message::Text(body)).is_err() {
+            // Other requests with the same transaction are still waiting for their responses.
+            let mut guard = self.pending.lock().expect("Must not panic");
+
+            if let Some(queue) = guard.get_mut(transaction) {
+                queue.retain(|(id, _)| *id != request_id);
+
+                if queue.is_empty() {
+                    guard.remove(transaction);
+                }
+            }
+
+            return Err(anyhow!("Janus WebSocket connection is closed"));
+        }
+
+        rx.await
+            .context("Janus WebSocket connection closed before the response")
+    }
+}
+
+#[derive(Deserialize)]
+struct IncomingMessage<'a> {
+    janus: &'a str,
+    transaction: Option<&'a str>,
+}
+
+async fn run(
+    janus_url: String,
+    mut outgoing: UnboundedReceiver<Message>,
+    pending: PendingRequests,
+    connections_count: Arc<AtomicU64>,
+    events_sink: Option<Sender<IncomingEvent>>,
+    watchdog: Option<TransactionWatchdog>,
+) {
+    let mut backoff = Backoff::new();
+
+    loop {
+        match connect(&janus_url).await {
+            Ok(ws) => {
+                backoff.reset();
+                connections_count.fetch_add(1, Ordering::SeqCst);
+
+                let is_finished = serve(
+                    ws,
+                    &janus_url,
+                    &mut outgoing,
+                    &pending,
+                    events_sink.as_ref(),
+                    watchdog.as_ref(),
+                )
+                .await;
+
+                if is_finished {
+                    return;
+                }
+            }
+            Err(err) => {
+                error!(
+                    crate::LOG,
+                    "Failed to connect to janus at {}: {:?}", janus_url, err
+                );
+            }
+        }
+
+        // Dropping the senders makes all the waiting requests fail.
+        pending.lock().expect("Must not panic").clear();
+
+        if !wait_reconnect(&mut outgoing, &pending, backoff.next_delay()).await {
+            return;
+        }
+    }
+}
+
+// Exchanges messages until the connection is lost or all clients are dropped.
+// Returns `true` in the latter case.
+async fn serve(
+    ws: WebSocket,
+    janus_url: &str,
+    outgoing: &mut UnboundedReceiver<Message>,
+    pending: &PendingRequests,
+    events_sink: Option<&Sender<IncomingEvent>>,
+    watchdog: Option<&TransactionWatchdog>,
+) -> bool {
+    let (mut ws_tx, mut ws_rx) = ws.split();
+
+    let is_finished = loop {
+        futures::select! {
+            message = outgoing.next().fuse() => match message {
+                Some(message) => {
+                    if let Err(err) = ws_tx.send(message).await {
+                        error!(crate::LOG, "Failed to send a message to janus at {}: {:?}", janus_url, err);
+                        break false;
+                    }
+                }
+                // All clients have been dropped.
+                None => break true,
+            },
+            message = ws_rx.next().fuse() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    handle_message(text, pending, events_sink, watchdog);
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    warn!(crate::LOG, "Janus at {} closed the WebSocket connection", janus_url);
+                    break false;
+                }
+                Some(Ok(_)) => (),
+                Some(Err(err)) => {
+                    error!(crate::LOG, "WebSocket error on janus at {}: {:?}", janus_url, err);
+                    break false;
+                }
+            },
+        }
+    };
+
+    let _ = ws_tx.close().await;
+    is_finished
+}
+
+// Waits before reconnecting, failing requests that are made meanwhile.
+// Returns `false` if all clients have been dropped so there's no need to reconnect.
+async fn wait_reconnect(
+    outgoing: &mut UnboundedReceiver<Message>,
+    pending: &PendingRequests,
+    delay: Duration,
+) -> bool {
+    let sleep = async_std::task::sleep(delay).fuse();
+    futures::pin_mut!(sleep);
+
+    loop {
+        futures::select! {
+            () = sleep => return true,
+            message = outgoing.next().fuse() => match message {
+                Some(_) => pending.lock().expect("Must not panic").clear(),
+                None => return false,
+            },
+        }
+    }
+}
+
+type WebSocket = async_tungstenite::WebSocketStream<async_tungstenite::async_std::ConnectStream>;
+
+async fn connect(janus_url: &str) -> anyhow::Result<WebSocket> {
+    let mut request = janus_url.into_client_request()?;
+
+    request.headers_mut().insert(
+        SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static(JANUS_PROTOCOL),
+    );
+
+    let (ws, _response) = async_tungstenite::async_std::connect_async(request).await?;
+    Ok(ws)
+}
+
+fn handle_message(
+    text: String,
+    pending: &PendingRequests,
+    events_sink: Option<&Sender<IncomingEvent>>,
+    watchdog: Option<&TransactionWatchdog>,
+) {
+    let message = match serde_json::from_str::<IncomingMessage>(&text) {
+        Ok(message) => message,
+        Err(err) => {
+            error!(
+                crate::LOG,
+                "Failed to parse a message from janus: {:?}, message = {}", err, text
+            );
+
+            return;
+        }
+    };
+
+    // Synchronous responses to requests. Plugin `event`s come asynchronously with
+    // the same transaction after the `ack` so they go to the events sink.
+    let is_response = matches!(message.janus, "ack" | "success" | "error");
+
+    if let (true, Some(transaction)) = (is_response, message.transaction) {
+        let maybe_waiter = {
+            let mut guard = pending.lock().expect("Must not panic");
+
+            match guard.get_mut(transaction) {
+                Some(queue) => {
+                    let waiter = queue.pop_front().map(|(_id, waiter)| waiter);
+
+                    if queue.is_empty() {
+                        guard.remove(transaction);
+                    }
+
+                    waiter
+                }
+                None => None,
+            }
+        };
+
+        if let Some(waiter) = maybe_waiter {
+            let _ = waiter.send(text);
+            return;
+        }
+    }
+
+    if let (Some(watchdog), "event", Some(transaction)) =
+        (watchdog, message.janus, message.transaction)
+    {
+        if !watchdog.complete(transaction) {
+            warn!(
+                crate::LOG,
+                "Dropping a late event from janus for an expired transaction: {}", text
+            );
+
+            return;
+        }
+    }
+
+    if let Some(sink) = events_sink {
+        match serde_json::from_str::<IncomingEvent>(&text) {
+            Ok(event) => sink.send(event).expect("Receiver must exist"),
+            Err(err) => error!(
+                crate::LOG,
+                "Failed to parse an event from janus: {:?}, event = {}", err, text
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn correlate_responses_with_requests() {
+        let pending = PendingRequests::default();
+        let (first_tx, first_rx) = oneshot::channel();
+        let (second_tx, second_rx) = oneshot::channel();
+
+        {
+            let mut guard = pending.lock().unwrap();
+            let queue = guard.entry("txn".to_owned()).or_default();
+            queue.push_back((0, first_tx));
+            queue.push_back((1, second_tx));
+        }
+
+        let (events_tx, events_rx) = crossbeam_channel::unbounded();
+        let first = r#"{"janus": "ack", "transaction": "txn", "session_id": 1}"#;
+        let second = r#"{"janus": "success", "transaction": "txn", "data": {"id": 2}}"#;
+        handle_message(first.to_owned(), &pending, Some(&events_tx), None);
+        handle_message(second.to_owned(), &pending, Some(&events_tx), None);
+
+        assert_eq!(first_rx.await.unwrap(), first);
+        assert_eq!(second_rx.await.unwrap(), second);
+        assert!(pending.lock().unwrap().is_empty());
+        assert!(events_rx.try_recv().is_err());
+    }
+
+    #[async_std::test]
+    async fn failed_send_withdraws_only_its_waiter() {
+        let (outgoing, outgoing_rx) = mpsc::unbounded();
+        drop(outgoing_rx);
+
+        let transport = WsTransport {
+            outgoing,
+            pending: PendingRequests::default(),
+            next_request_id: AtomicU64::new(1),
+            connections_count: Arc::new(AtomicU64::new(0)),
+        };
+
+        let (queued_tx, _queued_rx) = oneshot::channel();
+
+        transport
+            .pending
+            .lock()
+            .unwrap()
+            .entry("txn".to_owned())
+            .or_default()
+            .push_back((0, queued_tx));
+
+        transport
+            .send("txn", String::from("{}"))
+            .await
+            .expect_err("Unexpected send success");
+
+        let guard = transport.pending.lock().unwrap();
+        let queue = guard.get("txn").expect("Queued waiter has been dropped");
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].0, 0);
+    }
+
+    #[async_std::test]
+    async fn count_connections_on_reconnect() {
+        let listener = async_std::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind listener");
+
+        let janus_url = format!("ws://{}", listener.local_addr().unwrap());
+
+        // Close every connection right after the handshake to make the transport reconnect.
+        async_std::task::spawn(async move {
+            let mut incoming = listener.incoming();
+
+            while let Some(Ok(stream)) = incoming.next().await {
+                if let Ok(mut ws) = async_tungstenite::accept_async(stream).await {
+                    let _ = ws.close(None).await;
+                }
+            }
+        });
+
+        let transport = WsTransport::new(&janus_url, None, None);
+
+        for _ in 0..100 {
+            if transport.connections_count() >= 2 {
+                return;
+            }
+
+            async_std::task::sleep(Duration::from_millis(50)).await;
+        }
+
+        panic!("Transport hasn't reconnected");
+    }
+
+    #[test]
+    fn push_events_to_sink() {
+        let pending = PendingRequests::default();
+        let (events_tx, events_rx) = crossbeam_channel::unbounded();
+
+        let event = r#"{
+            "janus": "webrtcup",
+            "session_id": 1,
+            "sender": 2,
+            "opaque_id": "3"
+        }"#;
+
+        handle_message(event.to_owned(), &pending, Some(&events_tx), None);
+
+        match events_rx.try_recv() {
+            Ok(IncomingEvent::WebRtcUp(event)) => assert_eq!(event.opaque_id, "3"),
+            other => panic!("Expected webrtcup event, got {:?}", other),
+        }
+    }
+}
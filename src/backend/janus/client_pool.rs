@@ -1,6 +1,14 @@
-use super::client::{IncomingEvent, JanusClient, PollResult, SessionId};
-use crate::db::janus_backend;
+use super::{
+    client::{
+        create_handle::CreateHandleRequest, events::SessionResetEvent, HandleId, IncomingEvent,
+        JanusClient, PollResult, SessionId,
+    },
+    metrics::PollerMetrics,
+    transaction_watchdog::TransactionWatchdog,
+};
+use crate::db::{self, janus_backend};
 use crossbeam_channel::Sender;
+use rand::Rng;
 use slog::{error, warn};
 use std::{
     collections::{hash_map::Entry, HashMap},
@@ -12,19 +20,33 @@ use std::{
 };
 use svc_agent::AgentId;
 
+// Janus tears down sessions with no activity for 60 seconds by default.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(25);
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Clone)]
 pub struct Clients {
     clients: Arc<RwLock<HashMap<AgentId, ClientHandle>>>,
     events_sink: Sender<IncomingEvent>,
     group: Option<String>,
+    metrics: PollerMetrics,
+    watchdog: TransactionWatchdog,
 }
 
 impl Clients {
-    pub fn new(events_sink: Sender<IncomingEvent>, group: Option<String>) -> Self {
+    pub fn new(
+        events_sink: Sender<IncomingEvent>,
+        group: Option<String>,
+        metrics: PollerMetrics,
+        watchdog: TransactionWatchdog,
+    ) -> Self {
         Self {
             clients: Arc::new(RwLock::new(HashMap::new())),
             events_sink,
             group,
+            metrics,
+            watchdog,
         }
     }
 
@@ -45,25 +67,44 @@ impl Clients {
             Entry::Occupied(o) => Ok(o.get().client.clone()),
             Entry::Vacant(v) => {
                 let this = self.clone();
-                let client = JanusClient::new(backend.janus_url())?;
+                // Only the instance of the backend's group handles its events. Transactions
+                // sent to other groups' backends are not watched: their events never come
+                // to this instance so the watchdog would time out the successful ones too.
+                let is_events_receiver = self.group.as_deref() == backend.group();
+                let client = if is_events_receiver {
+                    JanusClient::new_events_receiver(
+                        backend.janus_url(),
+                        self.events_sink.clone(),
+                        self.watchdog.clone(),
+                    )?
+                } else {
+                    JanusClient::new(backend.janus_url())?
+                };
                 let session_id = backend.session_id();
                 let agent_id = backend.id().clone();
+                let janus_url = backend.janus_url().to_owned();
                 let is_cancelled = Arc::new(AtomicBool::new(false));
                 v.insert(ClientHandle {
                     client: client.clone(),
                     is_cancelled: is_cancelled.clone(),
                 });
-                if self.group.as_deref() == backend.group() {
-                    let agent = backend.id().clone();
+                if is_events_receiver {
                     async_std::task::spawn({
                         let client = client.clone();
                         async move {
-                            let sink = this.events_sink.clone();
+                            let poller = Poller {
+                                janus_client: client,
+                                session_id,
+                                janus_url,
+                                sink: this.events_sink.clone(),
+                                metrics: this.metrics.clone(),
+                                agent: agent_id.clone(),
+                            };
                             let _guard = PollerGuard {
                                 clients: &this,
                                 agent_id: &agent_id,
                             };
-                            start_polling(client, session_id, sink, &is_cancelled, agent).await;
+                            poller.start(&is_cancelled).await;
                         }
                     });
                 }
@@ -105,40 +146,238 @@ impl<'a> Drop for PollerGuard<'a> {
     }
 }
 
-async fn start_polling(
+struct Poller {
     janus_client: JanusClient,
     session_id: SessionId,
+    janus_url: String,
     sink: Sender<IncomingEvent>,
-    is_cancelled: &AtomicBool,
+    metrics: PollerMetrics,
     agent: AgentId,
-) {
-    let mut retries_count = 5;
-    loop {
-        if retries_count == 0 {
-            break;
-        }
-        if is_cancelled.load(Ordering::SeqCst) {
-            break;
-        }
-        let poll_result = janus_client.poll(session_id).await;
-        match poll_result {
-            Ok(PollResult::SessionNotFound) => {
-                warn!(
-                    crate::LOG,
-                    "Session {} not found on agent {}", session_id, agent
-                );
+}
+
+impl Poller {
+    // Over WebSocket events are pushed by Janus so instead of polling
+    // we only need to own the session and keep it alive.
+    async fn start(mut self, is_cancelled: &AtomicBool) {
+        let is_websocket = self.janus_client.is_websocket();
+        // The number of the WebSocket connection which has claimed the session.
+        let mut claimed_by = None;
+
+        let mut backoff = Backoff::new();
+        loop {
+            if is_cancelled.load(Ordering::SeqCst) {
                 break;
             }
-            Ok(PollResult::Events(events)) => {
-                for event in events {
-                    sink.send(event).expect("Receiver must exist");
+            // The counter is read before claiming so a reconnect in the middle
+            // leads to claiming once more rather than not claiming at all.
+            let connections_count = self.janus_client.connections_count();
+            if is_websocket && claimed_by != connections_count {
+                match self.claim_session().await {
+                    Ok(()) => claimed_by = connections_count,
+                    Err(err) => {
+                        error!(
+                            crate::LOG,
+                            "Failed to claim session on agent {}: {:?}", self.agent, err
+                        );
+                        self.metrics.observe_poll_failure(&self.agent);
+                        async_std::task::sleep(backoff.next_delay()).await;
+                        continue;
+                    }
                 }
             }
-            Err(err) => {
-                error!(crate::LOG, "Polling error for {}: {:?}", session_id, err);
-                async_std::task::sleep(Duration::from_millis(500)).await;
-                retries_count -= 1;
+            let poll_result = if is_websocket {
+                self.janus_client.keep_alive(self.session_id).await
+            } else {
+                self.janus_client.poll(self.session_id).await
+            };
+            match poll_result {
+                Ok(PollResult::SessionNotFound) => {
+                    warn!(
+                        crate::LOG,
+                        "Session {} not found on agent {}", self.session_id, self.agent
+                    );
+                    if let Err(err) = self.reset_session().await {
+                        error!(
+                            crate::LOG,
+                            "Failed to re-create session on agent {}: {:?}", self.agent, err
+                        );
+                        self.metrics.observe_poll_failure(&self.agent);
+                        async_std::task::sleep(backoff.next_delay()).await;
+                    } else {
+                        backoff.reset();
+                    }
+                }
+                Ok(PollResult::Events(events)) => {
+                    backoff.reset();
+                    for event in events {
+                        self.sink.send(event).expect("Receiver must exist");
+                    }
+                    if is_websocket {
+                        async_std::task::sleep(KEEP_ALIVE_INTERVAL).await;
+                    }
+                }
+                Err(err) => {
+                    error!(
+                        crate::LOG,
+                        "Polling error for {}: {:?}", self.session_id, err
+                    );
+                    self.metrics.observe_poll_failure(&self.agent);
+                    async_std::task::sleep(backoff.next_delay()).await;
+                }
             }
         }
     }
+
+    // Claims the session on the current WebSocket connection. The session may be gone
+    // when the connection has been lost for too long so it gets re-created then.
+    async fn claim_session(&mut self) -> anyhow::Result<()> {
+        match self.janus_client.claim_session(self.session_id).await {
+            Ok(PollResult::Events(_)) => return Ok(()),
+            Ok(PollResult::SessionNotFound) => warn!(
+                crate::LOG,
+                "Session {} not found on agent {}", self.session_id, self.agent
+            ),
+            Err(err) => warn!(
+                crate::LOG,
+                "Failed to claim session {} on agent {}: {:?}", self.session_id, self.agent, err
+            ),
+        }
+
+        self.reset_session().await
+    }
+
+    async fn reset_session(&mut self) -> anyhow::Result<()> {
+        let session = self.janus_client.create_session().await?;
+        let handle = self
+            .janus_client
+            .create_handle(CreateHandleRequest {
+                session_id: session.id,
+                opaque_id: db::janus_rtc_stream::Id::random(),
+            })
+            .await?;
+
+        self.session_id = session.id;
+        self.metrics.observe_session_reset(&self.agent);
+        self.notify_session_reset(session.id, handle.id);
+        Ok(())
+    }
+
+    // Updating the backend and cleaning up lost streams needs the DB
+    // so it's up to the events handler.
+    fn notify_session_reset(&self, session_id: SessionId, handle_id: HandleId) {
+        let event = SessionResetEvent {
+            backend_id: self.agent.clone(),
+            janus_url: self.janus_url.clone(),
+            session_id,
+            handle_id,
+        };
+
+        self.sink
+            .send(IncomingEvent::SessionReset(event))
+            .expect("Receiver must exist");
+    }
+}
+
+// Exponential backoff with equal jitter: a random delay between the half
+// and the full exponentially growing value.
+pub(super) struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    pub(super) fn new() -> Self {
+        Self { attempt: 0 }
+    }
+
+    pub(super) fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    pub(super) fn next_delay(&mut self) -> Duration {
+        let delay = MIN_RETRY_DELAY
+            .checked_mul(2u32.saturating_pow(self.attempt))
+            .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY));
+
+        self.attempt = self.attempt.saturating_add(1);
+        let half = delay / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+    #[async_std::test]
+    async fn watch_transactions_of_own_group_backends_only() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let db = TestDb::with_local_postgres(&postgres);
+
+        let (own_backend, foreign_backend) = {
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let own_backend = shared_helpers::insert_janus_backend(
+                &conn,
+                "http://localhost:8088/janus",
+                SessionId::random(),
+                HandleId::random(),
+            );
+
+            let foreign_agent = TestAgent::new("alpha", "janus-gateway-foreign", SVC_AUDIENCE);
+
+            let foreign_backend = factory::JanusBackend::new(
+                foreign_agent.agent_id().to_owned(),
+                HandleId::random(),
+                SessionId::random(),
+                "http://localhost:8088/janus".to_owned(),
+            )
+            .group("foreign")
+            .insert(&conn);
+
+            (own_backend, foreign_backend)
+        };
+
+        // The test context's instance has no group just like the own backend.
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let (events_tx, _events_rx) = crossbeam_channel::unbounded();
+        context.with_janus(events_tx);
+        let clients = context.janus_clients();
+
+        let own_client = clients
+            .get_or_insert(&own_backend)
+            .expect("Failed to get client");
+
+        let foreign_client = clients
+            .get_or_insert(&foreign_backend)
+            .expect("Failed to get client");
+
+        assert!(own_client.is_watched());
+        assert!(!foreign_client.is_watched());
+
+        clients.remove_client(own_backend.id());
+        clients.remove_client(foreign_backend.id());
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_limit() {
+        let mut backoff = Backoff::new();
+
+        for attempt in 0..20 {
+            let expected = MIN_RETRY_DELAY
+                .checked_mul(2u32.saturating_pow(attempt))
+                .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY));
+
+            let delay = backoff.next_delay();
+            assert!(delay >= expected / 2);
+            assert!(delay <= expected);
+        }
+
+        backoff.reset();
+        assert!(backoff.next_delay() <= MIN_RETRY_DELAY);
+    }
 }
@@ -1,8 +1,9 @@
-use std::time::Duration;
+use std::{collections::HashSet, fmt, time::Duration};
 
 use anyhow::Context;
-use prometheus::{IntGauge, IntGaugeVec, Opts, Registry};
-use slog::error;
+use prometheus::{IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry};
+use slog::{error, info};
+use svc_agent::AgentId;
 
 use crate::db::{agent_connection, ConnectionPool};
 
@@ -11,6 +12,8 @@ pub struct Metrics {
     total: IntGauge,
     connected_agents: IntGauge,
     load: IntGaugeVec,
+    draining_active_rooms: IntGaugeVec,
+    drained_backends: HashSet<AgentId>,
 }
 
 impl Metrics {
@@ -27,17 +30,27 @@ impl Metrics {
             Opts::new("janus_load", "Janus load metrics"),
             &["kind", "agent"],
         )?;
+        let draining_active_rooms = IntGaugeVec::new(
+            Opts::new(
+                "janus_draining_active_rooms",
+                "Active rooms left on draining janus backends",
+            ),
+            &["agent"],
+        )?;
         registry.register(Box::new(janus_basic_metrics))?;
         registry.register(Box::new(load.clone()))?;
+        registry.register(Box::new(draining_active_rooms.clone()))?;
         Ok(Self {
             online,
             total,
             connected_agents,
             load,
+            draining_active_rooms,
+            drained_backends: HashSet::new(),
         })
     }
 
-    pub fn start_collector(self, connection_pool: ConnectionPool, collect_interval: Duration) {
+    pub fn start_collector(mut self, connection_pool: ConnectionPool, collect_interval: Duration) {
         loop {
             if let Err(err) = self.collect(&connection_pool) {
                 error!(crate::LOG, "Janus' metrics collecting errored: {:?}", err);
@@ -46,7 +59,7 @@ impl Metrics {
         }
     }
 
-    fn collect(&self, pool: &ConnectionPool) -> anyhow::Result<()> {
+    fn collect(&mut self, pool: &ConnectionPool) -> anyhow::Result<()> {
         let conn = pool.get()?;
 
         let online_backends_count =
@@ -75,6 +88,81 @@ impl Metrics {
             agent_load.set(backend_load.taken);
         }
 
+        let draining_backends = crate::db::janus_backend::draining_backends_rooms(&conn)
+            .context("Failed to get draining janus backends rooms")?;
+        self.draining_active_rooms.reset();
+        let mut drained_backends = HashSet::with_capacity(draining_backends.len());
+        for backend in draining_backends {
+            self.draining_active_rooms
+                .get_metric_with_label_values(&[backend.backend_id.label()])?
+                .set(backend.active_rooms);
+
+            if backend.active_rooms == 0 {
+                if !self.drained_backends.contains(&backend.backend_id) {
+                    info!(
+                        crate::LOG,
+                        "Draining janus backend {} has no active rooms left, it's safe to stop it",
+                        backend.backend_id
+                    );
+                }
+
+                drained_backends.insert(backend.backend_id);
+            }
+        }
+        self.drained_backends = drained_backends;
+
         Ok(())
     }
 }
+
+#[derive(Clone)]
+pub struct PollerMetrics {
+    poll_failures: IntCounterVec,
+    session_resets: IntCounterVec,
+}
+
+impl PollerMetrics {
+    pub fn new(registry: &Registry) -> anyhow::Result<Self> {
+        let poll_failures = IntCounterVec::new(
+            Opts::new("janus_poll_failures", "Janus polling failures"),
+            &["agent"],
+        )?;
+        let session_resets = IntCounterVec::new(
+            Opts::new(
+                "janus_session_resets",
+                "Janus sessions re-created by the poller",
+            ),
+            &["agent"],
+        )?;
+        registry.register(Box::new(poll_failures.clone()))?;
+        registry.register(Box::new(session_resets.clone()))?;
+        Ok(Self {
+            poll_failures,
+            session_resets,
+        })
+    }
+
+    pub fn observe_poll_failure(&self, backend_id: &AgentId) {
+        if let Ok(counter) = self
+            .poll_failures
+            .get_metric_with_label_values(&[backend_id.label()])
+        {
+            counter.inc();
+        }
+    }
+
+    pub fn observe_session_reset(&self, backend_id: &AgentId) {
+        if let Ok(counter) = self
+            .session_resets
+            .get_metric_with_label_values(&[backend_id.label()])
+        {
+            counter.inc();
+        }
+    }
+}
+
+impl fmt::Debug for PollerMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PollerMetrics").finish()
+    }
+}
@@ -8,7 +8,7 @@ use svc_agent::{
         IncomingEvent as MQTTIncomingEvent, IncomingRequestProperties, IntoPublishableMessage,
         OutgoingResponse, ResponseStatus, ShortTermTimingProperties,
     },
-    Addressable,
+    Addressable, AgentId,
 };
 use svc_error::Error as SvcError;
 
@@ -22,13 +22,20 @@ use crate::{
         API_VERSION,
     },
     backend::janus::client::{create_handle::CreateHandleRequest, JanusClient},
-    db::{self, agent_connection, janus_backend, janus_rtc_stream, recording, room, rtc},
-    diesel::Connection,
+    db::{
+        self, agent_connection, agent_session, janus_backend, janus_rtc_stream, recording, room,
+        rtc,
+    },
+    diesel::{pg::PgConnection, Connection},
 };
 
 use serde::Deserialize;
 
-use self::client::{transactions::Transaction, IncomingEvent};
+use self::client::{
+    events::{MediaEvent, SessionResetEvent, SlowLinkEvent, TimeoutEvent},
+    transactions::Transaction,
+    IncomingEvent,
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -121,10 +128,9 @@ async fn handle_event_impl<C: Context>(
         }
         IncomingEvent::HangUp(ref inev) => handle_hangup_detach(context, inev).await,
         IncomingEvent::Detached(ref inev) => handle_hangup_detach(context, inev).await,
-        IncomingEvent::Media(_) | IncomingEvent::Timeout(_) | IncomingEvent::SlowLink(_) => {
-            // Ignore these kinds of events.
-            Ok(Box::new(stream::empty()))
-        }
+        IncomingEvent::SlowLink(ref inev) => handle_slow_link(context, inev).await,
+        IncomingEvent::Media(ref inev) => handle_media(context, inev).await,
+        IncomingEvent::Timeout(ref inev) => handle_timeout(context, inev).await,
         IncomingEvent::Event(resp) => {
             match resp.transaction {
                 Transaction::AgentLeave => Ok(Box::new(stream::empty())),
@@ -327,9 +333,10 @@ async fn handle_event_impl<C: Context>(
                             })
                             .transpose()?;
 
-                        let (room, rtcs_with_recs): (
+                        let (room, rtcs_with_recs, sessions): (
                             room::Object,
                             Vec<(rtc::Object, Option<recording::Object>)>,
+                            Vec<agent_session::Object>,
                         ) = {
                             let conn = context.get_conn().await?;
                             task::spawn_blocking(move || {
@@ -355,7 +362,10 @@ async fn handle_event_impl<C: Context>(
                                 let rtcs_with_recs =
                                     rtc::ListWithRecordingQuery::new(room.id()).execute(&conn)?;
 
-                                Ok::<_, AppError>((room, rtcs_with_recs))
+                                let sessions =
+                                    agent_session::ListQuery::new(room.id()).execute(&conn)?;
+
+                                Ok::<_, AppError>((room, rtcs_with_recs, sessions))
                             })
                             .await?
                         };
@@ -380,6 +390,7 @@ async fn handle_event_impl<C: Context>(
                             context,
                             &room,
                             recs_with_rtcs.into_iter(),
+                            &sessions,
                         )?;
 
                         let event_box = Box::new(event) as Box<dyn IntoPublishableMessage + Send>;
@@ -397,9 +408,308 @@ async fn handle_event_impl<C: Context>(
             }
         }
         IncomingEvent::KeepAlive => Ok(Box::new(stream::empty())),
+        IncomingEvent::SessionReset(ref inev) => handle_session_reset(context, inev).await,
+        IncomingEvent::TransactionTimedOut(transaction) => {
+            handle_transaction_timeout(context, transaction).await
+        }
     }
 }
 
+async fn handle_transaction_timeout<C: Context>(
+    context: &mut C,
+    transaction: Transaction,
+) -> Result<MessageStream, AppError> {
+    match transaction {
+        Transaction::CreateStream(tn) => {
+            context.add_logger_tags(o!("method" => tn.reqp.method().to_string()));
+
+            let app_error = AppError::new(
+                AppErrorKind::BackendRequestTimedOut,
+                anyhow!("Janus hasn't responded to stream creation in time"),
+            );
+
+            Ok(handle_response_error(context, &tn.reqp, app_error))
+        }
+        Transaction::ReadStream(tn) => {
+            context.add_logger_tags(o!("method" => tn.reqp.method().to_string()));
+
+            let app_error = AppError::new(
+                AppErrorKind::BackendRequestTimedOut,
+                anyhow!("Janus hasn't responded to stream reading in time"),
+            );
+
+            Ok(handle_response_error(context, &tn.reqp, app_error))
+        }
+        Transaction::UploadStream(tn) => {
+            context.add_logger_tags(o!("rtc_id" => tn.rtc_id.to_string()));
+
+            let app_error = AppError::new(
+                AppErrorKind::BackendRequestTimedOut,
+                anyhow!("Janus hasn't confirmed stream uploading in time"),
+            );
+
+            error!(context.logger(), "{}", app_error.source());
+            app_error.notify_sentry(context.logger());
+
+            // Consider the recording missing so that the room's upload could be finished.
+            let conn = context.get_conn().await?;
+            let rtc_id = tn.rtc_id;
+
+            let maybe_room_with_recs = task::spawn_blocking(move || {
+                conn.transaction::<_, AppError, _>(|| {
+                    // The upload could have been confirmed in the meantime.
+                    match recording::FindQuery::new(rtc_id).execute(&conn)? {
+                        Some(rec) if *rec.status() == recording::Status::InProgress => (),
+                        _ => return Ok(None),
+                    }
+
+                    recording::UpdateQuery::new(rtc_id)
+                        .status(recording::Status::Missing)
+                        .execute(&conn)?;
+
+                    let room = endpoint::helpers::find_room_by_rtc_id(
+                        rtc_id,
+                        endpoint::helpers::RoomTimeRequirement::Any,
+                        &conn,
+                    )?;
+
+                    let rtcs_with_recs =
+                        rtc::ListWithRecordingQuery::new(room.id()).execute(&conn)?;
+
+                    let sessions = agent_session::ListQuery::new(room.id()).execute(&conn)?;
+                    Ok(Some((room, rtcs_with_recs, sessions)))
+                })
+            })
+            .await?;
+
+            let (room, rtcs_with_recs, sessions) = match maybe_room_with_recs {
+                Some(room_with_recs) => room_with_recs,
+                None => return Ok(Box::new(stream::empty())),
+            };
+
+            endpoint::helpers::add_room_logger_tags(context, &room);
+
+            // Send room.upload event only when all the room's recordings are finished.
+            let rtcs_total = rtcs_with_recs.len();
+
+            let recs_with_rtcs = rtcs_with_recs
+                .into_iter()
+                .filter_map(|(rtc, maybe_recording)| {
+                    maybe_recording
+                        .filter(|recording| *recording.status() != recording::Status::InProgress)
+                        .map(|recording| (recording, rtc))
+                })
+                .collect::<Vec<_>>();
+
+            if recs_with_rtcs.len() < rtcs_total {
+                return Ok(Box::new(stream::empty()));
+            }
+
+            let event = endpoint::system::upload_event(
+                context,
+                &room,
+                recs_with_rtcs.into_iter(),
+                &sessions,
+            )?;
+            let event_box = Box::new(event) as Box<dyn IntoPublishableMessage + Send>;
+            Ok(Box::new(stream::once(event_box)))
+        }
+        Transaction::AgentLeave
+        | Transaction::UpdateReaderConfig
+        | Transaction::UpdateWriterConfig => Ok(Box::new(stream::empty())),
+    }
+}
+
+async fn handle_session_reset<C: Context>(
+    context: &mut C,
+    inev: &SessionResetEvent,
+) -> Result<MessageStream, AppError> {
+    context.add_logger_tags(o!("backend_id" => inev.backend_id.to_string()));
+
+    let conn = context.get_conn().await?;
+    let backend_id = inev.backend_id.clone();
+    let janus_url = inev.janus_url.clone();
+    let session_id = inev.session_id;
+    let handle_id = inev.handle_id;
+
+    // All handles of the old session are gone so the streams on the backend are lost.
+    let stopped_streams_with_rtc = task::spawn_blocking(move || {
+        conn.transaction::<_, AppError, _>(|| {
+            // The backend could have gone offline in the meantime.
+            if janus_backend::FindQuery::new()
+                .id(&backend_id)
+                .execute(&conn)?
+                .is_none()
+            {
+                return Ok(vec![]);
+            }
+
+            janus_backend::UpsertQuery::new(&backend_id, handle_id, session_id, &janus_url)
+                .execute(&conn)?;
+
+            let stopped_streams_with_rtc = stop_backend_streams(&backend_id, &conn)?;
+            agent_connection::BulkDisconnectByBackendQuery::new(&backend_id).execute(&conn)?;
+            Ok(stopped_streams_with_rtc)
+        })
+    })
+    .await?;
+
+    // Notify rooms so that clients could reconnect.
+    stopped_streams_events(context, stopped_streams_with_rtc)
+}
+
+// Janus has torn down the session so all of its handles are gone.
+async fn handle_timeout<C: Context>(
+    context: &mut C,
+    inev: &TimeoutEvent,
+) -> Result<MessageStream, AppError> {
+    context.add_logger_tags(o!("session_id" => inev.session_id.to_string()));
+
+    let conn = context.get_conn().await?;
+    let session_id = inev.session_id;
+
+    let stopped_streams_with_rtc = task::spawn_blocking(move || {
+        conn.transaction::<_, AppError, _>(|| {
+            let backend = match janus_backend::FindQuery::new()
+                .session_id(session_id)
+                .execute(&conn)?
+            {
+                Some(backend) => backend,
+                None => return Ok(vec![]),
+            };
+
+            let stopped_streams_with_rtc = stop_backend_streams(backend.id(), &conn)?;
+            agent_connection::BulkDisconnectByBackendQuery::new(backend.id()).execute(&conn)?;
+            Ok(stopped_streams_with_rtc)
+        })
+    })
+    .await?;
+
+    stopped_streams_events(context, stopped_streams_with_rtc)
+}
+
+fn stop_backend_streams(
+    backend_id: &AgentId,
+    conn: &PgConnection,
+) -> Result<Vec<(janus_rtc_stream::Object, rtc::Object)>, AppError> {
+    let streams_with_rtc = janus_rtc_stream::ListWithRtcQuery::new()
+        .active(true)
+        .backend_id(backend_id)
+        .execute(conn)?;
+
+    let mut stopped_streams_with_rtc = Vec::with_capacity(streams_with_rtc.len());
+
+    for (stream, rtc) in streams_with_rtc {
+        if let Some(stream) = janus_rtc_stream::stop(stream.id(), conn)? {
+            stopped_streams_with_rtc.push((stream, rtc));
+        }
+    }
+
+    Ok(stopped_streams_with_rtc)
+}
+
+fn stopped_streams_events<C: Context>(
+    context: &mut C,
+    stopped_streams_with_rtc: Vec<(janus_rtc_stream::Object, rtc::Object)>,
+) -> Result<MessageStream, AppError> {
+    let mut events = Vec::with_capacity(stopped_streams_with_rtc.len());
+
+    for (stream, rtc) in stopped_streams_with_rtc {
+        let event =
+            endpoint::rtc_stream::update_event(rtc.room_id(), stream, context.start_timestamp())?;
+
+        events.push(Box::new(event) as Box<dyn IntoPublishableMessage + Send>);
+    }
+
+    Ok(Box::new(stream::from_iter(events)))
+}
+
+async fn handle_slow_link<C: Context>(
+    context: &mut C,
+    inev: &SlowLinkEvent,
+) -> Result<MessageStream, AppError> {
+    let maybe_stream_with_room =
+        register_quality_event(context, inev, janus_rtc_stream::register_slow_link).await?;
+
+    let (rtc_stream, room) = match maybe_stream_with_room {
+        Some(stream_with_room) => stream_with_room,
+        None => return Ok(Box::new(stream::empty())),
+    };
+
+    context
+        .metrics()
+        .observe_janus_slow_link(rtc_stream.backend_id(), inev.uplink);
+
+    let data = endpoint::rtc_stream::QualityEventData::slow_link(&rtc_stream, inev.uplink);
+    let event = endpoint::rtc_stream::quality_event(room.id(), data, context.start_timestamp());
+    Ok(Box::new(stream::once(
+        Box::new(event) as Box<dyn IntoPublishableMessage + Send>
+    )))
+}
+
+async fn handle_media<C: Context>(
+    context: &mut C,
+    inev: &MediaEvent,
+) -> Result<MessageStream, AppError> {
+    // Only losing the media is interesting, receiving it again is a normal flow.
+    if inev.receiving {
+        return Ok(Box::new(stream::empty()));
+    }
+
+    let maybe_stream_with_room =
+        register_quality_event(context, inev, janus_rtc_stream::register_media_loss).await?;
+
+    let (rtc_stream, room) = match maybe_stream_with_room {
+        Some(stream_with_room) => stream_with_room,
+        None => return Ok(Box::new(stream::empty())),
+    };
+
+    context
+        .metrics()
+        .observe_janus_media_loss(rtc_stream.backend_id(), &inev.kind);
+
+    let data = endpoint::rtc_stream::QualityEventData::media(&rtc_stream, &inev.kind);
+    let event = endpoint::rtc_stream::quality_event(room.id(), data, context.start_timestamp());
+    Ok(Box::new(stream::once(
+        Box::new(event) as Box<dyn IntoPublishableMessage + Send>
+    )))
+}
+
+// Updates the stream's quality counters and finds the room to notify.
+async fn register_quality_event<C: Context, E: OpaqueId>(
+    context: &mut C,
+    inev: &E,
+    register: fn(
+        janus_rtc_stream::Id,
+        &PgConnection,
+    ) -> Result<Option<janus_rtc_stream::Object>, diesel::result::Error>,
+) -> Result<Option<(janus_rtc_stream::Object, room::Object)>, AppError> {
+    context.add_logger_tags(o!("rtc_stream_id" => inev.opaque_id().to_owned()));
+
+    let rtc_stream_id = inev
+        .opaque_id()
+        .parse()
+        .map_err(|err| anyhow!("Failed to parse opaque id as UUID: {}", err))
+        .error(AppErrorKind::MessageParsingFailed)?;
+
+    let conn = context.get_conn().await?;
+
+    task::spawn_blocking(move || {
+        if let Some(rtc_stream) = register(rtc_stream_id, &conn)? {
+            let room = endpoint::helpers::find_room_by_rtc_id(
+                rtc_stream.rtc_id(),
+                endpoint::helpers::RoomTimeRequirement::Open,
+                &conn,
+            )?;
+
+            return Ok(Some((rtc_stream, room)));
+        }
+
+        Ok::<_, AppError>(None)
+    })
+    .await
+}
+
 async fn handle_hangup_detach<C: Context, E: OpaqueId>(
     context: &mut C,
     inev: &E,
@@ -469,6 +779,7 @@ pub struct StatusEvent {
     pub balancer_capacity: Option<i32>,
     pub group: Option<String>,
     pub janus_url: Option<String>,
+    pub draining: Option<bool>,
 }
 
 async fn handle_status_event_impl<C: Context>(
@@ -523,13 +834,28 @@ async fn handle_status_event_impl<C: Context>(
                 q = q.group(group);
             }
 
+            if let Some(draining) = payload.draining {
+                q = q.draining(draining);
+            }
+
             q.execute(&conn)
         })
         .await?;
-        context
+        let client = context
             .janus_clients()
             .get_or_insert(&backend)
             .error(AppErrorKind::BrokerRequestFailed)?;
+
+        // Janus destroys sessions along with the WebSocket connection they were created on
+        // so the pooled client's connection must take the session over from the temporary one.
+        if client.is_websocket() {
+            client
+                .claim_session(backend.session_id())
+                .await
+                .context("ClaimSession")
+                .error(AppErrorKind::BackendRequestFailed)?;
+        }
+
         Ok(Box::new(stream::empty()))
     } else {
         context.janus_clients().remove_client(evp.as_agent_id());
@@ -570,6 +896,110 @@ async fn handle_status_event_impl<C: Context>(
 }
 
 ////////////////////////////////////////////////////////////////////////////////
+pub mod balancer;
 pub mod client;
 pub mod client_pool;
 pub mod metrics;
+pub mod transaction_watchdog;
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::janus::client::{HandleId, SessionId},
+        test_helpers::{fake_janus::FakeJanus, parse_messages, prelude::*, test_deps::LocalDeps},
+    };
+
+    use super::*;
+
+    // Publishes a stream on the backend and lets the fake Janus emit an event for its handle.
+    async fn stop_stream_with<F>(emit: F)
+    where
+        F: FnOnce(&FakeJanus, SessionId, HandleId, &str),
+    {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let janus = local_deps.run_janus();
+        let db = TestDb::with_local_postgres(&postgres);
+        let (session_id, handle_id) = shared_helpers::init_janus(&janus.url).await;
+
+        let (backend, rtc_stream) = {
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get DB connection");
+
+            let backend =
+                shared_helpers::insert_janus_backend(&conn, &janus.url, session_id, handle_id);
+
+            let room = shared_helpers::insert_room_with_backend_id(&conn, backend.id());
+            let rtc = shared_helpers::insert_rtc_with_room(&conn, &room);
+
+            let rtc_stream = factory::JanusRtcStream::new(USR_AUDIENCE)
+                .backend(&backend)
+                .rtc(&rtc)
+                .insert(&conn);
+
+            let rtc_stream = janus_rtc_stream::start(rtc_stream.id(), &conn)
+                .expect("Failed to start rtc stream")
+                .expect("Rtc stream not found");
+
+            (backend, rtc_stream)
+        };
+
+        // Start polling the backend's session.
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let (tx, rx) = crossbeam_channel::unbounded();
+        context.with_janus(tx);
+
+        context
+            .janus_clients()
+            .get_or_insert(&backend)
+            .expect("Failed to create janus client");
+
+        emit(
+            &janus,
+            session_id,
+            rtc_stream.handle_id(),
+            &rtc_stream.id().to_string(),
+        );
+
+        let event = rx.recv().expect("Failed to receive janus event");
+        context.janus_clients().remove_client(backend.id());
+        let messages = parse_messages(super::handle_event(&mut context, event).await).await;
+
+        // Assert notification.
+        let (payload, evp, _) = find_event::<serde_json::Value>(messages.as_slice());
+        assert_eq!(evp.label(), "rtc_stream.update");
+        assert_eq!(payload["id"], rtc_stream.id().to_string());
+
+        // Assert the stream is stopped.
+        let conn = context
+            .get_conn()
+            .await
+            .expect("Failed to get DB connection");
+
+        let active_streams = janus_rtc_stream::ListQuery::new()
+            .rtc_id(rtc_stream.rtc_id())
+            .active(true)
+            .execute(&conn)
+            .expect("Failed to list rtc streams");
+
+        assert!(active_streams.is_empty());
+    }
+
+    #[async_std::test]
+    async fn hangup_stops_stream() {
+        stop_stream_with(|janus, session_id, handle_id, opaque_id| {
+            janus.hangup(session_id, handle_id, opaque_id)
+        })
+        .await;
+    }
+
+    #[async_std::test]
+    async fn detached_stops_stream() {
+        stop_stream_with(|janus, session_id, handle_id, opaque_id| {
+            janus.detached(session_id, handle_id, opaque_id)
+        })
+        .await;
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::Sender;
+
+use super::client::{transactions::Transaction, IncomingEvent};
+use crate::config::BackendConfig;
+
+/// Keeps track of Janus transactions awaiting an asynchronous `event` response.
+///
+/// Transactions are keyed by their base64 representation which Janus echoes back in the event.
+/// If no event comes within the timeout the transaction gets expired and
+/// an `IncomingEvent::TransactionTimedOut` is sent to the events sink.
+/// Expired transactions are remembered for a while to drop their late events.
+#[derive(Debug, Clone)]
+pub struct TransactionWatchdog {
+    state: Arc<Mutex<State>>,
+    default_timeout: Duration,
+    stream_upload_timeout: Duration,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    pending: HashMap<String, PendingTransaction>,
+    // Expired transaction ids with the time to forget them.
+    expired: HashMap<String, Instant>,
+}
+
+#[derive(Debug)]
+struct PendingTransaction {
+    transaction: Transaction,
+    expires_at: Instant,
+}
+
+impl TransactionWatchdog {
+    pub fn new(config: &BackendConfig) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State::default())),
+            default_timeout: Duration::from_secs(config.default_timeout),
+            stream_upload_timeout: Duration::from_secs(config.stream_upload_timeout),
+        }
+    }
+
+    pub fn register(&self, transaction_id: &str, transaction: Transaction) {
+        self.register_at(transaction_id, transaction, Instant::now())
+    }
+
+    fn register_at(&self, transaction_id: &str, transaction: Transaction, now: Instant) {
+        let timeout = match transaction {
+            Transaction::CreateStream(_) | Transaction::ReadStream(_) => self.default_timeout,
+            Transaction::UploadStream(_) => self.stream_upload_timeout,
+            // Nobody waits for the result of these ones.
+            Transaction::AgentLeave
+            | Transaction::UpdateReaderConfig
+            | Transaction::UpdateWriterConfig => return,
+        };
+
+        let pending_transaction = PendingTransaction {
+            transaction,
+            expires_at: now + timeout,
+        };
+
+        self.state
+            .lock()
+            .expect("Must not panic")
+            .pending
+            .insert(transaction_id.to_owned(), pending_transaction);
+    }
+
+    /// Stops tracking the transaction.
+    ///
+    /// Returns `false` if it has already expired so its event must be dropped
+    /// since the timeout has been handled instead.
+    pub fn complete(&self, transaction_id: &str) -> bool {
+        let mut guard = self.state.lock().expect("Must not panic");
+        guard.pending.remove(transaction_id);
+        guard.expired.remove(transaction_id).is_none()
+    }
+
+    fn take_expired(&self, now: Instant) -> Vec<Transaction> {
+        let mut guard = self.state.lock().expect("Must not panic");
+        guard.expired.retain(|_, forget_at| *forget_at > now);
+
+        let expired_ids = guard
+            .pending
+            .iter()
+            .filter(|(_, pending_transaction)| pending_transaction.expires_at <= now)
+            .map(|(id, _)| id.to_owned())
+            .collect::<Vec<_>>();
+
+        // Keep tombstones as long as the longest timeout.
+        let forget_at = now + self.stream_upload_timeout.max(self.default_timeout);
+        let mut expired = Vec::with_capacity(expired_ids.len());
+
+        for id in expired_ids {
+            if let Some(pending_transaction) = guard.pending.remove(&id) {
+                guard.expired.insert(id, forget_at);
+                expired.push(pending_transaction.transaction);
+            }
+        }
+
+        expired
+    }
+
+    pub async fn start(self, events_sink: Sender<IncomingEvent>, check_period: Duration) {
+        loop {
+            async_std::task::sleep(check_period).await;
+
+            for transaction in self.take_expired(Instant::now()) {
+                events_sink
+                    .send(IncomingEvent::TransactionTimedOut(transaction))
+                    .expect("Receiver must exist");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::backend::janus::client::upload_stream::UploadStreamTransaction;
+    use crate::db;
+
+    fn build_watchdog() -> TransactionWatchdog {
+        TransactionWatchdog {
+            state: Arc::new(Mutex::new(State::default())),
+            default_timeout: Duration::from_secs(5),
+            stream_upload_timeout: Duration::from_secs(600),
+        }
+    }
+
+    fn upload_transaction() -> Transaction {
+        Transaction::UploadStream(UploadStreamTransaction {
+            rtc_id: db::rtc::Id::random(),
+            start_timestamp: Utc::now(),
+        })
+    }
+
+    #[test]
+    fn expire_after_timeout() {
+        let watchdog = build_watchdog();
+        let now = Instant::now();
+        watchdog.register_at("upload", upload_transaction(), now);
+
+        let expired = watchdog.take_expired(now + Duration::from_secs(599));
+        assert!(expired.is_empty());
+
+        let expired = watchdog.take_expired(now + Duration::from_secs(600));
+        assert_eq!(expired.len(), 1);
+        assert!(matches!(expired[0], Transaction::UploadStream(_)));
+
+        // Expired transactions are reported only once.
+        let expired = watchdog.take_expired(now + Duration::from_secs(601));
+        assert!(expired.is_empty());
+    }
+
+    #[test]
+    fn skip_completed() {
+        let watchdog = build_watchdog();
+        let now = Instant::now();
+        watchdog.register_at("upload", upload_transaction(), now);
+        assert!(watchdog.complete("upload"));
+
+        let expired = watchdog.take_expired(now + Duration::from_secs(600));
+        assert!(expired.is_empty());
+    }
+
+    #[test]
+    fn reject_completion_after_expiration() {
+        let watchdog = build_watchdog();
+        let now = Instant::now();
+        watchdog.register_at("upload", upload_transaction(), now);
+
+        let expired = watchdog.take_expired(now + Duration::from_secs(600));
+        assert_eq!(expired.len(), 1);
+
+        // The late event must be dropped but only once.
+        assert!(!watchdog.complete("upload"));
+        assert!(watchdog.complete("upload"));
+    }
+
+    #[test]
+    fn forget_expired_transactions() {
+        let watchdog = build_watchdog();
+        let now = Instant::now();
+        watchdog.register_at("upload", upload_transaction(), now);
+
+        let expired = watchdog.take_expired(now + Duration::from_secs(600));
+        assert_eq!(expired.len(), 1);
+
+        watchdog.take_expired(now + Duration::from_secs(1200));
+        assert!(watchdog.complete("upload"));
+    }
+
+    #[test]
+    fn skip_fire_and_forget_transactions() {
+        let watchdog = build_watchdog();
+        let now = Instant::now();
+        watchdog.register_at("leave", Transaction::AgentLeave, now);
+
+        let expired = watchdog.take_expired(now + Duration::from_secs(600));
+        assert!(expired.is_empty());
+        assert!(watchdog.complete("leave"));
+    }
+}
@@ -6,6 +6,8 @@ use svc_authn::jose::Algorithm;
 use svc_authz::ConfigMap as Authz;
 use svc_error::extension::sentry::Config as SentryConfig;
 
+use crate::{backend::janus::balancer::BalancingStrategyKind, db::room::QualityPreset};
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
     pub id: AccountId,
@@ -24,6 +26,16 @@ pub struct Config {
     pub metrics: MetricsConfig,
     pub max_room_duration: Option<i64>,
     pub janus_group: Option<String>,
+    #[serde(default)]
+    pub balancer: BalancerConfig,
+    #[serde(default)]
+    pub room_closing: RoomClosingConfig,
+    #[serde(default)]
+    pub quotas: HashMap<String, QuotaConfig>,
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+    #[serde(default)]
+    pub quality_presets: QualityPresetsConfig,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -78,9 +90,120 @@ pub struct MetricsConfig {
     pub http: MetricsHttpConfig,
     #[serde(with = "humantime_serde")]
     pub janus_metrics_collect_interval: Duration,
+    /// Usage counters are not exported if not set.
+    #[serde(default, with = "humantime_serde")]
+    pub usage_metrics_collect_interval: Option<Duration>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct MetricsHttpConfig {
     pub bind_address: std::net::SocketAddr,
 }
+
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct BalancerConfig {
+    #[serde(default)]
+    pub default_strategy: BalancingStrategyKind,
+    #[serde(default)]
+    pub audiences: HashMap<String, BalancingStrategyKind>,
+}
+
+impl BalancerConfig {
+    pub fn strategy(&self, audience: &str) -> BalancingStrategyKind {
+        self.audiences
+            .get(audience)
+            .copied()
+            .unwrap_or(self.default_strategy)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RoomClosingConfig {
+    /// Every instance scans the whole room table each period so keep it reasonably long.
+    #[serde(with = "humantime_serde")]
+    pub check_period: Duration,
+    /// Seconds before the room's closing to send `room.closing_soon` notifications at.
+    pub warning_offsets: Vec<i32>,
+}
+
+impl Default for RoomClosingConfig {
+    fn default() -> Self {
+        Self {
+            check_period: Duration::from_secs(30),
+            warning_offsets: vec![],
+        }
+    }
+}
+
+/// Limits of an audience's usage of the shared backends. Missing limits aren't enforced.
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct QuotaConfig {
+    /// Rooms which haven't been closed yet including scheduled ones.
+    pub max_open_rooms: Option<i64>,
+    /// Streams which are being published at the moment.
+    pub max_active_streams: Option<i64>,
+    /// Recorded minutes since the beginning of the current month in UTC.
+    pub max_monthly_recorded_minutes: Option<f64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    /// Period after which a retry with the same idempotency key creates a new object.
+    #[serde(with = "humantime_serde")]
+    pub key_ttl: Duration,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            key_ttl: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+pub type QualityPresetConfigMap = HashMap<QualityPreset, QualityPresetConfig>;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct QualityPresetsConfig {
+    /// Presets of audiences which are missing in `audiences`.
+    pub default: QualityPresetConfigMap,
+    pub audiences: HashMap<String, QualityPresetConfigMap>,
+}
+
+impl QualityPresetsConfig {
+    pub fn get(&self, audience: &str, preset: QualityPreset) -> Option<QualityPresetConfig> {
+        self.audiences
+            .get(audience)
+            .unwrap_or(&self.default)
+            .get(&preset)
+            .copied()
+    }
+}
+
+impl Default for QualityPresetsConfig {
+    fn default() -> Self {
+        let default = vec![
+            (QualityPreset::Low, 300_000),
+            (QualityPreset::Medium, 1_000_000),
+            (QualityPreset::High, 2_500_000),
+            (QualityPreset::AudioOnly, 64_000),
+        ]
+        .into_iter()
+        .map(|(preset, video_remb)| (preset, QualityPresetConfig { video_remb }))
+        .collect();
+
+        Self {
+            default,
+            audiences: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct QualityPresetConfig {
+    /// Writers' bitrate limit in bits per second which also weighs the room's reserve.
+    pub video_remb: u32,
+}
@@ -2,7 +2,7 @@ use chrono::{serde::ts_seconds, DateTime, Utc};
 use diesel::{pg::PgConnection, result::Error};
 use diesel_derive_enum::DbEnum;
 use serde::{Deserialize, Serialize};
-use svc_agent::AgentId;
+use svc_agent::{AccountId, AgentId};
 use uuid::Uuid;
 
 use super::room::Object as Room;
@@ -25,6 +25,8 @@ pub enum Status {
     #[serde(rename = "in_progress")]
     InProgress,
     Ready,
+    // Waits for a moderator to admit it to the room.
+    Waiting,
 }
 
 #[derive(Debug, Serialize, Deserialize, Identifiable, Queryable, QueryableByName, Associations)]
@@ -39,8 +41,15 @@ pub struct Object {
     status: Status,
 }
 
-#[cfg(test)]
 impl Object {
+    pub fn agent_id(&self) -> &AgentId {
+        &self.agent_id
+    }
+
+    pub fn room_id(&self) -> db::room::Id {
+        self.room_id
+    }
+
     pub fn status(&self) -> Status {
         self.status
     }
@@ -154,26 +163,123 @@ impl<'a> InsertQuery<'a> {
         }
     }
 
-    #[cfg(test)]
     pub fn status(self, status: Status) -> Self {
         Self { status, ..self }
     }
 
+    /// Updates the status of the existing agent except for an admitted one which is never put
+    /// back to the waiting room.
     pub fn execute(&self, conn: &PgConnection) -> Result<Object, Error> {
         use crate::schema::agent::dsl::*;
-        use diesel::{ExpressionMethods, RunQueryDsl};
+        use diesel::{dsl::sql, ExpressionMethods, RunQueryDsl};
 
         diesel::insert_into(agent)
             .values(self)
             .on_conflict((agent_id, room_id))
             .do_update()
-            .set(status.eq(Status::InProgress))
+            .set(status.eq(sql::<Agent_status>(
+                "CASE WHEN excluded.status = 'waiting' THEN agent.status ELSE excluded.status END",
+            )))
             .get_result(conn)
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
+pub struct FindQuery<'a> {
+    agent_id: &'a AgentId,
+    room_id: db::room::Id,
+}
+
+impl<'a> FindQuery<'a> {
+    pub fn new(agent_id: &'a AgentId, room_id: db::room::Id) -> Self {
+        Self { agent_id, room_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
+        use diesel::prelude::*;
+
+        agent::table
+            .filter(agent::agent_id.eq(self.agent_id))
+            .filter(agent::room_id.eq(self.room_id))
+            .get_result(conn)
+            .optional()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// All agents of the account in the room regardless of their status.
+#[derive(Debug)]
+pub struct ListByAccountQuery<'a> {
+    account_id: &'a AccountId,
+    room_id: db::room::Id,
+}
+
+impl<'a> ListByAccountQuery<'a> {
+    pub fn new(account_id: &'a AccountId, room_id: db::room::Id) -> Self {
+        Self {
+            account_id,
+            room_id,
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Vec<Object>, Error> {
+        use diesel::{dsl::sql, prelude::*};
+        use svc_agent::sql::Account_id;
+
+        agent::table
+            .filter(agent::room_id.eq(self.room_id))
+            .filter(sql("(agent_id).account_id = ").bind::<Account_id, _>(self.account_id))
+            .order_by(agent::created_at.asc())
+            .get_results(conn)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct CountQuery<'a> {
+    room_id: db::room::Id,
+    statuses: &'a [Status],
+    except_agent_id: Option<&'a AgentId>,
+}
+
+impl<'a> CountQuery<'a> {
+    pub fn new(room_id: db::room::Id, statuses: &'a [Status]) -> Self {
+        Self {
+            room_id,
+            statuses,
+            except_agent_id: None,
+        }
+    }
+
+    pub fn except_agent_id(self, agent_id: &'a AgentId) -> Self {
+        Self {
+            except_agent_id: Some(agent_id),
+            ..self
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<i64, Error> {
+        use diesel::{dsl::count_star, prelude::*};
+
+        let mut query = agent::table
+            .filter(agent::room_id.eq(self.room_id))
+            .filter(agent::status.eq_any(self.statuses))
+            .select(count_star())
+            .into_boxed();
+
+        if let Some(agent_id) = self.except_agent_id {
+            query = query.filter(agent::agent_id.ne(agent_id));
+        }
+
+        query.get_result(conn)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[derive(Debug, AsChangeset)]
 #[table_name = "agent"]
 pub struct UpdateQuery<'a> {
@@ -72,6 +72,28 @@ impl<'a> FindQuery<'a> {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+pub struct ListByRoomQuery {
+    room_id: db::room::Id,
+}
+
+impl ListByRoomQuery {
+    pub fn new(room_id: db::room::Id) -> Self {
+        Self { room_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Vec<Object>, Error> {
+        use diesel::prelude::*;
+
+        agent_connection::table
+            .inner_join(agent::table)
+            .filter(agent::room_id.eq(self.room_id))
+            .select(ALL_COLUMNS)
+            .get_results(conn)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 pub struct CountQuery {}
 
 impl CountQuery {
@@ -0,0 +1,181 @@
+use chrono::{DateTime, Utc};
+use diesel::{pg::PgConnection, result::Error};
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+use crate::{db, db::room::Object as Room, schema::agent_session};
+
+////////////////////////////////////////////////////////////////////////////////
+
+type AllColumns = (
+    agent_session::id,
+    agent_session::agent_id,
+    agent_session::room_id,
+    agent_session::started_at,
+    agent_session::finished_at,
+    agent_session::leave_reason,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    agent_session::id,
+    agent_session::agent_id,
+    agent_session::room_id,
+    agent_session::started_at,
+    agent_session::finished_at,
+    agent_session::leave_reason,
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[PgType = "agent_session_leave_reason"]
+#[DieselType = "Agent_session_leave_reason"]
+pub enum LeaveReason {
+    Left,
+    Kicked,
+    Banned,
+    // Moved to another room of the same breakout group.
+    Moved,
+    Closed,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName, Associations)]
+#[belongs_to(Room, foreign_key = "room_id")]
+#[table_name = "agent_session"]
+pub struct Object {
+    id: Uuid,
+    agent_id: AgentId,
+    room_id: db::room::Id,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    leave_reason: Option<LeaveReason>,
+}
+
+impl Object {
+    pub fn agent_id(&self) -> &AgentId {
+        &self.agent_id
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    pub fn finished_at(&self) -> Option<DateTime<Utc>> {
+        self.finished_at
+    }
+
+    pub fn leave_reason(&self) -> Option<LeaveReason> {
+        self.leave_reason
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct ListQuery {
+    room_id: db::room::Id,
+}
+
+impl ListQuery {
+    pub fn new(room_id: db::room::Id) -> Self {
+        Self { room_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Vec<Object>, Error> {
+        use diesel::prelude::*;
+
+        agent_session::table
+            .filter(agent_session::room_id.eq(self.room_id))
+            .select(ALL_COLUMNS)
+            .order_by(agent_session::started_at.asc())
+            .get_results(conn)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Opens a session unless the agent already has an open one in the room.
+#[derive(Debug)]
+pub struct StartQuery<'a> {
+    agent_id: &'a AgentId,
+    room_id: db::room::Id,
+}
+
+impl<'a> StartQuery<'a> {
+    pub fn new(agent_id: &'a AgentId, room_id: db::room::Id) -> Self {
+        Self { agent_id, room_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Object, Error> {
+        use diesel::prelude::*;
+
+        let maybe_open_session = agent_session::table
+            .filter(agent_session::agent_id.eq(self.agent_id))
+            .filter(agent_session::room_id.eq(self.room_id))
+            .filter(agent_session::finished_at.is_null())
+            .select(ALL_COLUMNS)
+            .get_result(conn)
+            .optional()?;
+
+        match maybe_open_session {
+            Some(session) => Ok(session),
+            None => diesel::insert_into(agent_session::table)
+                .values((
+                    agent_session::agent_id.eq(self.agent_id),
+                    agent_session::room_id.eq(self.room_id),
+                ))
+                .returning(ALL_COLUMNS)
+                .get_result(conn),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Closes open sessions in the room: all of them or only the agent's one.
+#[derive(Debug)]
+pub struct FinishQuery<'a> {
+    room_id: db::room::Id,
+    reason: LeaveReason,
+    agent_id: Option<&'a AgentId>,
+}
+
+impl<'a> FinishQuery<'a> {
+    pub fn new(room_id: db::room::Id, reason: LeaveReason) -> Self {
+        Self {
+            room_id,
+            reason,
+            agent_id: None,
+        }
+    }
+
+    pub fn agent_id(self, agent_id: &'a AgentId) -> Self {
+        Self {
+            agent_id: Some(agent_id),
+            ..self
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<usize, Error> {
+        use diesel::{dsl::sql, prelude::*};
+
+        // Sessions are started with the database's clock so they should be finished with it too.
+        let mut query = diesel::update(agent_session::table)
+            .set((
+                agent_session::finished_at.eq(sql("NOW()")),
+                agent_session::leave_reason.eq(self.reason),
+            ))
+            .into_boxed()
+            .filter(agent_session::room_id.eq(self.room_id))
+            .filter(agent_session::finished_at.is_null());
+
+        if let Some(agent_id) = self.agent_id {
+            query = query.filter(agent_session::agent_id.eq(agent_id));
+        }
+
+        query.execute(conn)
+    }
+}
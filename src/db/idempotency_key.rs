@@ -0,0 +1,295 @@
+use chrono::{DateTime, Utc};
+use diesel::{pg::PgConnection, result::Error};
+use diesel_derive_enum::DbEnum;
+
+use crate::{db, schema::idempotency_key};
+
+////////////////////////////////////////////////////////////////////////////////
+
+type AllColumns = (
+    idempotency_key::audience,
+    idempotency_key::kind,
+    idempotency_key::key,
+    idempotency_key::room_id,
+    idempotency_key::rtc_id,
+    idempotency_key::created_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    idempotency_key::audience,
+    idempotency_key::kind,
+    idempotency_key::key,
+    idempotency_key::room_id,
+    idempotency_key::rtc_id,
+    idempotency_key::created_at,
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The kind of the object created by the request. Each kind has its own keys.
+#[derive(Clone, Copy, Debug, DbEnum, PartialEq)]
+#[PgType = "idempotency_key_kind"]
+#[DieselType = "Idempotency_key_kind"]
+pub enum Kind {
+    Room,
+    Rtc,
+}
+
+/// A client provided key of a creation request pointing to the created object.
+///
+/// Keys get deleted along with their rooms and rtcs by the foreign keys so they never dangle.
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[table_name = "idempotency_key"]
+#[primary_key(audience, kind, key)]
+pub struct Object {
+    audience: String,
+    kind: Kind,
+    key: String,
+    room_id: db::room::Id,
+    rtc_id: Option<db::rtc::Id>,
+    created_at: DateTime<Utc>,
+}
+
+impl Object {
+    /// The created room or the room the rtc has been created in.
+    pub fn room_id(&self) -> db::room::Id {
+        self.room_id
+    }
+
+    pub fn rtc_id(&self) -> Option<db::rtc::Id> {
+        self.rtc_id
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Finds the key unless it has been created before `created_after` that is expired.
+#[derive(Debug)]
+pub struct FindQuery<'a> {
+    audience: &'a str,
+    kind: Kind,
+    key: &'a str,
+    created_after: DateTime<Utc>,
+}
+
+impl<'a> FindQuery<'a> {
+    pub fn new(audience: &'a str, kind: Kind, key: &'a str, created_after: DateTime<Utc>) -> Self {
+        Self {
+            audience,
+            kind,
+            key,
+            created_after,
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
+        use diesel::prelude::*;
+
+        idempotency_key::table
+            .filter(idempotency_key::audience.eq(self.audience))
+            .filter(idempotency_key::kind.eq(self.kind))
+            .filter(idempotency_key::key.eq(self.key))
+            .filter(idempotency_key::created_at.gt(self.created_after))
+            .select(ALL_COLUMNS)
+            .get_result(conn)
+            .optional()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Stores the key for the created room or rtc replacing the expired one if any.
+///
+/// Returns `None` if the key already exists and has been created after `expired_before`
+/// meaning a concurrent request with the same key has already created its object.
+#[derive(Debug)]
+pub struct InsertQuery<'a> {
+    audience: &'a str,
+    key: &'a str,
+    room_id: db::room::Id,
+    rtc_id: Option<db::rtc::Id>,
+    expired_before: DateTime<Utc>,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(
+        audience: &'a str,
+        key: &'a str,
+        room_id: db::room::Id,
+        expired_before: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            audience,
+            key,
+            room_id,
+            rtc_id: None,
+            expired_before,
+        }
+    }
+
+    pub fn rtc_id(self, rtc_id: db::rtc::Id) -> Self {
+        Self {
+            rtc_id: Some(rtc_id),
+            ..self
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
+        use diesel::{
+            prelude::*,
+            sql_types::{Nullable, Text, Timestamptz, Uuid},
+        };
+
+        let kind = match self.rtc_id {
+            Some(_) => Kind::Rtc,
+            None => Kind::Room,
+        };
+
+        diesel::sql_query(INSERT_SQL)
+            .bind::<Text, _>(self.audience)
+            .bind::<Idempotency_key_kind, _>(kind)
+            .bind::<Text, _>(self.key)
+            .bind::<Uuid, _>(self.room_id)
+            .bind::<Nullable<Uuid>, _>(self.rtc_id)
+            .bind::<Timestamptz, _>(Utc::now())
+            .bind::<Timestamptz, _>(self.expired_before)
+            .get_result(conn)
+            .optional()
+    }
+}
+
+const INSERT_SQL: &str = r#"
+INSERT INTO idempotency_key (audience, kind, key, room_id, rtc_id, created_at)
+VALUES ($1, $2, $3, $4, $5, $6)
+ON CONFLICT (audience, kind, key) DO UPDATE
+SET room_id = EXCLUDED.room_id,
+    rtc_id = EXCLUDED.rtc_id,
+    created_at = EXCLUDED.created_at
+WHERE idempotency_key.created_at <= $7
+RETURNING *
+"#;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Deletes keys created before `created_before` that is expired ones.
+#[derive(Debug)]
+pub struct DeleteQuery {
+    created_before: DateTime<Utc>,
+}
+
+impl DeleteQuery {
+    pub fn new(created_before: DateTime<Utc>) -> Self {
+        Self { created_before }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<usize, Error> {
+        use diesel::prelude::*;
+
+        diesel::delete(idempotency_key::table)
+            .filter(idempotency_key::created_at.lt(self.created_before))
+            .execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+    #[test]
+    fn skip_existing_key() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let db = TestDb::with_local_postgres(&postgres);
+        let conn = db
+            .connection_pool()
+            .get()
+            .expect("Failed to get DB connection");
+        let room = shared_helpers::insert_room(&conn);
+        let other_room = shared_helpers::insert_room(&conn);
+        let expired_before = Utc::now() - Duration::hours(1);
+
+        InsertQuery::new(USR_AUDIENCE, "key", room.id(), expired_before)
+            .execute(&conn)
+            .expect("Failed to insert idempotency key")
+            .expect("Idempotency key hasn't been inserted");
+
+        let maybe_key = InsertQuery::new(USR_AUDIENCE, "key", other_room.id(), expired_before)
+            .execute(&conn)
+            .expect("Failed to insert idempotency key");
+
+        assert!(maybe_key.is_none());
+
+        let key = FindQuery::new(USR_AUDIENCE, Kind::Room, "key", expired_before)
+            .execute(&conn)
+            .expect("Failed to find idempotency key")
+            .expect("Idempotency key not found");
+
+        assert_eq!(key.room_id(), room.id());
+    }
+
+    #[test]
+    fn replace_expired_key() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let db = TestDb::with_local_postgres(&postgres);
+        let conn = db
+            .connection_pool()
+            .get()
+            .expect("Failed to get DB connection");
+        let room = shared_helpers::insert_room(&conn);
+        let other_room = shared_helpers::insert_room(&conn);
+
+        InsertQuery::new(
+            USR_AUDIENCE,
+            "key",
+            room.id(),
+            Utc::now() - Duration::hours(1),
+        )
+        .execute(&conn)
+        .expect("Failed to insert idempotency key")
+        .expect("Idempotency key hasn't been inserted");
+
+        let key = InsertQuery::new(USR_AUDIENCE, "key", other_room.id(), Utc::now())
+            .execute(&conn)
+            .expect("Failed to insert idempotency key")
+            .expect("Expired idempotency key hasn't been replaced");
+
+        assert_eq!(key.room_id(), other_room.id());
+    }
+
+    #[test]
+    fn separate_room_and_rtc_keys() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let db = TestDb::with_local_postgres(&postgres);
+        let conn = db
+            .connection_pool()
+            .get()
+            .expect("Failed to get DB connection");
+        let room = shared_helpers::insert_room(&conn);
+        let rtc = shared_helpers::insert_rtc_with_room(&conn, &room);
+        let expired_before = Utc::now() - Duration::hours(1);
+
+        InsertQuery::new(USR_AUDIENCE, "key", room.id(), expired_before)
+            .execute(&conn)
+            .expect("Failed to insert idempotency key")
+            .expect("Room idempotency key hasn't been inserted");
+
+        InsertQuery::new(USR_AUDIENCE, "key", room.id(), expired_before)
+            .rtc_id(rtc.id())
+            .execute(&conn)
+            .expect("Failed to insert idempotency key")
+            .expect("Rtc idempotency key hasn't been inserted");
+
+        let key = FindQuery::new(USR_AUDIENCE, Kind::Rtc, "key", expired_before)
+            .execute(&conn)
+            .expect("Failed to find idempotency key")
+            .expect("Idempotency key not found");
+
+        assert_eq!(key.room_id(), room.id());
+        assert_eq!(key.rtc_id(), Some(rtc.id()));
+    }
+}
@@ -4,10 +4,12 @@ use crate::{
         client::{HandleId, SessionId},
         JANUS_API_VERSION,
     },
+    db::room::DEFAULT_VIDEO_REMB,
     schema::janus_backend,
 };
-use chrono::{DateTime, Utc};
+use chrono::{serde::ts_seconds, DateTime, Utc};
 use diesel::{pg::PgConnection, result::Error};
+use serde::{Deserialize, Serialize};
 use svc_agent::AgentId;
 
 pub type AllColumns = (
@@ -20,6 +22,7 @@ pub type AllColumns = (
     janus_backend::api_version,
     janus_backend::group,
     janus_backend::janus_url,
+    janus_backend::draining,
 );
 
 pub const ALL_COLUMNS: AllColumns = (
@@ -32,22 +35,25 @@ pub const ALL_COLUMNS: AllColumns = (
     janus_backend::api_version,
     janus_backend::group,
     janus_backend::janus_url,
+    janus_backend::draining,
 );
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug, Identifiable, Queryable, QueryableByName, Associations)]
+#[derive(Debug, Deserialize, Serialize, Identifiable, Queryable, QueryableByName, Associations)]
 #[table_name = "janus_backend"]
 pub struct Object {
     id: AgentId,
     handle_id: HandleId,
     session_id: SessionId,
+    #[serde(with = "ts_seconds")]
     created_at: DateTime<Utc>,
     capacity: Option<i32>,
     balancer_capacity: Option<i32>,
     api_version: String,
     group: Option<String>,
     janus_url: String,
+    draining: bool,
 }
 
 impl Object {
@@ -71,6 +77,12 @@ impl Object {
     pub fn group(&self) -> Option<&str> {
         self.group.as_deref()
     }
+
+    /// A draining backend keeps serving its rooms but doesn't get new ones.
+    #[cfg(test)]
+    pub fn is_draining(&self) -> bool {
+        self.draining
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -118,24 +130,42 @@ impl<'a> ListQuery<'a> {
 
 pub struct FindQuery<'a> {
     id: Option<&'a AgentId>,
+    session_id: Option<SessionId>,
 }
 
 impl<'a> FindQuery<'a> {
     pub fn new() -> Self {
-        Self { id: None }
+        Self {
+            id: None,
+            session_id: None,
+        }
     }
 
     pub fn id(self, id: &'a AgentId) -> Self {
-        Self { id: Some(id) }
+        Self {
+            id: Some(id),
+            ..self
+        }
+    }
+
+    pub fn session_id(self, session_id: SessionId) -> Self {
+        Self {
+            session_id: Some(session_id),
+            ..self
+        }
     }
 
     pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
         use diesel::prelude::*;
 
-        match self.id {
-            Some(ref id) => janus_backend::table.find(id).get_result(conn).optional(),
-            None => Err(Error::QueryBuilderError(
-                "id parameter is required parameter of the query".into(),
+        match (self.id, self.session_id) {
+            (Some(ref id), _) => janus_backend::table.find(id).get_result(conn).optional(),
+            (None, Some(session_id)) => janus_backend::table
+                .filter(janus_backend::session_id.eq(session_id))
+                .first(conn)
+                .optional(),
+            (None, None) => Err(Error::QueryBuilderError(
+                "id or session_id parameter is required parameter of the query".into(),
             )),
         }
     }
@@ -154,6 +184,7 @@ pub struct UpsertQuery<'a> {
     api_version: String,
     group: Option<&'a str>,
     janus_url: &'a str,
+    draining: Option<bool>,
 }
 
 impl<'a> UpsertQuery<'a> {
@@ -172,6 +203,7 @@ impl<'a> UpsertQuery<'a> {
             api_version: JANUS_API_VERSION.to_string(),
             group: None,
             janus_url,
+            draining: None,
         }
     }
 
@@ -196,6 +228,13 @@ impl<'a> UpsertQuery<'a> {
         }
     }
 
+    pub fn draining(self, draining: bool) -> Self {
+        Self {
+            draining: Some(draining),
+            ..self
+        }
+    }
+
     pub fn execute(&self, conn: &PgConnection) -> Result<Object, Error> {
         use crate::schema::janus_backend::dsl::janus_backend;
         use diesel::RunQueryDsl;
@@ -211,6 +250,34 @@ impl<'a> UpsertQuery<'a> {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Identifiable, AsChangeset)]
+#[table_name = "janus_backend"]
+pub struct UpdateQuery {
+    id: AgentId,
+    draining: Option<bool>,
+}
+
+impl UpdateQuery {
+    pub fn new(id: AgentId) -> Self {
+        Self { id, draining: None }
+    }
+
+    pub fn draining(self, draining: bool) -> Self {
+        Self {
+            draining: Some(draining),
+            ..self
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
+        use diesel::prelude::*;
+
+        diesel::update(self).set(self).get_result(conn).optional()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 pub struct DeleteQuery<'a> {
     id: &'a AgentId,
 }
@@ -229,22 +296,53 @@ impl<'a> DeleteQuery<'a> {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-// Returns the most loaded backend capable to host the room with its reserve considering:
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct BackendLoad {
+    #[sql_type = "svc_agent::sql::Agent_id"]
+    pub id: AgentId,
+    #[sql_type = "diesel::sql_types::Nullable<diesel::sql_types::Integer>"]
+    pub capacity: Option<i32>,
+    #[sql_type = "diesel::sql_types::Nullable<diesel::sql_types::Integer>"]
+    pub balancer_capacity: Option<i32>,
+    /// Sum of the greatest of taken load and reserve of active rooms.
+    #[sql_type = "diesel::sql_types::Double"]
+    pub load: f64,
+    /// Actual load by online agents considering writers' bitrate.
+    #[sql_type = "diesel::sql_types::Double"]
+    pub taken: f64,
+    /// Number of agents connected to the backend's rtcs.
+    #[sql_type = "diesel::sql_types::BigInt"]
+    pub connections: i64,
+}
+
+impl BackendLoad {
+    pub fn effective_capacity(&self) -> i32 {
+        self.balancer_capacity.or(self.capacity).unwrap_or(i32::MAX)
+    }
+}
+
+// Returns the load of each backend which may host a new room considering:
+// - draining backends are skipped;
 // - room opening period;
 // - actual number of online agents;
-// - optional backend capacity;
-// - optional room reserve;
-// - writer's bitrate;
+// - optional room reserve weighed by the bitrate of the room's quality preset
+//   the same way as `room::Object::balancing_reserve` does;
+// - writer's bitrate falling back to the room's quality preset one so that readers are
+//   weighed in the same units as the reserve;
 // - possible multiple RTCs in each room.
-const MOST_LOADED_SQL: &str = r#"
+//
+// Backends are returned in random order so that the balancing strategies break ties randomly.
+const BALANCING_SNAPSHOT_SQL: &str = r#"
     WITH
         room_load AS (
             SELECT
                 a.room_id,
-                SUM(COALESCE(rwc.video_remb, 1000000) / 1000000.0) AS taken
+                SUM(COALESCE(rwc.video_remb, r.video_remb, $3) / $3::FLOAT8) AS taken
             FROM agent AS a
             INNER JOIN agent_connection AS ac
             ON ac.agent_id = a.id
+            INNER JOIN room AS r
+            ON r.id = a.room_id
             LEFT JOIN rtc
             ON rtc.id = ac.rtc_id
             LEFT JOIN rtc_writer_config AS rwc
@@ -252,7 +350,9 @@ const MOST_LOADED_SQL: &str = r#"
             GROUP BY a.room_id
         ),
         active_room AS (
-            SELECT *
+            SELECT
+                *,
+                CEIL(reserve * COALESCE(video_remb, $3) / $3::FLOAT8) AS weighted_reserve
             FROM room
             WHERE backend_id IS NOT NULL
             AND   LOWER(time) <= NOW()
@@ -261,120 +361,63 @@ const MOST_LOADED_SQL: &str = r#"
         janus_backend_load AS (
             SELECT
                 backend_id,
-                SUM(GREATEST(taken, reserve)) AS load
+                SUM(GREATEST(taken, reserve)) AS load,
+                SUM(taken) AS taken
             FROM (
                 SELECT DISTINCT ON(backend_id, room_id)
                     ar.backend_id,
                     ar.id                   AS room_id,
                     COALESCE(rl.taken, 0)   AS taken,
-                    COALESCE(ar.reserve, 0) AS reserve
+                    COALESCE(ar.weighted_reserve, 0) AS reserve
                 FROM active_room AS ar
                 LEFT JOIN room_load AS rl
                 ON rl.room_id = ar.id
             ) AS sub
             GROUP BY backend_id
-        )
-    SELECT jb.*
-    FROM janus_backend AS jb
-    LEFT JOIN janus_backend_load AS jbl
-    ON jbl.backend_id = jb.id
-    LEFT JOIN room AS r2
-    ON 1 = 1
-    WHERE r2.id = $1
-    AND   COALESCE(jb.balancer_capacity, jb.capacity, 2147483647) - COALESCE(jbl.load, 0) >= COALESCE(r2.reserve, 1)
-    AND   jb.api_version = $2
-    AND   ($3 IS NULL OR jb."group" = $3)
-    ORDER BY COALESCE(jbl.load, 0) DESC, RANDOM()
-    LIMIT 1
-"#;
-
-pub fn most_loaded(
-    room_id: db::room::Id,
-    group: Option<&str>,
-    conn: &PgConnection,
-) -> Result<Option<Object>, Error> {
-    use diesel::{
-        prelude::*,
-        sql_types::{Nullable, Text, Uuid},
-    };
-
-    diesel::sql_query(MOST_LOADED_SQL)
-        .bind::<Uuid, _>(room_id)
-        .bind::<Text, _>(JANUS_API_VERSION)
-        .bind::<Nullable<Text>, _>(group)
-        .get_result(conn)
-        .optional()
-}
-
-// The same as above but finds the least loaded backend instead without considering the reserve.
-const LEAST_LOADED_SQL: &str = r#"
-    WITH
-        room_load AS (
-            SELECT
-                a.room_id,
-                SUM(COALESCE(rwc.video_remb, 1000000) / 1000000.0) AS taken
-            FROM agent AS a
-            INNER JOIN agent_connection AS ac
-            ON ac.agent_id = a.id
-            LEFT JOIN rtc
-            ON rtc.id = ac.rtc_id
-            LEFT JOIN rtc_writer_config AS rwc
-            ON rwc.rtc_id = rtc.id
-            GROUP BY a.room_id
-        ),
-        active_room AS (
-            SELECT *
-            FROM room
-            WHERE backend_id IS NOT NULL
-            AND   LOWER(time) <= NOW()
-            AND   (UPPER(time) IS NULL OR UPPER(time) > NOW())
         ),
-        janus_backend_load AS (
+        janus_backend_connections AS (
             SELECT
-                backend_id,
-                SUM(taken) AS load
-            FROM (
-                SELECT DISTINCT ON(backend_id, room_id)
-                    ar.backend_id,
-                    ar.id                 AS room_id,
-                    COALESCE(rl.taken, 0) AS taken
-                FROM active_room AS ar
-                LEFT JOIN room_load AS rl
-                ON rl.room_id = ar.id
-            ) AS sub
-            GROUP BY backend_id
+                ar.backend_id,
+                COUNT(ac.agent_id) AS connections
+            FROM active_room AS ar
+            INNER JOIN rtc
+            ON rtc.room_id = ar.id
+            INNER JOIN agent_connection AS ac
+            ON ac.rtc_id = rtc.id
+            GROUP BY ar.backend_id
         )
-    SELECT jb.*
+    SELECT
+        jb.id,
+        jb.capacity,
+        jb.balancer_capacity,
+        COALESCE(jbl.load, 0)::FLOAT8        AS load,
+        COALESCE(jbl.taken, 0)::FLOAT8       AS taken,
+        COALESCE(jbc.connections, 0)::BIGINT AS connections
     FROM janus_backend AS jb
     LEFT JOIN janus_backend_load AS jbl
     ON jbl.backend_id = jb.id
-    LEFT JOIN room AS r2
-    ON 1 = 1
-    WHERE r2.id = $1
-    AND   jb.api_version = $2
-    AND   ($3 IS NULL OR jb."group" = $3)
-    ORDER BY
-        COALESCE(jb.balancer_capacity, jb.capacity, 2147483647) - COALESCE(jbl.load, 0) DESC,
-        RANDOM()
-    LIMIT 1
+    LEFT JOIN janus_backend_connections AS jbc
+    ON jbc.backend_id = jb.id
+    WHERE jb.api_version = $1
+    AND   NOT jb.draining
+    AND   ($2 IS NULL OR jb."group" = $2)
+    ORDER BY RANDOM()
 "#;
 
-pub fn least_loaded(
-    room_id: db::room::Id,
+pub fn balancing_snapshot(
     group: Option<&str>,
     conn: &PgConnection,
-) -> Result<Option<Object>, Error> {
+) -> Result<Vec<BackendLoad>, Error> {
     use diesel::{
         prelude::*,
-        sql_types::{Nullable, Text, Uuid},
+        sql_types::{BigInt, Nullable, Text},
     };
 
-    diesel::sql_query(LEAST_LOADED_SQL)
-        .bind::<Uuid, _>(room_id)
+    diesel::sql_query(BALANCING_SNAPSHOT_SQL)
         .bind::<Text, _>(JANUS_API_VERSION)
         .bind::<Nullable<Text>, _>(group)
-        .get_result(conn)
-        .optional()
+        .bind::<BigInt, _>(DEFAULT_VIDEO_REMB)
+        .get_results(conn)
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -386,10 +429,12 @@ const FREE_CAPACITY_SQL: &str = r#"
         room_load AS (
             SELECT
                 a.room_id,
-                SUM(COALESCE(rwc.video_remb, 1000000) / 1000000.0) AS taken
+                SUM(COALESCE(rwc.video_remb, r.video_remb, $2) / $2::FLOAT8) AS taken
             FROM agent AS a
             INNER JOIN agent_connection AS ac
             ON ac.agent_id = a.id
+            INNER JOIN room AS r
+            ON r.id = a.room_id
             LEFT JOIN rtc
             ON rtc.id = ac.rtc_id
             LEFT JOIN rtc_writer_config AS rwc
@@ -397,7 +442,9 @@ const FREE_CAPACITY_SQL: &str = r#"
             GROUP BY a.room_id
         ),
         active_room AS (
-            SELECT *
+            SELECT
+                *,
+                CEIL(reserve * COALESCE(video_remb, $2) / $2::FLOAT8) AS weighted_reserve
             FROM room
             WHERE backend_id IS NOT NULL
             AND   LOWER(time) <= NOW()
@@ -414,7 +461,7 @@ const FREE_CAPACITY_SQL: &str = r#"
                     ar.backend_id,
                     ar.id                   AS room_id,
                     COALESCE(rl.taken, 0)   AS taken,
-                    COALESCE(ar.reserve, 0) AS reserve
+                    COALESCE(ar.weighted_reserve, 0) AS reserve
                 FROM active_room AS ar
                 LEFT JOIN room_load AS rl
                 ON rl.room_id = ar.id
@@ -429,9 +476,9 @@ const FREE_CAPACITY_SQL: &str = r#"
                     GREATEST(
                         (
                             CASE
-                                WHEN COALESCE(ar.reserve, 0) > COALESCE(rl.taken, 0)
+                                WHEN COALESCE(ar.weighted_reserve, 0) > COALESCE(rl.taken, 0)
                                     THEN LEAST(
-                                        COALESCE(ar.reserve, 0) - COALESCE(rl.taken, 0),
+                                        COALESCE(ar.weighted_reserve, 0) - COALESCE(rl.taken, 0),
                                         COALESCE(jb.capacity, 2147483647) - COALESCE(jbl.total_taken, 0)
                                     )
                                 ELSE
@@ -461,10 +508,14 @@ struct FreeCapacityQueryRow {
 }
 
 pub fn free_capacity(rtc_id: db::rtc::Id, conn: &PgConnection) -> Result<i32, Error> {
-    use diesel::{prelude::*, sql_types::Uuid};
+    use diesel::{
+        prelude::*,
+        sql_types::{BigInt, Uuid},
+    };
 
     diesel::sql_query(FREE_CAPACITY_SQL)
         .bind::<Uuid, _>(rtc_id)
+        .bind::<BigInt, _>(DEFAULT_VIDEO_REMB)
         .get_result::<FreeCapacityQueryRow>(conn)
         .map(|row| row.free_capacity)
 }
@@ -501,9 +552,11 @@ pub struct ReserveLoadQueryLoad {
 pub fn reserve_load_for_each_backend(
     conn: &PgConnection,
 ) -> Result<Vec<ReserveLoadQueryLoad>, Error> {
-    use diesel::prelude::*;
+    use diesel::{prelude::*, sql_types::BigInt};
 
-    diesel::sql_query(LOAD_FOR_EACH_BACKEND).get_results(conn)
+    diesel::sql_query(LOAD_FOR_EACH_BACKEND)
+        .bind::<BigInt, _>(DEFAULT_VIDEO_REMB)
+        .get_results(conn)
 }
 
 const LOAD_FOR_EACH_BACKEND: &str = r#"
@@ -511,10 +564,12 @@ WITH
     room_load AS (
         SELECT
             a.room_id,
-            SUM(COALESCE(rwc.video_remb, 1000000) / 1000000.0) AS taken
+            SUM(COALESCE(rwc.video_remb, r.video_remb, $1) / $1::FLOAT8) AS taken
         FROM agent AS a
         INNER JOIN agent_connection AS ac
         ON ac.agent_id = a.id
+        INNER JOIN room AS r
+        ON r.id = a.room_id
         LEFT JOIN rtc
         ON rtc.id = ac.rtc_id
         LEFT JOIN rtc_writer_config AS rwc
@@ -522,7 +577,9 @@ WITH
         GROUP BY a.room_id
     ),
     active_room AS (
-        SELECT *
+        SELECT
+            *,
+            CEIL(reserve * COALESCE(video_remb, $1) / $1::FLOAT8) AS weighted_reserve
         FROM room
         WHERE backend_id IS NOT NULL
         AND   LOWER(time) <= NOW()
@@ -538,7 +595,7 @@ WITH
                 ar.backend_id,
                 ar.id                   AS room_id,
                 COALESCE(rl.taken, 0)   AS taken,
-                COALESCE(ar.reserve, 0) AS reserve
+                COALESCE(ar.weighted_reserve, 0) AS reserve
             FROM active_room AS ar
             LEFT JOIN room_load AS rl
             ON rl.room_id = ar.id
@@ -554,6 +611,35 @@ LEFT OUTER JOIN janus_backend_load jbl
 ON jb.id = jbl.backend_id;
 "#;
 
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(QueryableByName, Debug)]
+pub struct DrainingBackendRooms {
+    #[sql_type = "svc_agent::sql::Agent_id"]
+    pub backend_id: AgentId,
+    #[sql_type = "diesel::sql_types::BigInt"]
+    pub active_rooms: i64,
+}
+
+// Rooms that are not closed yet may still use the backend.
+const DRAINING_BACKENDS_ROOMS_SQL: &str = r#"
+SELECT
+    jb.id AS backend_id,
+    COUNT(r.id) AS active_rooms
+FROM janus_backend AS jb
+LEFT JOIN room AS r
+ON  r.backend_id = jb.id
+AND (UPPER(r.time) IS NULL OR UPPER(r.time) > NOW())
+WHERE jb.draining
+GROUP BY jb.id
+"#;
+
+pub fn draining_backends_rooms(conn: &PgConnection) -> Result<Vec<DrainingBackendRooms>, Error> {
+    use diesel::prelude::*;
+
+    diesel::sql_query(DRAINING_BACKENDS_ROOMS_SQL).get_results(conn)
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::{Duration, Utc};
@@ -561,7 +647,7 @@ mod tests {
 
     use crate::{
         backend::janus::client::{HandleId, SessionId},
-        db::rtc::SharingPolicy as RtcSharingPolicy,
+        db::{room::QualityPreset, rtc::SharingPolicy as RtcSharingPolicy},
         test_helpers::{prelude::*, test_deps::LocalDeps},
     };
 
@@ -649,4 +735,196 @@ mod tests {
                 assert_eq!(b.load, *expected_load as i64);
             });
     }
+
+    #[async_std::test]
+    async fn balancing_snapshot_skips_draining_backends() {
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let conn = TestDb::with_local_postgres(&postgres)
+            .connection_pool()
+            .get()
+            .expect("Failed to get db conn");
+
+        let draining_backend = shared_helpers::insert_janus_backend(
+            &conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        );
+
+        super::UpdateQuery::new(draining_backend.id().to_owned())
+            .draining(true)
+            .execute(&conn)
+            .expect("Failed to update backend")
+            .expect("Backend not found");
+
+        let backends = super::balancing_snapshot(None, &conn).expect("Db query failed");
+        assert!(backends.is_empty());
+
+        let active_backend = shared_helpers::insert_janus_backend(
+            &conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        );
+
+        let backends = super::balancing_snapshot(None, &conn).expect("Db query failed");
+        assert_eq!(backends.len(), 1);
+        assert_eq!(&backends[0].id, active_backend.id());
+    }
+
+    #[async_std::test]
+    async fn balancing_snapshot_load() {
+        let now = Utc::now();
+
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let conn = TestDb::with_local_postgres(&postgres)
+            .connection_pool()
+            .get()
+            .expect("Failed to get db conn");
+
+        let backend = shared_helpers::insert_janus_backend(
+            &conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        );
+
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((Bound::Included(now), Bound::Unbounded))
+            .rtc_sharing_policy(RtcSharingPolicy::Shared)
+            .backend_id(backend.id())
+            .reserve(10)
+            .insert(&conn);
+
+        let rtc = shared_helpers::insert_rtc_with_room(&conn, &room);
+
+        for i in 0..3 {
+            let agent = TestAgent::new("web", &format!("user{}", i), USR_AUDIENCE);
+            shared_helpers::insert_connected_agent(&conn, agent.agent_id(), room.id(), rtc.id());
+        }
+
+        let backends = super::balancing_snapshot(None, &conn).expect("Db query failed");
+        assert_eq!(backends.len(), 1);
+        assert_eq!(backends[0].load, 10.0);
+        assert_eq!(backends[0].taken, 3.0);
+        assert_eq!(backends[0].connections, 3);
+    }
+
+    #[async_std::test]
+    async fn balancing_snapshot_load_with_quality_preset() {
+        let now = Utc::now();
+
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let conn = TestDb::with_local_postgres(&postgres)
+            .connection_pool()
+            .get()
+            .expect("Failed to get db conn");
+
+        let backend = shared_helpers::insert_janus_backend(
+            &conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        );
+
+        // 10 reserved slots of 2.5 Mbps writers.
+        factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((Bound::Included(now), Bound::Unbounded))
+            .rtc_sharing_policy(RtcSharingPolicy::Shared)
+            .backend_id(backend.id())
+            .reserve(10)
+            .quality_preset(QualityPreset::High, 2_500_000)
+            .insert(&conn);
+
+        let backends = super::balancing_snapshot(None, &conn).expect("Db query failed");
+        assert_eq!(backends.len(), 1);
+        assert_eq!(backends[0].load, 25.0);
+        assert_eq!(backends[0].taken, 0.0);
+    }
+
+    #[async_std::test]
+    async fn balancing_snapshot_load_matches_room_balancing_reserve() {
+        let now = Utc::now();
+
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let conn = TestDb::with_local_postgres(&postgres)
+            .connection_pool()
+            .get()
+            .expect("Failed to get db conn");
+
+        let backend = shared_helpers::insert_janus_backend(
+            &conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        );
+
+        // 3 reserved slots of 1.5 Mbps writers round up to 5.
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((Bound::Included(now), Bound::Unbounded))
+            .rtc_sharing_policy(RtcSharingPolicy::Shared)
+            .backend_id(backend.id())
+            .reserve(3)
+            .quality_preset(QualityPreset::Medium, 1_500_000)
+            .insert(&conn);
+
+        // The rtc has no writer config so readers are weighed by the room's preset bitrate.
+        let rtc = shared_helpers::insert_rtc_with_room(&conn, &room);
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        shared_helpers::insert_connected_agent(&conn, agent.agent_id(), room.id(), rtc.id());
+
+        let backends = super::balancing_snapshot(None, &conn).expect("Db query failed");
+        assert_eq!(backends.len(), 1);
+        assert_eq!(backends[0].load, f64::from(room.balancing_reserve()));
+        assert_eq!(backends[0].load, 5.0);
+        assert_eq!(backends[0].taken, 1.5);
+    }
+
+    #[async_std::test]
+    async fn draining_backends_rooms() {
+        let now = Utc::now();
+
+        let local_deps = LocalDeps::new();
+        let postgres = local_deps.run_postgres();
+        let conn = TestDb::with_local_postgres(&postgres)
+            .connection_pool()
+            .get()
+            .expect("Failed to get db conn");
+
+        let backend = shared_helpers::insert_janus_backend(
+            &conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        );
+
+        super::UpdateQuery::new(backend.id().to_owned())
+            .draining(true)
+            .execute(&conn)
+            .expect("Failed to update backend");
+
+        factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((
+                Bound::Included(now),
+                Bound::Excluded(now + Duration::hours(1)),
+            ))
+            .rtc_sharing_policy(RtcSharingPolicy::Shared)
+            .backend_id(backend.id())
+            .insert(&conn);
+
+        shared_helpers::insert_closed_room_with_backend_id(&conn, backend.id());
+
+        let rooms = super::draining_backends_rooms(&conn).expect("Db query failed");
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(&rooms[0].backend_id, backend.id());
+        assert_eq!(rooms[0].active_rooms, 1);
+    }
 }
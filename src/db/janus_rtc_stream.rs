@@ -9,7 +9,7 @@ use crate::{
     backend::janus::client::HandleId,
     db,
     db::rtc::Object as Rtc,
-    schema::{janus_rtc_stream, rtc},
+    schema::{janus_rtc_stream, room, rtc},
 };
 use derive_more::{Display, FromStr};
 use diesel_derive_newtype::DieselNewType;
@@ -29,6 +29,8 @@ type AllColumns = (
     janus_rtc_stream::sent_by,
     janus_rtc_stream::time,
     janus_rtc_stream::created_at,
+    janus_rtc_stream::slow_link_count,
+    janus_rtc_stream::media_loss_count,
 );
 const ALL_COLUMNS: AllColumns = (
     janus_rtc_stream::id,
@@ -39,6 +41,8 @@ const ALL_COLUMNS: AllColumns = (
     janus_rtc_stream::sent_by,
     janus_rtc_stream::time,
     janus_rtc_stream::created_at,
+    janus_rtc_stream::slow_link_count,
+    janus_rtc_stream::media_loss_count,
 );
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -67,6 +71,10 @@ pub struct Object {
     time: Option<Time>,
     #[serde(with = "ts_seconds")]
     created_at: DateTime<Utc>,
+    #[serde(default)]
+    slow_link_count: i32,
+    #[serde(default)]
+    media_loss_count: i32,
 }
 
 impl Object {
@@ -105,6 +113,14 @@ impl Object {
         self.created_at
     }
 
+    pub fn slow_link_count(&self) -> i32 {
+        self.slow_link_count
+    }
+
+    pub fn media_loss_count(&self) -> i32 {
+        self.media_loss_count
+    }
+
     pub fn set_time(&mut self, time: Option<Time>) -> &mut Self {
         self.time = time;
         self
@@ -213,6 +229,20 @@ impl ListQuery {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// The number of streams being published in the audience's rooms.
+pub fn count_active(audience: &str, conn: &PgConnection) -> Result<i64, Error> {
+    use diesel::{dsl::sql, prelude::*};
+
+    janus_rtc_stream::table
+        .inner_join(rtc::table.inner_join(room::table))
+        .filter(room::audience.eq(audience))
+        .filter(sql(ACTIVE_SQL))
+        .count()
+        .get_result(conn)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 #[derive(Debug, Default)]
 pub struct ListWithRtcQuery<'a> {
     active: Option<bool>,
@@ -249,6 +279,10 @@ impl<'a> ListWithRtcQuery<'a> {
             Some(false) => q = q.filter(sql(&format!("not {}", ACTIVE_SQL))),
         }
 
+        if let Some(backend_id) = self.backend_id {
+            q = q.filter(janus_rtc_stream::backend_id.eq(backend_id));
+        }
+
         q.order_by(janus_rtc_stream::id)
             .select((self::ALL_COLUMNS, super::rtc::ALL_COLUMNS))
             .load(conn)
@@ -333,3 +367,29 @@ pub fn stop(id: db::janus_rtc_stream::Id, conn: &PgConnection) -> Result<Option<
         .get_result(conn)
         .optional()
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub fn register_slow_link(
+    id: db::janus_rtc_stream::Id,
+    conn: &PgConnection,
+) -> Result<Option<Object>, Error> {
+    use diesel::prelude::*;
+
+    diesel::update(janus_rtc_stream::table.filter(janus_rtc_stream::id.eq(id)))
+        .set(janus_rtc_stream::slow_link_count.eq(janus_rtc_stream::slow_link_count + 1))
+        .get_result(conn)
+        .optional()
+}
+
+pub fn register_media_loss(
+    id: db::janus_rtc_stream::Id,
+    conn: &PgConnection,
+) -> Result<Option<Object>, Error> {
+    use diesel::prelude::*;
+
+    diesel::update(janus_rtc_stream::table.filter(janus_rtc_stream::id.eq(id)))
+        .set(janus_rtc_stream::media_loss_count.eq(janus_rtc_stream::media_loss_count + 1))
+        .get_result(conn)
+        .optional()
+}
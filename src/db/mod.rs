@@ -22,19 +22,30 @@ pub fn create_pool(url: &str, size: u32, idle_size: Option<u32>, timeout: u64) -
 
 pub mod sql {
     pub use super::{
-        agent::Agent_status, recording::Recording_status, room::Room_backend,
+        agent::Agent_status,
+        agent_session::Agent_session_leave_reason,
+        idempotency_key::Idempotency_key_kind,
+        recording::Recording_status,
+        room::{Quality_preset, Room_backend},
         rtc::Rtc_sharing_policy,
+        stage_member::Stage_member_status,
     };
     pub use svc_agent::sql::{Account_id, Agent_id};
 }
 
 pub mod agent;
 pub mod agent_connection;
+pub mod agent_session;
+pub mod idempotency_key;
 pub mod janus_backend;
 pub mod janus_rtc_stream;
+pub mod raised_hand;
 pub mod recording;
 pub mod room;
+pub mod room_ban;
 pub mod rtc;
 pub mod rtc_reader_config;
 pub mod rtc_writer_config;
 pub mod rtc_writer_config_snapshot;
+pub mod stage_member;
+pub mod usage;
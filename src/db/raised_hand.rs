@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use diesel::{pg::PgConnection, result::Error};
+use svc_agent::AgentId;
+
+use crate::{db, db::room::Object as Room, schema::raised_hand};
+
+////////////////////////////////////////////////////////////////////////////////
+
+type AllColumns = (
+    raised_hand::room_id,
+    raised_hand::agent_id,
+    raised_hand::raised_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    raised_hand::room_id,
+    raised_hand::agent_id,
+    raised_hand::raised_at,
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName, Associations)]
+#[belongs_to(Room, foreign_key = "room_id")]
+#[table_name = "raised_hand"]
+#[primary_key(room_id, agent_id)]
+pub struct Object {
+    room_id: db::room::Id,
+    agent_id: AgentId,
+    raised_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn agent_id(&self) -> &AgentId {
+        &self.agent_id
+    }
+
+    pub fn raised_at(&self) -> DateTime<Utc> {
+        self.raised_at
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Lists raised hands in the room in the order of raising.
+#[derive(Debug)]
+pub struct ListQuery {
+    room_id: db::room::Id,
+}
+
+impl ListQuery {
+    pub fn new(room_id: db::room::Id) -> Self {
+        Self { room_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Vec<Object>, Error> {
+        use diesel::prelude::*;
+
+        raised_hand::table
+            .filter(raised_hand::room_id.eq(self.room_id))
+            .select(ALL_COLUMNS)
+            .order_by(raised_hand::raised_at.asc())
+            .get_results(conn)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Raises the agent's hand keeping its place in the queue if it's already raised.
+///
+/// Returns the number of raised hands, i.e. 0 if the hand has already been raised.
+#[derive(Debug, Insertable)]
+#[table_name = "raised_hand"]
+pub struct InsertQuery<'a> {
+    room_id: db::room::Id,
+    agent_id: &'a AgentId,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(room_id: db::room::Id, agent_id: &'a AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<usize, Error> {
+        use diesel::prelude::*;
+
+        diesel::insert_into(raised_hand::table)
+            .values(self)
+            .on_conflict_do_nothing()
+            .execute(conn)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Lowers the agent's hand. Returns the number of lowered hands.
+#[derive(Debug)]
+pub struct DeleteQuery<'a> {
+    room_id: db::room::Id,
+    agent_id: &'a AgentId,
+}
+
+impl<'a> DeleteQuery<'a> {
+    pub fn new(room_id: db::room::Id, agent_id: &'a AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<usize, Error> {
+        use diesel::prelude::*;
+
+        let target = raised_hand::table
+            .filter(raised_hand::room_id.eq(self.room_id))
+            .filter(raised_hand::agent_id.eq(self.agent_id));
+
+        diesel::delete(target).execute(conn)
+    }
+}
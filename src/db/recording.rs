@@ -2,7 +2,7 @@ use std::{fmt, ops::Bound};
 
 use super::rtc::Object as Rtc;
 use crate::db;
-use crate::schema::recording;
+use crate::schema::{recording, rtc};
 use chrono::{DateTime, Utc};
 use diesel::{pg::PgConnection, result::Error};
 use diesel_derive_enum::DbEnum;
@@ -110,6 +110,22 @@ impl FindQuery {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+pub fn in_progress_by_room(
+    room_id: db::room::Id,
+    conn: &PgConnection,
+) -> Result<Vec<Object>, Error> {
+    use diesel::prelude::*;
+
+    recording::table
+        .inner_join(rtc::table)
+        .filter(rtc::room_id.eq(room_id))
+        .filter(recording::status.eq(Status::InProgress))
+        .select(ALL_COLUMNS)
+        .get_results(conn)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 #[derive(Debug, Insertable)]
 #[table_name = "recording"]
 pub struct InsertQuery {
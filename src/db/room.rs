@@ -36,6 +36,15 @@ type AllColumns = (
     room::backend_id,
     room::rtc_sharing_policy,
     room::classroom_id,
+    room::closed_at,
+    room::closing_warning_offset,
+    room::locked,
+    room::requires_admission,
+    room::max_agents,
+    room::parent_id,
+    room::stage_mode,
+    room::quality_preset,
+    room::video_remb,
 );
 
 const ALL_COLUMNS: AllColumns = (
@@ -49,6 +58,15 @@ const ALL_COLUMNS: AllColumns = (
     room::backend_id,
     room::rtc_sharing_policy,
     room::classroom_id,
+    room::closed_at,
+    room::closing_warning_offset,
+    room::locked,
+    room::requires_admission,
+    room::max_agents,
+    room::parent_id,
+    room::stage_mode,
+    room::quality_preset,
+    room::video_remb,
 );
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -99,6 +117,26 @@ impl From<RoomBackend> for RtcSharingPolicy {
     }
 }
 
+/// Named writers' quality tier which is translated to the bitrate configured for the audience.
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+#[DieselType = "Quality_preset"]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    AudioOnly,
+}
+
+impl QualityPreset {
+    pub fn send_video(self) -> bool {
+        self != Self::AudioOnly
+    }
+}
+
+// Writer's bitrate the backend load calculation assumes when it's not limited.
+pub(crate) const DEFAULT_VIDEO_REMB: i64 = 1_000_000;
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(
@@ -121,6 +159,24 @@ pub struct Object {
     backend_id: Option<AgentId>,
     rtc_sharing_policy: RtcSharingPolicy,
     classroom_id: Option<Uuid>,
+    // When the service has actually closed the room, i.e. notified and disconnected its agents.
+    #[serde(skip)]
+    closed_at: Option<DateTime<Utc>>,
+    // The smallest offset in seconds before closing at which participants have been warned.
+    #[serde(skip)]
+    closing_warning_offset: Option<i32>,
+    locked: bool,
+    requires_admission: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_agents: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_id: Option<Id>,
+    stage_mode: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quality_preset: Option<QualityPreset>,
+    // The preset's bitrate at the moment of the room creation.
+    #[serde(skip)]
+    video_remb: Option<i64>,
 }
 
 impl Object {
@@ -136,6 +192,10 @@ impl Object {
         &self.time
     }
 
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
     pub fn reserve(&self) -> Option<i32> {
         self.reserve
     }
@@ -163,6 +223,65 @@ impl Object {
     pub fn classroom_id(&self) -> Option<Uuid> {
         self.classroom_id
     }
+
+    #[cfg(test)]
+    pub fn closed_at(&self) -> Option<DateTime<Utc>> {
+        self.closed_at
+    }
+
+    pub fn closing_warning_offset(&self) -> Option<i32> {
+        self.closing_warning_offset
+    }
+
+    /// Only moderators may enter a locked room.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Agents have to be admitted by a moderator to enter the room.
+    pub fn requires_admission(&self) -> bool {
+        self.requires_admission
+    }
+
+    /// The maximum number of agents allowed to be in the room at the same time.
+    pub fn max_agents(&self) -> Option<i32> {
+        self.max_agents
+    }
+
+    /// The room which this one is a breakout room of.
+    pub fn parent_id(&self) -> Option<Id> {
+        self.parent_id
+    }
+
+    /// Only moderators and agents they have promoted to the stage may publish.
+    pub fn is_stage_mode(&self) -> bool {
+        self.stage_mode
+    }
+
+    /// The default quality preset for the room's writers.
+    pub fn quality_preset(&self) -> Option<QualityPreset> {
+        self.quality_preset
+    }
+
+    /// The bitrate of the room's quality preset for the room's writers.
+    pub fn video_remb(&self) -> Option<i64> {
+        self.video_remb
+    }
+
+    /// The reserve to choose a backend for the room with that is the number of reserved slots
+    /// weighed by the bitrate of the room's quality preset.
+    pub fn balancing_reserve(&self) -> i32 {
+        let reserve = self.reserve.unwrap_or(1);
+        let video_remb = self.video_remb.unwrap_or(DEFAULT_VIDEO_REMB);
+        (f64::from(reserve) * video_remb as f64 / DEFAULT_VIDEO_REMB as f64).ceil() as i32
+    }
+
+    pub fn closes_at(&self) -> Option<DateTime<Utc>> {
+        match self.time.1 {
+            Bound::Included(t) | Bound::Excluded(t) => Some(t),
+            Bound::Unbounded => None,
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -193,6 +312,29 @@ impl FindQueryable for FindQuery {
     }
 }
 
+/// Locks the room until the end of the transaction to serialize concurrent changes of it.
+#[derive(Debug)]
+pub struct LockQuery {
+    id: Id,
+}
+
+impl LockQuery {
+    pub fn new(id: Id) -> Self {
+        Self { id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<(), Error> {
+        use diesel::prelude::*;
+
+        room::table
+            .filter(room::id.eq(self.id))
+            .select(room::id)
+            .for_update()
+            .get_result::<Id>(conn)
+            .map(|_| ())
+    }
+}
+
 #[derive(Debug)]
 pub struct FindByRtcIdQuery {
     rtc_id: db::rtc::Id,
@@ -219,6 +361,148 @@ impl FindQueryable for FindByRtcIdQuery {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+const CLOSED_SQL: &str = "coalesce(upper(\"room\".\"time\") < now(), false)";
+
+#[derive(Debug, Default)]
+pub struct ListQuery<'a> {
+    audience: Option<&'a str>,
+    classroom_id: Option<Uuid>,
+    time: Option<Time>,
+    rtc_sharing_policy: Option<RtcSharingPolicy>,
+    tags: Option<&'a JsonValue>,
+    closed: Option<bool>,
+    parent_id: Option<Id>,
+    after: Option<(DateTime<Utc>, Id)>,
+    limit: Option<i64>,
+}
+
+impl<'a> ListQuery<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn audience(self, audience: &'a str) -> Self {
+        Self {
+            audience: Some(audience),
+            ..self
+        }
+    }
+
+    pub fn classroom_id(self, classroom_id: Uuid) -> Self {
+        Self {
+            classroom_id: Some(classroom_id),
+            ..self
+        }
+    }
+
+    pub fn time(self, time: Time) -> Self {
+        Self {
+            time: Some(time),
+            ..self
+        }
+    }
+
+    pub fn rtc_sharing_policy(self, rtc_sharing_policy: RtcSharingPolicy) -> Self {
+        Self {
+            rtc_sharing_policy: Some(rtc_sharing_policy),
+            ..self
+        }
+    }
+
+    pub fn tags(self, tags: &'a JsonValue) -> Self {
+        Self {
+            tags: Some(tags),
+            ..self
+        }
+    }
+
+    pub fn closed(self, closed: bool) -> Self {
+        Self {
+            closed: Some(closed),
+            ..self
+        }
+    }
+
+    pub fn parent_id(self, parent_id: Id) -> Self {
+        Self {
+            parent_id: Some(parent_id),
+            ..self
+        }
+    }
+
+    /// Returns rooms going after the given one in the descending `(created_at, id)` order.
+    pub fn after(self, created_at: DateTime<Utc>, id: Id) -> Self {
+        Self {
+            after: Some((created_at, id)),
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Vec<Object>, Error> {
+        use diesel::{
+            dsl::sql,
+            prelude::*,
+            sql_types::{Jsonb, Tstzrange},
+        };
+
+        let mut q = room::table.into_boxed();
+
+        if let Some(audience) = self.audience {
+            q = q.filter(room::audience.eq(audience));
+        }
+
+        if let Some(classroom_id) = self.classroom_id {
+            q = q.filter(room::classroom_id.eq(classroom_id));
+        }
+
+        if let Some(time) = self.time {
+            q = q.filter(sql("\"room\".\"time\" && ").bind::<Tstzrange, _>(time));
+        }
+
+        if let Some(rtc_sharing_policy) = self.rtc_sharing_policy {
+            q = q.filter(room::rtc_sharing_policy.eq(rtc_sharing_policy));
+        }
+
+        if let Some(tags) = self.tags {
+            q = q.filter(sql("\"room\".\"tags\"::jsonb @> ").bind::<Jsonb, _>(tags));
+        }
+
+        match self.closed {
+            None => (),
+            Some(true) => q = q.filter(sql(CLOSED_SQL)),
+            Some(false) => q = q.filter(sql(&format!("not {}", CLOSED_SQL))),
+        }
+
+        if let Some(parent_id) = self.parent_id {
+            q = q.filter(room::parent_id.eq(parent_id));
+        }
+
+        if let Some((created_at, id)) = self.after {
+            q = q.filter(
+                room::created_at
+                    .lt(created_at)
+                    .or(room::created_at.eq(created_at).and(room::id.lt(id))),
+            );
+        }
+
+        if let Some(limit) = self.limit {
+            q = q.limit(limit);
+        }
+
+        q.order_by((room::created_at.desc(), room::id.desc()))
+            .get_results(conn)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 // Filtering out rooms with every recording ready using left and inner joins
 // and condition that recording.rtc_id is null. In diagram below room1
 // and room3 will be selected (room1 - there's one recording that is not
@@ -251,6 +535,139 @@ pub fn finished_with_in_progress_recordings(
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Rooms which time is over but the service hasn't closed them yet.
+pub fn finished_unclosed(conn: &PgConnection) -> Result<Vec<Object>, Error> {
+    use diesel::{dsl::sql, prelude::*};
+
+    room::table
+        .filter(room::closed_at.is_null())
+        .filter(sql("upper(\"room\".\"time\") <= now()"))
+        .get_results(conn)
+}
+
+/// The number of the audience's rooms which haven't been closed yet including scheduled ones.
+pub fn count_open(audience: &str, conn: &PgConnection) -> Result<i64, Error> {
+    use diesel::{dsl::sql, prelude::*};
+
+    room::table
+        .filter(room::audience.eq(audience))
+        .filter(room::closed_at.is_null())
+        .filter(sql(&format!("not {}", CLOSED_SQL)))
+        .count()
+        .get_result(conn)
+}
+
+const BOUND_OVERLONG_SQL: &str = r#"
+    UPDATE room
+    SET time = tstzrange(lower(time), now(), '[)')
+    WHERE closed_at IS NULL
+    AND   upper_inf(time)
+    AND   NOT lower_inf(time)
+    AND   lower(time) + $1 * INTERVAL '1 hour' <= now()
+"#;
+
+/// Sets the closing time to now for unbounded rooms opened longer than `max_duration` hours ago.
+pub fn bound_overlong(max_duration: i64, conn: &PgConnection) -> Result<usize, Error> {
+    use diesel::{prelude::*, sql_types::BigInt};
+
+    diesel::sql_query(BOUND_OVERLONG_SQL)
+        .bind::<BigInt, _>(max_duration)
+        .execute(conn)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Marks the room as closed by the service.
+///
+/// Returns `None` if the room has already been closed or is being closed by a concurrent
+/// transaction so only one of concurrent callers gets it and performs the closing.
+/// The room stays locked until the end of the transaction.
+#[derive(Debug)]
+pub struct CloseQuery {
+    id: Id,
+}
+
+impl CloseQuery {
+    pub fn new(id: Id) -> Self {
+        Self { id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
+        use diesel::prelude::*;
+
+        let target = room::table
+            .filter(room::id.eq(self.id))
+            .filter(room::closed_at.is_null());
+
+        // Skip the room instead of waiting for another instance closing it at the moment.
+        let maybe_locked = target
+            .clone()
+            .select(room::id)
+            .for_update()
+            .skip_locked()
+            .get_result::<Id>(conn)
+            .optional()?;
+
+        if maybe_locked.is_none() {
+            return Ok(None);
+        }
+
+        diesel::update(target)
+            .set(room::closed_at.eq(Utc::now()))
+            .get_result(conn)
+            .optional()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Open rooms which are going to be closed within `seconds`.
+pub fn closing_within(seconds: i32, conn: &PgConnection) -> Result<Vec<Object>, Error> {
+    use diesel::{dsl::sql, prelude::*, sql_types::Integer};
+
+    room::table
+        .filter(room::closed_at.is_null())
+        .filter(sql("upper(\"room\".\"time\") > now()"))
+        .filter(
+            sql("upper(\"room\".\"time\") <= now() + INTERVAL '1 second' * ")
+                .bind::<Integer, _>(seconds),
+        )
+        .get_results(conn)
+}
+
+/// Remembers that participants have been warned about the room closing at `offset` seconds
+/// before it.
+///
+/// Returns `None` if they have already been warned at the same or a smaller offset.
+#[derive(Debug)]
+pub struct WarnClosingQuery {
+    id: Id,
+    offset: i32,
+}
+
+impl WarnClosingQuery {
+    pub fn new(id: Id, offset: i32) -> Self {
+        Self { id, offset }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
+        use diesel::prelude::*;
+
+        let target = room::table.filter(room::id.eq(self.id)).filter(
+            room::closing_warning_offset
+                .is_null()
+                .or(room::closing_warning_offset.gt(self.offset)),
+        );
+
+        diesel::update(target)
+            .set(room::closing_warning_offset.eq(self.offset))
+            .get_result(conn)
+            .optional()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 #[derive(Debug, Insertable)]
 #[table_name = "room"]
 pub struct InsertQuery<'a> {
@@ -262,6 +679,13 @@ pub struct InsertQuery<'a> {
     backend_id: Option<&'a AgentId>,
     rtc_sharing_policy: RtcSharingPolicy,
     classroom_id: Option<Uuid>,
+    locked: Option<bool>,
+    requires_admission: Option<bool>,
+    max_agents: Option<i32>,
+    parent_id: Option<Id>,
+    stage_mode: Option<bool>,
+    quality_preset: Option<QualityPreset>,
+    video_remb: Option<i64>,
 }
 
 impl<'a> InsertQuery<'a> {
@@ -275,6 +699,13 @@ impl<'a> InsertQuery<'a> {
             backend_id: None,
             rtc_sharing_policy,
             classroom_id: None,
+            locked: None,
+            requires_admission: None,
+            max_agents: None,
+            parent_id: None,
+            stage_mode: None,
+            quality_preset: None,
+            video_remb: None,
         }
     }
 
@@ -307,6 +738,49 @@ impl<'a> InsertQuery<'a> {
         }
     }
 
+    pub fn locked(self, locked: bool) -> Self {
+        Self {
+            locked: Some(locked),
+            ..self
+        }
+    }
+
+    pub fn requires_admission(self, requires_admission: bool) -> Self {
+        Self {
+            requires_admission: Some(requires_admission),
+            ..self
+        }
+    }
+
+    pub fn max_agents(self, max_agents: i32) -> Self {
+        Self {
+            max_agents: Some(max_agents),
+            ..self
+        }
+    }
+
+    pub fn parent_id(self, parent_id: Id) -> Self {
+        Self {
+            parent_id: Some(parent_id),
+            ..self
+        }
+    }
+
+    pub fn stage_mode(self, stage_mode: bool) -> Self {
+        Self {
+            stage_mode: Some(stage_mode),
+            ..self
+        }
+    }
+
+    pub fn quality_preset(self, quality_preset: QualityPreset, video_remb: u32) -> Self {
+        Self {
+            quality_preset: Some(quality_preset),
+            video_remb: Some(video_remb.into()),
+            ..self
+        }
+    }
+
     pub fn execute(&self, conn: &PgConnection) -> Result<Object, Error> {
         use crate::schema::room::dsl::room;
         use diesel::RunQueryDsl;
@@ -326,6 +800,11 @@ pub struct UpdateQuery<'a> {
     tags: Option<JsonValue>,
     backend_id: Option<&'a AgentId>,
     classroom_id: Option<Uuid>,
+    closing_warning_offset: Option<Option<i32>>,
+    locked: Option<bool>,
+    requires_admission: Option<bool>,
+    max_agents: Option<Option<i32>>,
+    stage_mode: Option<bool>,
 }
 
 impl<'a> UpdateQuery<'a> {
@@ -337,11 +816,23 @@ impl<'a> UpdateQuery<'a> {
             reserve: Default::default(),
             tags: Default::default(),
             classroom_id: Default::default(),
+            closing_warning_offset: Default::default(),
+            locked: Default::default(),
+            requires_admission: Default::default(),
+            max_agents: Default::default(),
+            stage_mode: Default::default(),
         }
     }
 
     pub fn time(self, time: Option<Time>) -> Self {
-        Self { time, ..self }
+        // Participants have to be warned once again about the new closing time.
+        let closing_warning_offset = time.map(|_| None);
+
+        Self {
+            time,
+            closing_warning_offset,
+            ..self
+        }
     }
 
     pub fn reserve(self, reserve: Option<Option<i32>>) -> Self {
@@ -363,6 +854,25 @@ impl<'a> UpdateQuery<'a> {
         }
     }
 
+    pub fn locked(self, locked: Option<bool>) -> Self {
+        Self { locked, ..self }
+    }
+
+    pub fn requires_admission(self, requires_admission: Option<bool>) -> Self {
+        Self {
+            requires_admission,
+            ..self
+        }
+    }
+
+    pub fn max_agents(self, max_agents: Option<Option<i32>>) -> Self {
+        Self { max_agents, ..self }
+    }
+
+    pub fn stage_mode(self, stage_mode: Option<bool>) -> Self {
+        Self { stage_mode, ..self }
+    }
+
     pub fn execute(&self, conn: &PgConnection) -> Result<Object, Error> {
         use diesel::prelude::*;
 
@@ -441,4 +951,57 @@ mod tests {
             }
         }
     }
+
+    mod balancing_reserve {
+        use chrono::Duration;
+
+        use super::super::*;
+        use crate::test_helpers::{prelude::*, test_deps::LocalDeps};
+
+        fn insert_room(
+            conn: &PgConnection,
+            reserve: i32,
+            maybe_quality_preset: Option<(QualityPreset, u32)>,
+        ) -> Object {
+            let now = Utc::now();
+
+            let mut q = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((
+                    Bound::Included(now),
+                    Bound::Excluded(now + Duration::hours(1)),
+                ))
+                .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                .reserve(reserve);
+
+            if let Some((quality_preset, video_remb)) = maybe_quality_preset {
+                q = q.quality_preset(quality_preset, video_remb);
+            }
+
+            q.insert(conn)
+        }
+
+        #[test]
+        fn weighs_reserve_by_quality_preset_bitrate() {
+            let local_deps = LocalDeps::new();
+            let postgres = local_deps.run_postgres();
+            let db = TestDb::with_local_postgres(&postgres);
+            let conn = db
+                .connection_pool()
+                .get()
+                .expect("Failed to get db connection");
+
+            let room = insert_room(&conn, 3, None);
+            assert_eq!(room.quality_preset(), None);
+            assert_eq!(room.balancing_reserve(), 3);
+
+            let room = insert_room(&conn, 3, Some((QualityPreset::Low, 300_000)));
+            assert_eq!(room.quality_preset(), Some(QualityPreset::Low));
+            assert_eq!(room.balancing_reserve(), 1);
+
+            let room = insert_room(&conn, 3, Some((QualityPreset::High, 2_500_000)));
+            assert_eq!(room.quality_preset(), Some(QualityPreset::High));
+            assert_eq!(room.balancing_reserve(), 8);
+        }
+    }
 }
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use diesel::{pg::PgConnection, result::Error};
+use svc_agent::{AccountId, AgentId};
+
+use crate::{db, db::room::Object as Room, schema::room_ban};
+
+////////////////////////////////////////////////////////////////////////////////
+
+type AllColumns = (
+    room_ban::room_id,
+    room_ban::account_id,
+    room_ban::banned_by,
+    room_ban::created_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    room_ban::room_id,
+    room_ban::account_id,
+    room_ban::banned_by,
+    room_ban::created_at,
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName, Associations)]
+#[belongs_to(Room, foreign_key = "room_id")]
+#[table_name = "room_ban"]
+#[primary_key(room_id, account_id)]
+pub struct Object {
+    room_id: db::room::Id,
+    account_id: AccountId,
+    banned_by: AgentId,
+    created_at: DateTime<Utc>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FindQuery<'a> {
+    room_id: db::room::Id,
+    account_id: &'a AccountId,
+}
+
+impl<'a> FindQuery<'a> {
+    pub fn new(room_id: db::room::Id, account_id: &'a AccountId) -> Self {
+        Self {
+            room_id,
+            account_id,
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
+        use diesel::prelude::*;
+
+        room_ban::table
+            .filter(room_ban::room_id.eq(self.room_id))
+            .filter(room_ban::account_id.eq(self.account_id))
+            .select(ALL_COLUMNS)
+            .get_result(conn)
+            .optional()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Insertable)]
+#[table_name = "room_ban"]
+pub struct InsertQuery<'a> {
+    room_id: db::room::Id,
+    account_id: &'a AccountId,
+    banned_by: &'a AgentId,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(room_id: db::room::Id, account_id: &'a AccountId, banned_by: &'a AgentId) -> Self {
+        Self {
+            room_id,
+            account_id,
+            banned_by,
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Object, Error> {
+        use diesel::prelude::*;
+
+        diesel::insert_into(room_ban::table)
+            .values(self)
+            .on_conflict((room_ban::room_id, room_ban::account_id))
+            .do_update()
+            .set(room_ban::banned_by.eq(self.banned_by))
+            .returning(ALL_COLUMNS)
+            .get_result(conn)
+    }
+}
@@ -0,0 +1,167 @@
+use chrono::{DateTime, Utc};
+use diesel::{pg::PgConnection, result::Error};
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+use svc_agent::AgentId;
+
+use crate::{db, db::room::Object as Room, schema::stage_member};
+
+////////////////////////////////////////////////////////////////////////////////
+
+type AllColumns = (
+    stage_member::room_id,
+    stage_member::agent_id,
+    stage_member::status,
+    stage_member::invited_by,
+    stage_member::created_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    stage_member::room_id,
+    stage_member::agent_id,
+    stage_member::status,
+    stage_member::invited_by,
+    stage_member::created_at,
+);
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[PgType = "stage_member_status"]
+#[DieselType = "Stage_member_status"]
+pub enum Status {
+    Invited,
+    Accepted,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName, Associations)]
+#[belongs_to(Room, foreign_key = "room_id")]
+#[table_name = "stage_member"]
+#[primary_key(room_id, agent_id)]
+pub struct Object {
+    room_id: db::room::Id,
+    agent_id: AgentId,
+    status: Status,
+    invited_by: AgentId,
+    created_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn status(&self) -> Status {
+        self.status
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FindQuery<'a> {
+    room_id: db::room::Id,
+    agent_id: &'a AgentId,
+}
+
+impl<'a> FindQuery<'a> {
+    pub fn new(room_id: db::room::Id, agent_id: &'a AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
+        use diesel::prelude::*;
+
+        stage_member::table
+            .filter(stage_member::room_id.eq(self.room_id))
+            .filter(stage_member::agent_id.eq(self.agent_id))
+            .select(ALL_COLUMNS)
+            .get_result(conn)
+            .optional()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Invites the agent to the stage. Re-inviting an agent who is already on the stage is a no-op.
+#[derive(Debug, Insertable)]
+#[table_name = "stage_member"]
+pub struct InsertQuery<'a> {
+    room_id: db::room::Id,
+    agent_id: &'a AgentId,
+    invited_by: &'a AgentId,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(room_id: db::room::Id, agent_id: &'a AgentId, invited_by: &'a AgentId) -> Self {
+        Self {
+            room_id,
+            agent_id,
+            invited_by,
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Object, Error> {
+        use diesel::prelude::*;
+
+        diesel::insert_into(stage_member::table)
+            .values(self)
+            .on_conflict((stage_member::room_id, stage_member::agent_id))
+            .do_update()
+            .set(stage_member::invited_by.eq(self.invited_by))
+            .returning(ALL_COLUMNS)
+            .get_result(conn)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Puts the invited agent on the stage.
+///
+/// Returns `None` if the agent hasn't been invited.
+#[derive(Debug)]
+pub struct AcceptQuery<'a> {
+    room_id: db::room::Id,
+    agent_id: &'a AgentId,
+}
+
+impl<'a> AcceptQuery<'a> {
+    pub fn new(room_id: db::room::Id, agent_id: &'a AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Option<Object>, Error> {
+        use diesel::prelude::*;
+
+        let target = stage_member::table
+            .filter(stage_member::room_id.eq(self.room_id))
+            .filter(stage_member::agent_id.eq(self.agent_id));
+
+        diesel::update(target)
+            .set(stage_member::status.eq(Status::Accepted))
+            .returning(ALL_COLUMNS)
+            .get_result(conn)
+            .optional()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct DeleteQuery<'a> {
+    room_id: db::room::Id,
+    agent_id: &'a AgentId,
+}
+
+impl<'a> DeleteQuery<'a> {
+    pub fn new(room_id: db::room::Id, agent_id: &'a AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<usize, Error> {
+        use diesel::prelude::*;
+
+        let target = stage_member::table
+            .filter(stage_member::room_id.eq(self.room_id))
+            .filter(stage_member::agent_id.eq(self.agent_id));
+
+        diesel::delete(target).execute(conn)
+    }
+}
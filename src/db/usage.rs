@@ -0,0 +1,234 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use diesel::{pg::PgConnection, result::Error};
+use serde::Serialize;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, QueryableByName, Serialize)]
+pub struct Object {
+    #[sql_type = "diesel::sql_types::Text"]
+    audience: String,
+    #[sql_type = "diesel::sql_types::Uuid"]
+    room_id: db::room::Id,
+    /// UTC day.
+    #[sql_type = "diesel::sql_types::Date"]
+    day: NaiveDate,
+    #[sql_type = "diesel::sql_types::Double"]
+    participant_minutes: f64,
+    #[sql_type = "diesel::sql_types::Double"]
+    publisher_minutes: f64,
+    #[sql_type = "diesel::sql_types::Double"]
+    recorded_minutes: f64,
+}
+
+impl Object {
+    pub fn audience(&self) -> &str {
+        &self.audience
+    }
+
+    pub fn participant_minutes(&self) -> f64 {
+        self.participant_minutes
+    }
+
+    pub fn publisher_minutes(&self) -> f64 {
+        self.publisher_minutes
+    }
+
+    pub fn recorded_minutes(&self) -> f64 {
+        self.recorded_minutes
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Splits usage intervals by UTC days within the period and sums them up for each room:
+// - participant time comes from agents' attendance sessions;
+// - publisher time comes from streams;
+// - recorded time is the time of streams of RTCs being recorded except missing recordings.
+//
+// Unfinished intervals last until now. Intervals are prefiltered by the period
+// to use indexes instead of scanning the whole history.
+const USAGE_SQL: &str = r#"
+    WITH
+        period AS (
+            SELECT TSTZRANGE(LEAST($2, NOW()), LEAST($3, NOW())) AS time
+        ),
+        participant AS (
+            SELECT
+                r.audience,
+                s.room_id,
+                TSTZRANGE(s.started_at, GREATEST(s.started_at, COALESCE(s.finished_at, NOW()))) AS time
+            FROM agent_session AS s
+            INNER JOIN room AS r
+            ON r.id = s.room_id
+            WHERE s.started_at < $3
+            AND   (s.finished_at IS NULL OR s.finished_at > $2)
+        ),
+        publisher AS (
+            SELECT
+                r.audience,
+                rtc.room_id,
+                TSTZRANGE(LOWER(jrs.time), GREATEST(LOWER(jrs.time), COALESCE(UPPER(jrs.time), NOW()))) AS time,
+                rec.rtc_id IS NOT NULL AS recorded
+            FROM janus_rtc_stream AS jrs
+            INNER JOIN rtc
+            ON rtc.id = jrs.rtc_id
+            INNER JOIN room AS r
+            ON r.id = rtc.room_id
+            LEFT JOIN recording AS rec
+            ON  rec.rtc_id = jrs.rtc_id
+            AND rec.status <> 'missing'
+            WHERE LOWER(jrs.time) < $3
+            AND   (UPPER(jrs.time) IS NULL OR UPPER(jrs.time) > $2)
+        ),
+        usage_interval AS (
+            SELECT audience, room_id, time, 'participant' AS kind
+            FROM participant
+            UNION ALL
+            SELECT audience, room_id, time, 'publisher' AS kind
+            FROM publisher
+            UNION ALL
+            SELECT audience, room_id, time, 'recorded' AS kind
+            FROM publisher
+            WHERE recorded
+        ),
+        daily_interval AS (
+            SELECT
+                ui.audience,
+                ui.room_id,
+                (d.day AT TIME ZONE 'UTC')::DATE AS day,
+                ui.kind,
+                ui.time * p.time * TSTZRANGE(d.day, d.day + INTERVAL '1 day') AS time
+            FROM usage_interval AS ui
+            CROSS JOIN period AS p
+            CROSS JOIN LATERAL GENERATE_SERIES(
+                DATE_TRUNC('day', LOWER(ui.time * p.time) AT TIME ZONE 'UTC') AT TIME ZONE 'UTC',
+                UPPER(ui.time * p.time),
+                INTERVAL '1 day'
+            ) AS d(day)
+            WHERE ui.time && p.time
+            AND   ($1::TEXT IS NULL OR ui.audience = $1)
+        )
+    SELECT
+        audience,
+        room_id,
+        day,
+        (
+            COALESCE(SUM(EXTRACT(EPOCH FROM UPPER(time) - LOWER(time))) FILTER (WHERE kind = 'participant'), 0) / 60
+        )::FLOAT8 AS participant_minutes,
+        (
+            COALESCE(SUM(EXTRACT(EPOCH FROM UPPER(time) - LOWER(time))) FILTER (WHERE kind = 'publisher'), 0) / 60
+        )::FLOAT8 AS publisher_minutes,
+        (
+            COALESCE(SUM(EXTRACT(EPOCH FROM UPPER(time) - LOWER(time))) FILTER (WHERE kind = 'recorded'), 0) / 60
+        )::FLOAT8 AS recorded_minutes
+    FROM daily_interval
+    WHERE NOT ISEMPTY(time)
+    GROUP BY audience, room_id, day
+    ORDER BY day, audience, room_id
+"#;
+
+#[derive(Debug)]
+pub struct UsageQuery<'a> {
+    audience: Option<&'a str>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+impl<'a> UsageQuery<'a> {
+    pub fn new(from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        Self {
+            audience: None,
+            from,
+            to,
+        }
+    }
+
+    pub fn audience(self, audience: &'a str) -> Self {
+        Self {
+            audience: Some(audience),
+            ..self
+        }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<Vec<Object>, Error> {
+        use diesel::{
+            prelude::*,
+            sql_types::{Nullable, Text, Timestamptz},
+        };
+
+        diesel::sql_query(USAGE_SQL)
+            .bind::<Nullable<Text>, _>(self.audience)
+            .bind::<Timestamptz, _>(self.from)
+            .bind::<Timestamptz, _>(self.to)
+            .get_results(conn)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(QueryableByName)]
+struct RecordedMinutes {
+    #[sql_type = "diesel::sql_types::Double"]
+    recorded_minutes: f64,
+}
+
+// The total of `recorded_minutes` from `USAGE_SQL` for a single audience
+// without splitting by rooms and days.
+const RECORDED_MINUTES_SQL: &str = r#"
+    WITH
+        period AS (
+            SELECT TSTZRANGE(LEAST($2, NOW()), LEAST($3, NOW())) AS time
+        ),
+        recorded AS (
+            SELECT
+                TSTZRANGE(LOWER(jrs.time), GREATEST(LOWER(jrs.time), COALESCE(UPPER(jrs.time), NOW()))) * p.time AS time
+            FROM janus_rtc_stream AS jrs
+            INNER JOIN rtc
+            ON rtc.id = jrs.rtc_id
+            INNER JOIN room AS r
+            ON r.id = rtc.room_id
+            INNER JOIN recording AS rec
+            ON  rec.rtc_id = jrs.rtc_id
+            AND rec.status <> 'missing'
+            CROSS JOIN period AS p
+            WHERE r.audience = $1
+            AND   LOWER(jrs.time) < $3
+            AND   (UPPER(jrs.time) IS NULL OR UPPER(jrs.time) > $2)
+        )
+    SELECT
+        (
+            COALESCE(SUM(EXTRACT(EPOCH FROM UPPER(time) - LOWER(time))), 0) / 60
+        )::FLOAT8 AS recorded_minutes
+    FROM recorded
+    WHERE NOT ISEMPTY(time)
+"#;
+
+#[derive(Debug)]
+pub struct RecordedMinutesQuery<'a> {
+    audience: &'a str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+impl<'a> RecordedMinutesQuery<'a> {
+    pub fn new(audience: &'a str, from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        Self { audience, from, to }
+    }
+
+    pub fn execute(&self, conn: &PgConnection) -> Result<f64, Error> {
+        use diesel::{
+            prelude::*,
+            sql_types::{Text, Timestamptz},
+        };
+
+        diesel::sql_query(RECORDED_MINUTES_SQL)
+            .bind::<Text, _>(self.audience)
+            .bind::<Timestamptz, _>(self.from)
+            .bind::<Timestamptz, _>(self.to)
+            .get_result::<RecordedMinutes>(conn)
+            .map(|row| row.recorded_minutes)
+    }
+}
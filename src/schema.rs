@@ -23,6 +23,34 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::db::sql::*;
+
+    agent_session (id) {
+        id -> Uuid,
+        agent_id -> Agent_id,
+        room_id -> Uuid,
+        started_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+        leave_reason -> Nullable<Agent_session_leave_reason>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::db::sql::*;
+
+    idempotency_key (audience, kind, key) {
+        audience -> Text,
+        key -> Text,
+        room_id -> Uuid,
+        rtc_id -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        kind -> Idempotency_key_kind,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::db::sql::*;
@@ -37,6 +65,7 @@ table! {
         api_version -> Text,
         group -> Nullable<Text>,
         janus_url -> Text,
+        draining -> Bool,
     }
 }
 
@@ -53,6 +82,19 @@ table! {
         sent_by -> Agent_id,
         time -> Nullable<Tstzrange>,
         created_at -> Timestamptz,
+        slow_link_count -> Int4,
+        media_loss_count -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::db::sql::*;
+
+    raised_hand (room_id, agent_id) {
+        room_id -> Uuid,
+        agent_id -> Agent_id,
+        raised_at -> Timestamptz,
     }
 }
 
@@ -84,6 +126,27 @@ table! {
         backend_id -> Nullable<Agent_id>,
         rtc_sharing_policy -> Rtc_sharing_policy,
         classroom_id -> Nullable<Uuid>,
+        closed_at -> Nullable<Timestamptz>,
+        closing_warning_offset -> Nullable<Int4>,
+        locked -> Bool,
+        requires_admission -> Bool,
+        max_agents -> Nullable<Int4>,
+        parent_id -> Nullable<Uuid>,
+        stage_mode -> Bool,
+        quality_preset -> Nullable<Quality_preset>,
+        video_remb -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::db::sql::*;
+
+    room_ban (room_id, account_id) {
+        room_id -> Uuid,
+        account_id -> Account_id,
+        banned_by -> Agent_id,
+        created_at -> Timestamptz,
     }
 }
 
@@ -137,26 +200,50 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::db::sql::*;
+
+    stage_member (room_id, agent_id) {
+        room_id -> Uuid,
+        agent_id -> Agent_id,
+        status -> Stage_member_status,
+        invited_by -> Agent_id,
+        created_at -> Timestamptz,
+    }
+}
+
 joinable!(agent -> room (room_id));
 joinable!(agent_connection -> agent (agent_id));
 joinable!(agent_connection -> rtc (rtc_id));
+joinable!(agent_session -> room (room_id));
+joinable!(idempotency_key -> room (room_id));
+joinable!(idempotency_key -> rtc (rtc_id));
 joinable!(janus_rtc_stream -> janus_backend (backend_id));
 joinable!(janus_rtc_stream -> rtc (rtc_id));
+joinable!(raised_hand -> room (room_id));
 joinable!(recording -> rtc (rtc_id));
+joinable!(room_ban -> room (room_id));
 joinable!(rtc -> room (room_id));
 joinable!(rtc_reader_config -> rtc (rtc_id));
 joinable!(rtc_writer_config -> rtc (rtc_id));
 joinable!(rtc_writer_config_snapshot -> rtc (rtc_id));
+joinable!(stage_member -> room (room_id));
 
 allow_tables_to_appear_in_same_query!(
     agent,
     agent_connection,
+    agent_session,
+    idempotency_key,
     janus_backend,
     janus_rtc_stream,
+    raised_hand,
     recording,
     room,
+    room_ban,
     rtc,
     rtc_reader_config,
     rtc_writer_config,
     rtc_writer_config_snapshot,
+    stage_member,
 );
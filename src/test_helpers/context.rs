@@ -13,7 +13,10 @@ use crate::{
         context::{Context, GlobalContext, JanusTopics, MessageContext},
         metrics::Metrics,
     },
-    backend::janus::{client::IncomingEvent, client_pool::Clients},
+    backend::janus::{
+        client::IncomingEvent, client_pool::Clients, metrics::PollerMetrics,
+        transaction_watchdog::TransactionWatchdog,
+    },
     config::Config,
     db::ConnectionPool as Db,
 };
@@ -67,6 +70,9 @@ fn build_config() -> Config {
             }
         },
         "max_room_duration": 7,
+        "room_closing": {
+            "warning_offsets": [600, 120],
+        },
     });
 
     serde_json::from_value::<Config>(config).expect("Failed to parse test config")
@@ -104,7 +110,9 @@ impl TestContext {
     }
 
     pub fn with_janus(&mut self, events_sink: Sender<IncomingEvent>) {
-        self.clients = Some(Clients::new(events_sink, None));
+        let metrics = PollerMetrics::new(&Registry::new()).unwrap();
+        let watchdog = TransactionWatchdog::new(&self.config.backend);
+        self.clients = Some(Clients::new(events_sink, None, metrics, watchdog));
     }
 
     pub fn config_mut(&mut self) -> &mut Config {
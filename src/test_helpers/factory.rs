@@ -1,5 +1,6 @@
 use diesel::pg::PgConnection;
 use rand::Rng;
+use serde_json::Value as JsonValue;
 use svc_agent::{AccountId, AgentId};
 
 use crate::{
@@ -20,6 +21,8 @@ pub struct Room<'a> {
     rtc_sharing_policy: db::rtc::SharingPolicy,
     backend_id: Option<&'a AgentId>,
     reserve: Option<i32>,
+    tags: Option<&'a JsonValue>,
+    quality_preset: Option<(db::room::QualityPreset, u32)>,
 }
 
 impl<'a> Room<'a> {
@@ -30,6 +33,8 @@ impl<'a> Room<'a> {
             rtc_sharing_policy: db::rtc::SharingPolicy::None,
             backend_id: None,
             reserve: None,
+            tags: None,
+            quality_preset: None,
         }
     }
 
@@ -54,6 +59,13 @@ impl<'a> Room<'a> {
         }
     }
 
+    pub fn tags(self, tags: &'a JsonValue) -> Self {
+        Self {
+            tags: Some(tags),
+            ..self
+        }
+    }
+
     pub fn rtc_sharing_policy(self, rtc_sharing_policy: db::rtc::SharingPolicy) -> Self {
         Self {
             rtc_sharing_policy,
@@ -68,6 +80,13 @@ impl<'a> Room<'a> {
         }
     }
 
+    pub fn quality_preset(self, quality_preset: db::room::QualityPreset, video_remb: u32) -> Self {
+        Self {
+            quality_preset: Some((quality_preset, video_remb)),
+            ..self
+        }
+    }
+
     pub fn insert(self, conn: &PgConnection) -> db::room::Object {
         let audience = self.audience.expect("Audience not set");
         let time = self.time.expect("Time not set");
@@ -82,6 +101,14 @@ impl<'a> Room<'a> {
             q = q.reserve(reserve);
         }
 
+        if let Some(tags) = self.tags {
+            q = q.tags(tags);
+        }
+
+        if let Some((quality_preset, video_remb)) = self.quality_preset {
+            q = q.quality_preset(quality_preset, video_remb);
+        }
+
         q.execute(conn).expect("Failed to insert room")
     }
 }
@@ -283,6 +310,20 @@ impl<'a> JanusRtcStream<'a> {
         }
     }
 
+    pub fn backend(self, backend: &'a db::janus_backend::Object) -> Self {
+        Self {
+            backend: Some(backend),
+            ..self
+        }
+    }
+
+    pub fn rtc(self, rtc: &'a db::rtc::Object) -> Self {
+        Self {
+            rtc: Some(rtc),
+            ..self
+        }
+    }
+
     pub fn insert(&self, conn: &PgConnection) -> db::janus_rtc_stream::Object {
         let default_backend;
 
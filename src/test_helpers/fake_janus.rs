@@ -0,0 +1,416 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use serde_json::{json, Value};
+
+use crate::backend::janus::client::{HandleId, SessionId};
+
+// Long-polling requests get a keep-alive if there are no events during this period.
+const LONG_POLL_TIMEOUT: Duration = Duration::from_secs(1);
+const LONG_POLL_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+const MAX_EVENTS: usize = 5;
+
+const PLUGIN: &str = "janus.plugin.conference";
+
+// https://janus.conf.meetecho.com/docs/rest.html#errors
+const JANUS_ERROR_UNKNOWN_REQUEST: u64 = 453;
+const JANUS_ERROR_SESSION_NOT_FOUND: u64 = 458;
+const JANUS_ERROR_HANDLE_NOT_FOUND: u64 = 459;
+const JANUS_ERROR_PLUGIN_MESSAGE: u64 = 464;
+
+const SDP_ANSWER: &str = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\n";
+
+/// A failure to inject into the next plugin request with the given method.
+#[derive(Debug, Clone, Copy)]
+pub enum FakeJanusFailure {
+    /// Janus responds to the request with an error instead of `ack`.
+    Error,
+    /// The request is acknowledged but the plugin responds with a non-200 status.
+    ErrorStatus,
+}
+
+#[derive(Debug, Default)]
+struct Session {
+    handles: HashSet<HandleId>,
+    events: VecDeque<Value>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    sessions: HashMap<SessionId, Session>,
+    failures: HashMap<String, VecDeque<FakeJanusFailure>>,
+}
+
+type SharedState = Arc<Mutex<State>>;
+
+/// In-process Janus Gateway HTTP API with the conference plugin.
+///
+/// Plugin requests are acknowledged immediately and their events are delivered on long-polling.
+/// Other events may be pushed by the test.
+pub struct FakeJanus {
+    pub url: String,
+    state: SharedState,
+}
+
+impl FakeJanus {
+    pub fn start() -> Self {
+        let listener =
+            std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind fake janus");
+
+        let port = listener
+            .local_addr()
+            .expect("Failed to get fake janus address")
+            .port();
+
+        let state = SharedState::default();
+        let mut app = tide::with_state(state.clone());
+        app.at("/janus").post(handle_request);
+        app.at("/janus/:session_id").get(handle_long_poll);
+
+        async_std::task::spawn(async move {
+            app.listen(listener)
+                .await
+                .expect("Fake janus server failed");
+        });
+
+        Self {
+            url: format!("http://127.0.0.1:{}/janus", port),
+            state,
+        }
+    }
+
+    pub fn push_event(&self, session_id: SessionId, event: Value) {
+        if let Some(session) = self.lock().sessions.get_mut(&session_id) {
+            session.events.push_back(event);
+        }
+    }
+
+    pub fn webrtcup(&self, session_id: SessionId, handle_id: HandleId, opaque_id: &str) {
+        let event = json!({
+            "janus": "webrtcup",
+            "session_id": session_id,
+            "sender": handle_id,
+            "opaque_id": opaque_id,
+        });
+
+        self.push_event(session_id, event);
+    }
+
+    pub fn hangup(&self, session_id: SessionId, handle_id: HandleId, opaque_id: &str) {
+        let event = json!({
+            "janus": "hangup",
+            "session_id": session_id,
+            "sender": handle_id,
+            "opaque_id": opaque_id,
+            "reason": "DTLS alert",
+        });
+
+        self.push_event(session_id, event);
+    }
+
+    pub fn detached(&self, session_id: SessionId, handle_id: HandleId, opaque_id: &str) {
+        let event = json!({
+            "janus": "detached",
+            "session_id": session_id,
+            "sender": handle_id,
+            "opaque_id": opaque_id,
+        });
+
+        self.push_event(session_id, event);
+    }
+
+    /// Makes the next plugin request with `method` (e.g. `stream.create`) fail.
+    pub fn fail_next(&self, method: &str, failure: FakeJanusFailure) {
+        self.lock()
+            .failures
+            .entry(method.to_owned())
+            .or_default()
+            .push_back(failure);
+    }
+
+    /// Drops the session like Janus does on timeout.
+    pub fn destroy_session(&self, session_id: SessionId) {
+        self.lock().sessions.remove(&session_id);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<State> {
+        self.state.lock().expect("Must not panic")
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+async fn handle_request(mut req: tide::Request<SharedState>) -> tide::Result {
+    let request: Value = req.body_json().await?;
+    let response = {
+        let mut state = req.state().lock().expect("Must not panic");
+        handle_janus_request(&mut state, &request)
+    };
+
+    json_response(&response)
+}
+
+async fn handle_long_poll(req: tide::Request<SharedState>) -> tide::Result {
+    let session_id = req.param("session_id")?.parse::<SessionId>()?;
+    let deadline = Instant::now() + LONG_POLL_TIMEOUT;
+
+    loop {
+        {
+            let mut state = req.state().lock().expect("Must not panic");
+
+            let session = match state.sessions.get_mut(&session_id) {
+                Some(session) => session,
+                None => return Ok(tide::Response::new(404)),
+            };
+
+            if !session.events.is_empty() {
+                let count = std::cmp::min(session.events.len(), MAX_EVENTS);
+                let events = session.events.drain(..count).collect::<Vec<_>>();
+                return json_response(&Value::Array(events));
+            }
+        }
+
+        if Instant::now() >= deadline {
+            return json_response(&json!([{ "janus": "keepalive" }]));
+        }
+
+        async_std::task::sleep(LONG_POLL_CHECK_INTERVAL).await;
+    }
+}
+
+fn json_response(body: &Value) -> tide::Result {
+    Ok(tide::Response::builder(200)
+        .body(tide::Body::from_json(body)?)
+        .build())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn handle_janus_request(state: &mut State, request: &Value) -> Value {
+    let transaction = request.get("transaction").cloned().unwrap_or(Value::Null);
+
+    let maybe_session_id = request
+        .get("session_id")
+        .and_then(|v| serde_json::from_value::<SessionId>(v.clone()).ok());
+
+    let maybe_handle_id = request
+        .get("handle_id")
+        .and_then(|v| serde_json::from_value::<HandleId>(v.clone()).ok());
+
+    let kind = request.get("janus").and_then(|v| v.as_str()).unwrap_or("");
+
+    if kind == "create" {
+        let session_id = SessionId::random();
+        state.sessions.insert(session_id, Session::default());
+
+        return json!({
+            "janus": "success",
+            "transaction": transaction,
+            "data": { "id": session_id },
+        });
+    }
+
+    let session_id = match maybe_session_id {
+        Some(session_id) if state.sessions.contains_key(&session_id) => session_id,
+        _ => {
+            return error(
+                &transaction,
+                JANUS_ERROR_SESSION_NOT_FOUND,
+                "No such session",
+            )
+        }
+    };
+
+    let session = state
+        .sessions
+        .get_mut(&session_id)
+        .expect("Session must exist");
+
+    match kind {
+        "attach" => {
+            let handle_id = HandleId::random();
+            session.handles.insert(handle_id);
+
+            json!({
+                "janus": "success",
+                "session_id": session_id,
+                "transaction": transaction,
+                "data": { "id": handle_id },
+            })
+        }
+        "claim" => json!({
+            "janus": "success",
+            "session_id": session_id,
+            "transaction": transaction,
+        }),
+        "keepalive" | "trickle" => ack(session_id, &transaction),
+        "detach" => match maybe_handle_id {
+            Some(handle_id) if session.handles.remove(&handle_id) => json!({
+                "janus": "success",
+                "session_id": session_id,
+                "transaction": transaction,
+            }),
+            _ => error(&transaction, JANUS_ERROR_HANDLE_NOT_FOUND, "No such handle"),
+        },
+        "hangup" => match maybe_handle_id {
+            Some(handle_id) if session.handles.contains(&handle_id) => json!({
+                "janus": "success",
+                "session_id": session_id,
+                "transaction": transaction,
+            }),
+            _ => error(&transaction, JANUS_ERROR_HANDLE_NOT_FOUND, "No such handle"),
+        },
+        "message" => {
+            let handle_id = match maybe_handle_id {
+                Some(handle_id) if session.handles.contains(&handle_id) => handle_id,
+                _ => return error(&transaction, JANUS_ERROR_HANDLE_NOT_FOUND, "No such handle"),
+            };
+
+            let body = request.get("body").cloned().unwrap_or(Value::Null);
+            let method = body.get("method").and_then(|v| v.as_str()).unwrap_or("");
+
+            let maybe_failure = state
+                .failures
+                .get_mut(method)
+                .and_then(|failures| failures.pop_front());
+
+            let session = state
+                .sessions
+                .get_mut(&session_id)
+                .expect("Session must exist");
+
+            let (data, jsep) = match maybe_failure {
+                Some(FakeJanusFailure::Error) => {
+                    return error(&transaction, JANUS_ERROR_PLUGIN_MESSAGE, "Injected failure");
+                }
+                Some(FakeJanusFailure::ErrorStatus) => (
+                    json!({ "status": "500", "reason": "Injected failure" }),
+                    None,
+                ),
+                None => plugin_response(method, &body),
+            };
+
+            let mut event = json!({
+                "janus": "event",
+                "session_id": session_id,
+                "sender": handle_id,
+                "transaction": transaction,
+                "plugindata": { "plugin": PLUGIN, "data": data },
+            });
+
+            if let Some(jsep) = jsep {
+                event["jsep"] = jsep;
+            }
+
+            session.events.push_back(event);
+            ack(session_id, &transaction)
+        }
+        _ => error(&transaction, JANUS_ERROR_UNKNOWN_REQUEST, "Unknown request"),
+    }
+}
+
+// Successful plugin data and JSEP for a conference plugin method.
+fn plugin_response(method: &str, body: &Value) -> (Value, Option<Value>) {
+    match method {
+        "stream.create" | "stream.read" => (
+            json!({ "status": "200" }),
+            Some(json!({ "type": "answer", "sdp": SDP_ANSWER })),
+        ),
+        "stream.upload" => {
+            let started_at = Utc::now().timestamp_millis();
+
+            let data = json!({
+                "status": "200",
+                "id": body.get("id").cloned().unwrap_or(Value::Null),
+                "started_at": started_at,
+                "time": [[started_at, started_at + 1000]],
+                "mjr_dumps_uris": [],
+            });
+
+            (data, None)
+        }
+        _ => (json!({ "status": "200" }), None),
+    }
+}
+
+fn ack(session_id: SessionId, transaction: &Value) -> Value {
+    json!({
+        "janus": "ack",
+        "session_id": session_id,
+        "transaction": transaction,
+    })
+}
+
+fn error(transaction: &Value, code: u64, reason: &str) -> Value {
+    json!({
+        "janus": "error",
+        "transaction": transaction,
+        "error": { "code": code, "reason": reason },
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::janus::client::{
+        create_handle::CreateHandleRequest, IncomingEvent, JanusClient, PollResult,
+    };
+    use crate::db;
+
+    use super::*;
+
+    async fn poll_event(client: &JanusClient, session_id: SessionId) -> IncomingEvent {
+        loop {
+            match client.poll(session_id).await.expect("Failed to poll") {
+                PollResult::Events(events) => {
+                    if let Some(event) = events
+                        .into_iter()
+                        .find(|event| !matches!(event, IncomingEvent::KeepAlive))
+                    {
+                        return event;
+                    }
+                }
+                PollResult::SessionNotFound => panic!("Session not found"),
+            }
+        }
+    }
+
+    #[async_std::test]
+    async fn push_scripted_events() {
+        let janus = FakeJanus::start();
+        let client = JanusClient::new(&janus.url).expect("Failed to build janus client");
+        let session_id = client.create_session().await.unwrap().id;
+
+        let handle_id = client
+            .create_handle(CreateHandleRequest {
+                session_id,
+                opaque_id: db::janus_rtc_stream::Id::random(),
+            })
+            .await
+            .unwrap()
+            .id;
+
+        janus.webrtcup(session_id, handle_id, "opaque");
+
+        match poll_event(&client, session_id).await {
+            IncomingEvent::WebRtcUp(event) => assert_eq!(event.opaque_id, "opaque"),
+            other => panic!("Expected webrtcup event, got {:?}", other),
+        }
+    }
+
+    #[async_std::test]
+    async fn report_destroyed_session() {
+        let janus = FakeJanus::start();
+        let client = JanusClient::new(&janus.url).expect("Failed to build janus client");
+        let session_id = client.create_session().await.unwrap().id;
+        janus.destroy_session(session_id);
+
+        let result = client.poll(session_id).await.expect("Failed to poll");
+        assert!(matches!(result, PollResult::SessionNotFound));
+    }
+}
@@ -60,7 +60,7 @@ pub async fn handle_event<H: EventHandler>(
     Ok(parse_messages(messages).await)
 }
 
-async fn parse_messages(mut messages: MessageStream) -> Vec<OutgoingEnvelope> {
+pub async fn parse_messages(mut messages: MessageStream) -> Vec<OutgoingEnvelope> {
     let mut parsed_messages = vec![];
 
     while let Some(message) = messages.next().await {
@@ -230,6 +230,7 @@ pub mod authz;
 pub mod context;
 pub mod db;
 pub mod factory;
+pub mod fake_janus;
 pub mod outgoing_envelope;
 pub mod shared_helpers;
 pub mod test_deps;
@@ -21,7 +21,7 @@ use crate::{
     diesel::Identifiable,
 };
 
-use super::{agent::TestAgent, factory, SVC_AUDIENCE, USR_AUDIENCE};
+use super::{agent::TestAgent, db::TestDb, factory, SVC_AUDIENCE, USR_AUDIENCE};
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -138,6 +138,31 @@ pub async fn init_janus(janus_url: &str) -> (SessionId, HandleId) {
     (session_id, handle)
 }
 
+// Creates a session on the Janus at `janus_url` and inserts its backend with a room and an rtc.
+// The agent gets connected to the rtc through its own Janus handle.
+pub async fn insert_connected_to_janus_agent(
+    db: &TestDb,
+    janus_url: &str,
+    agent_id: &AgentId,
+) -> (JanusBackend, Rtc, AgentConnection) {
+    let (session_id, handle_id) = init_janus(janus_url).await;
+    let user_handle = create_handle(janus_url, session_id).await;
+
+    let conn = db
+        .connection_pool()
+        .get()
+        .expect("Failed to get DB connection");
+
+    let backend = insert_janus_backend(&conn, janus_url, session_id, handle_id);
+    let room = insert_room_with_backend_id(&conn, backend.id());
+    let rtc = insert_rtc_with_room(&conn, &room);
+
+    let (_, agent_connection) =
+        insert_connected_to_handle_agent(&conn, agent_id, room.id(), rtc.id(), user_handle);
+
+    (backend, rtc, agent_connection)
+}
+
 pub fn insert_connected_to_handle_agent(
     conn: &PgConnection,
     agent_id: &AgentId,
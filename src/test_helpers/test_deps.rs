@@ -1,9 +1,6 @@
-use testcontainers::{clients, core::WaitFor, images, Container, Image};
+use testcontainers::{clients, images, Container};
 
-pub struct JanusHandle<'a> {
-    pub url: String,
-    _container: Container<'a, images::generic::GenericImage>,
-}
+use super::fake_janus::FakeJanus;
 
 pub struct PostgresHandle<'a> {
     pub connection_string: String,
@@ -34,19 +31,7 @@ impl LocalDeps {
         }
     }
 
-    pub fn run_janus(&self) -> JanusHandle {
-        let base_dir = env!("CARGO_MANIFEST_DIR");
-        let image = images::generic::GenericImage::new("foxford/janus-gateway:v0.8.11")
-            .with_volume(
-                format!("{}/{}", base_dir, "src/test_helpers/janus_confs/"),
-                "/opt/janus/etc/janus/",
-            )
-            .with_entrypoint("/opt/janus/bin/janus")
-            .with_wait_for(WaitFor::message_on_stdout("HTTP webserver started"));
-        let node = self.docker.run(image);
-        JanusHandle {
-            url: format!("http://localhost:{}/janus", node.get_host_port(8088)),
-            _container: node,
-        }
+    pub fn run_janus(&self) -> FakeJanus {
+        FakeJanus::start()
     }
 }